@@ -1,5 +1,42 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos(r#"protos/cage.proto"#)?;
     tonic_build::compile_protos(r#"protos/salmon.proto"#)?;
+    build_info();
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Exposes the build to `env!`, read by the version endpoint.
+///
+/// Git information is left empty when building outside of a work tree.
+fn build_info() {
+    let git = |args: &[&str]| Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned());
+    let commit = git(&["rev-parse", "HEAD"]).unwrap_or_default();
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"])
+        .map_or(false, |status| !status.is_empty());
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let rustc = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        .unwrap_or_default();
+    let built_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+
+    println!("cargo:rustc-env=MATATABI_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=MATATABI_GIT_DIRTY={}", dirty);
+    println!("cargo:rustc-env=MATATABI_RUSTC_VERSION={}", rustc);
+    println!("cargo:rustc-env=MATATABI_BUILT_AT={}", built_at);
+    for path in ["build.rs", "protos", ".git/HEAD", ".git/refs", ".git/index"] {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+}
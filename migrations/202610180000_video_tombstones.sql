@@ -0,0 +1,10 @@
+-- DROP TABLE video_tombstones;
+CREATE TABLE video_tombstones (
+    -- Videos deleted by the collector are kept here,
+    -- so that feeds can publish them as cancellations.
+    video_id VARCHAR(11) NOT NULL PRIMARY KEY,
+    channel_id VARCHAR(24),
+    title VARCHAR(255) NOT NULL,
+    will_start_at TIMESTAMPTZ NULL,
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,20 @@
+-- Full-text and trigram indexes used by the Postgres search backend.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Titles and descriptions are mixed Japanese and English,
+-- so they are tokenized without language specific stemming.
+ALTER TABLE videos ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple'::REGCONFIG, coalesce(title, '')), 'A') ||
+    setweight(to_tsvector('simple'::REGCONFIG, coalesce(description, '')), 'B')
+) STORED;
+CREATE INDEX videos_search_vector_idx ON videos USING GIN (search_vector);
+CREATE INDEX videos_title_trgm_idx ON videos USING GIN (title gin_trgm_ops);
+
+ALTER TABLE channels ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    to_tsvector('simple'::REGCONFIG, coalesce(description, ''))
+) STORED;
+CREATE INDEX channels_search_vector_idx ON channels USING GIN (search_vector);
+
+CREATE INDEX livers_name_trgm_idx ON livers USING GIN (name gin_trgm_ops);
+CREATE INDEX livers_localized_name_trgm_idx ON livers USING GIN (localized_name gin_trgm_ops);
+CREATE INDEX affiliations_name_trgm_idx ON affiliations USING GIN (name gin_trgm_ops);
@@ -0,0 +1,9 @@
+-- Normalised readings of liver names, in romaji, written by the Postgres search backend.
+-- Transliteration is done by the application, so this can not be a generated column.
+CREATE TABLE liver_readings (
+    liver_id BIGINT NOT NULL PRIMARY KEY,
+    reading TEXT NOT NULL,
+
+    FOREIGN KEY (liver_id) REFERENCES livers(liver_id) ON DELETE CASCADE
+);
+CREATE INDEX liver_readings_reading_trgm_idx ON liver_readings USING GIN (reading gin_trgm_ops);
@@ -0,0 +1,13 @@
+-- Names of affiliations, livers and channels per locale, such as 'ja', 'en' or 'id'.
+-- `livers.name` and `livers.localized_name` are kept as the Japanese and English names of last resort.
+CREATE TABLE localized_names (
+    -- 'affiliation', 'liver' or 'channel'.
+    entity VARCHAR(16) NOT NULL,
+    -- Primary key of the named row, as text.
+    entity_id VARCHAR(24) NOT NULL,
+    -- Lowercase BCP 47 language tag.
+    locale VARCHAR(35) NOT NULL,
+    name VARCHAR(128) NOT NULL,
+
+    PRIMARY KEY (entity, entity_id, locale)
+);
@@ -0,0 +1,3 @@
+-- Channels sent without a publishing date used to get the epoch, keep them as NULL instead.
+ALTER TABLE channels ALTER COLUMN published_at DROP NOT NULL;
+UPDATE channels SET published_at = NULL WHERE published_at = TIMESTAMPTZ 'epoch';
@@ -0,0 +1,13 @@
+-- Keys of REST api clients. Only the SHA-256 of a key is kept, the key itself is shown once when issued.
+CREATE TABLE api_keys (
+    key_id BIGSERIAL NOT NULL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    -- Leading characters of the key, to tell keys apart without the key itself.
+    prefix VARCHAR(16) NOT NULL,
+    key_hash CHAR(64) NOT NULL UNIQUE,
+    -- Token bucket refilled by `rate_per_minute` tokens a minute, holding up to `burst` tokens.
+    rate_per_minute INTEGER NOT NULL CHECK (rate_per_minute > 0),
+    burst INTEGER NOT NULL CHECK (burst > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at TIMESTAMPTZ NULL
+);
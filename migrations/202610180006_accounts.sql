@@ -0,0 +1,11 @@
+-- Accounts of the Cage service. Passwords are kept as argon2 PHC strings.
+CREATE TYPE account_status AS ENUM ('active', 'inactive', 'blocked');
+
+CREATE TABLE accounts (
+    account_id BIGSERIAL NOT NULL PRIMARY KEY,
+    user_name VARCHAR(64) NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    status account_status NOT NULL DEFAULT 'active',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
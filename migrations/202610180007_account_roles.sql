@@ -0,0 +1,7 @@
+-- Roles of Cage accounts. `affiliation_scope` limits writes to livers, channels and videos of those affiliations,
+-- NULL leaves the account unscoped.
+CREATE TYPE account_role AS ENUM ('reader', 'collector', 'curator', 'admin');
+
+ALTER TABLE accounts
+    ADD COLUMN role account_role NOT NULL DEFAULT 'reader',
+    ADD COLUMN affiliation_scope BIGINT[];
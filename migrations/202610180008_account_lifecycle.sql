@@ -0,0 +1,22 @@
+-- Bumped when every token of an account is revoked, such as on a password change.
+ALTER TABLE accounts ADD COLUMN token_version INTEGER NOT NULL DEFAULT 0;
+
+-- Refresh tokens are only kept as their SHA-256 in hex, and are revoked when exchanged.
+CREATE TABLE refresh_tokens (
+    token_id BIGSERIAL NOT NULL PRIMARY KEY,
+    account_id BIGINT NOT NULL REFERENCES accounts (account_id) ON DELETE CASCADE,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX refresh_tokens_account_id_idx ON refresh_tokens (account_id);
+
+-- Access tokens revoked before they expire, by the `jti` claim. Rows are dropped once the token has expired.
+CREATE TABLE revoked_tokens (
+    jti VARCHAR(64) NOT NULL PRIMARY KEY,
+    account_id BIGINT NOT NULL REFERENCES accounts (account_id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,2 @@
+-- Common name of the client certificate a collector authenticates with over mutual TLS, instead of a password.
+ALTER TABLE accounts ADD COLUMN certificate_subject VARCHAR(256) UNIQUE;
@@ -0,0 +1,22 @@
+-- Salmon batches and how they ended, listed by the admin console.
+CREATE TYPE batch_outcome AS ENUM ('committed', 'denied', 'failed');
+
+CREATE TABLE ingestion_batches (
+    batch_id BIGSERIAL NOT NULL PRIMARY KEY,
+    -- Kept after the account is deleted, by name.
+    account_id BIGINT REFERENCES accounts (account_id) ON DELETE SET NULL,
+    user_name VARCHAR(64) NOT NULL,
+    -- Table the batch was sent to, such as `livers`.
+    target VARCHAR(16) NOT NULL,
+    received INTEGER NOT NULL,
+    inserted INTEGER NOT NULL DEFAULT 0,
+    updated INTEGER NOT NULL DEFAULT 0,
+    deleted INTEGER NOT NULL DEFAULT 0,
+    outcome batch_outcome NOT NULL,
+    -- Why a batch was denied or failed.
+    message TEXT NOT NULL DEFAULT '',
+    started_at TIMESTAMPTZ NOT NULL,
+    elapsed_ms BIGINT NOT NULL
+);
+
+CREATE INDEX ingestion_batches_started_at_idx ON ingestion_batches (started_at DESC);
@@ -0,0 +1,2 @@
+-- Streams that ended, so that schedules stop carrying them over into the next day.
+ALTER TABLE videos ADD COLUMN ended_at TIMESTAMPTZ;
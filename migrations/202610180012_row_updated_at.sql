@@ -0,0 +1,4 @@
+-- When affiliations, livers and channels last changed, sent by the REST api as `Last-Modified` as for videos.
+ALTER TABLE affiliations ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE livers ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE channels ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP;
@@ -0,0 +1,146 @@
+use sqlx::PgPool;
+
+use crate::database::{AccountObject, AccountRole, AffiliationId, ApiKeyObject};
+use crate::server::cage;
+
+const DEFAULT_RATE_PER_MINUTE: u32 = 600;
+const DEFAULT_BURST: u32 = 120;
+
+const API_KEY_USAGE: &str = "\
+usage: matatabi api-key issue <name> [<rate per minute> <burst>]
+       matatabi api-key list
+       matatabi api-key quota <key id> <rate per minute> <burst>
+       matatabi api-key revoke <key id>";
+
+const ACCOUNT_USAGE: &str = "\
+usage: matatabi account create <user name>
+       matatabi account list
+       matatabi account role <user name> <reader|collector|curator|admin> [<affiliation id>...]
+       matatabi account certificate <user name> [<common name>]";
+
+/// `matatabi api-key ...`, managing keys of REST api clients.
+///
+/// Quotas and revocations reach a running server within a minute, when its cache of keys expires.
+pub async fn api_key(args: &[String], pool: &PgPool) -> Result<(), String> {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match args.as_slice() {
+        ["issue", name] => issue(name, DEFAULT_RATE_PER_MINUTE, DEFAULT_BURST, pool).await,
+        ["issue", name, rate, burst] => issue(name, positive(rate)?, positive(burst)?, pool).await,
+        ["list"] => {
+            let keys = ApiKeyObject::fetch_all(pool).await.map_err(|e| e.to_string())?;
+            println!("{:>6}  {:<24} {:<14} {:>8} {:>6}  status", "id", "name", "prefix", "rate/min", "burst");
+            for key in keys {
+                let status = match key.revoked_at() {
+                    Some(at) => format!("revoked at {}", at.to_rfc3339()),
+                    None => "active".to_owned()
+                };
+                println!("{:>6}  {:<24} {:<14} {:>8} {:>6}  {}",
+                    key.key_id(), key.name(), key.prefix(), key.rate_per_minute(), key.burst(), status);
+            }
+            Ok(())
+        }
+        ["quota", id, rate, burst] => {
+            let updated = ApiKeyObject::update_quota(key_id(id)?, positive(rate)?, positive(burst)?, pool).await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("api key {} is not found.", id))?;
+            println!("{}: {} per minute, burst of {}", updated, updated.rate_per_minute(), updated.burst());
+            Ok(())
+        }
+        ["revoke", id] => {
+            let revoked = ApiKeyObject::revoke(key_id(id)?, pool).await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("api key {} is not found.", id))?;
+            println!("{}: revoked", revoked);
+            Ok(())
+        }
+        _ => Err(API_KEY_USAGE.to_owned())
+    }
+}
+
+/// `matatabi account ...`, managing roles of Cage accounts.
+///
+/// Accounts are created here or by an admin with `createAccount`, the password of `create` is read from the standard input.
+/// A role given with affiliation ids only writes livers, channels and videos of those affiliations.
+/// A client certificate mapped to an account authenticates as it over mutual TLS, without the common name the mapping is removed.
+pub async fn account(args: &[String], pool: &PgPool) -> Result<(), String> {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match args.as_slice() {
+        ["create", name] => {
+            let name = cage::check_user_name(name).map_err(|e| e.message().to_owned())?;
+            let mut password = String::new();
+            std::io::stdin().read_line(&mut password).map_err(|e| e.to_string())?;
+            let password = password.trim_end_matches(&['\r', '\n'][..]).to_owned();
+            cage::check_password("password", &password).map_err(|e| e.message().to_owned())?;
+            let password_hash = cage::hash_password(password).await.map_err(|e| e.message().to_owned())?;
+            let created = AccountObject::create(&name, &password_hash, pool).await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("{} is already taken.", name))?;
+            println!("{}", created);
+            Ok(())
+        }
+        ["list"] => {
+            let accounts = AccountObject::fetch_all(pool).await.map_err(|e| e.to_string())?;
+            println!("{:>6}  {:<24} {:<10} {:<9} {:<24} scope", "id", "name", "role", "status", "certificate");
+            for account in accounts {
+                let scope = match account.affiliation_scope() {
+                    Some(scope) => scope.iter().map(|id| i64::from(*id).to_string()).collect::<Vec<_>>().join(","),
+                    None => "all".to_owned()
+                };
+                println!("{:>6}  {:<24} {:<10} {:<9} {:<24} {}",
+                    account.account_id(), account.user_name(), account.role().name(),
+                    format!("{:?}", account.status()).to_lowercase(), account.certificate_subject().unwrap_or("-"), scope);
+            }
+            Ok(())
+        }
+        ["role", name, role, scope @ ..] => {
+            let role = role.parse::<AccountRole>()?;
+            let scope = scope.iter()
+                .map(|id| id.parse::<i64>()
+                    .map(AffiliationId::from)
+                    .map_err(|_| format!("{} is not a valid affiliation id.", id)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let account = AccountObject::fetch_from_name(name, pool).await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("account {} is not found.", name))?;
+            let scope = (!scope.is_empty()).then_some(scope.as_slice());
+            let updated = AccountObject::update_role(account.account_id(), role, scope, pool).await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("account {} is not found.", name))?;
+            println!("{}", updated);
+            Ok(())
+        }
+        ["certificate", name, common_name @ ..] if common_name.len() <= 1 => {
+            let account = AccountObject::fetch_from_name(name, pool).await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("account {} is not found.", name))?;
+            let updated = AccountObject::update_certificate_subject(account.account_id(), common_name.first().copied(), pool).await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("account {} is not found.", name))?;
+            match updated.certificate_subject() {
+                Some(subject) => println!("{}: certificate {}", updated, subject),
+                None => println!("{}: no certificate", updated)
+            }
+            Ok(())
+        }
+        _ => Err(ACCOUNT_USAGE.to_owned())
+    }
+}
+
+async fn issue(name: &str, rate_per_minute: u32, burst: u32, pool: &PgPool) -> Result<(), String> {
+    let (issued, key) = ApiKeyObject::issue(name, rate_per_minute, burst, pool).await
+        .map_err(|e| e.to_string())?;
+    println!("{}: {} per minute, burst of {}", issued, issued.rate_per_minute(), issued.burst());
+    println!("{}", key);
+    eprintln!("The key is not shown again, only its hash is stored.");
+    Ok(())
+}
+
+fn key_id(id: &str) -> Result<i64, String> {
+    id.parse().map_err(|_| format!("{} is not a valid key id.", id))
+}
+
+fn positive(value: &str) -> Result<u32, String> {
+    value.parse().ok()
+        .filter(|value| *value > 0)
+        .ok_or_else(|| format!("{} is not a positive number.", value))
+}
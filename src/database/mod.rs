@@ -8,8 +8,17 @@ pub use self::{
         livers_object::LiverObject,
         channel_object::{ChannelObject, InitChannelObject},
         upcoming_object::{VideoObject, InitVideoObject},
+        schedule_object::{ScheduleFilter, ScheduledVideoObject, VideoFeed, VideoStatus, VideoTombstoneObject},
+        search_object::{self, AffiliationCountObject},
+        name_object::{with_names, LocalizedNames, DEFAULT_LOCALES},
+        api_key_object::ApiKeyObject,
+        account_object::{AccountObject, AccountRole, AccountStatus},
+        token_object::{RefreshTokenObject, RevokedTokenObject, REFRESH_TOKEN_PREFIX},
+        batch_object::{BatchOutcome, IngestionBatchObject, InitIngestionBatchObject},
 
         Fetch,
-        Accessor
+        Accessor,
+        Scoped,
+        Table
     },
 };
\ No newline at end of file
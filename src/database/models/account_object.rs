@@ -0,0 +1,259 @@
+#![allow(dead_code)]
+
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::Postgres;
+
+use super::id_object::AffiliationId;
+
+/// Whether an account may use the apis, stored as the `account_status` enum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type)]
+#[sqlx(type_name = "account_status", rename_all = "lowercase")]
+pub enum AccountStatus {
+    Active,
+    Inactive,
+    Blocked
+}
+
+/// What an account may write, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, sqlx::Type)]
+#[sqlx(type_name = "account_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AccountRole {
+    /// Reads only.
+    Reader,
+    /// Inserts and updates livers, channels and videos.
+    Collector,
+    /// Also deletes them.
+    Curator,
+    /// Also writes affiliations.
+    Admin
+}
+
+impl AccountRole {
+    pub const ALL: [AccountRole; 4] = [AccountRole::Reader, AccountRole::Collector, AccountRole::Curator, AccountRole::Admin];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AccountRole::Reader => "reader",
+            AccountRole::Collector => "collector",
+            AccountRole::Curator => "curator",
+            AccountRole::Admin => "admin"
+        }
+    }
+}
+
+impl std::str::FromStr for AccountRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter()
+            .find(|role| role.name() == s)
+            .ok_or_else(|| format!("{} is not a role, expected one of reader, collector, curator or admin.", s))
+    }
+}
+
+/// Account of the Cage service. The password is only kept as an argon2 PHC string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
+pub struct AccountObject {
+    account_id: i64,
+    user_name: String,
+    password_hash: String,
+    status: AccountStatus,
+    role: AccountRole,
+    /// `None` for an account that is not limited to some affiliations.
+    affiliation_scope: Option<Vec<i64>>,
+    /// Tokens issued under an older version are revoked.
+    token_version: i32,
+    /// Common name of the client certificate mapped to the account.
+    certificate_subject: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>
+}
+
+impl Display for AccountObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "account >> {}: {} ({}, {:?})", self.account_id, self.user_name, self.role.name(), self.status)
+    }
+}
+
+impl AccountObject {
+    pub fn account_id(&self) -> i64 {
+        self.account_id
+    }
+
+    pub fn user_name(&self) -> &str {
+        &self.user_name
+    }
+
+    pub fn password_hash(&self) -> &str {
+        &self.password_hash
+    }
+
+    pub fn status(&self) -> AccountStatus {
+        self.status
+    }
+
+    pub fn role(&self) -> AccountRole {
+        self.role
+    }
+
+    pub fn affiliation_scope(&self) -> Option<Vec<AffiliationId>> {
+        self.affiliation_scope.as_ref()
+            .map(|scope| scope.iter().copied().map(AffiliationId::from).collect())
+    }
+
+    pub fn token_version(&self) -> i32 {
+        self.token_version
+    }
+
+    pub fn certificate_subject(&self) -> Option<&str> {
+        self.certificate_subject.as_deref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl AccountObject {
+    /// Store a new active account.
+    ///
+    /// [Ok()]: `None` - The user name is already taken.
+    pub async fn create<'a, E>(user_name: &str, password_hash: &str, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let created = sqlx::query_as::<_, Self>(r#"
+            INSERT INTO accounts (user_name, password_hash)
+            VALUES ($1, $2)
+            ON CONFLICT (user_name) DO NOTHING
+            RETURNING account_id, user_name, password_hash, status, role, affiliation_scope, token_version, certificate_subject, created_at, updated_at
+        "#).bind(user_name)
+           .bind(password_hash)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(created)
+    }
+
+    pub async fn fetch_from_id<'a, E>(account_id: i64, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let found = sqlx::query_as::<_, Self>(r#"
+            SELECT account_id, user_name, password_hash, status, role, affiliation_scope, token_version, certificate_subject, created_at, updated_at
+              FROM accounts
+             WHERE account_id = $1
+        "#).bind(account_id)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(found)
+    }
+
+    pub async fn fetch_from_name<'a, E>(user_name: &str, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let found = sqlx::query_as::<_, Self>(r#"
+            SELECT account_id, user_name, password_hash, status, role, affiliation_scope, token_version, certificate_subject, created_at, updated_at
+              FROM accounts
+             WHERE user_name = $1
+        "#).bind(user_name)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(found)
+    }
+
+    pub async fn fetch_all<'a, E>(transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let all = sqlx::query_as::<_, Self>(r#"
+            SELECT account_id, user_name, password_hash, status, role, affiliation_scope, token_version, certificate_subject, created_at, updated_at
+              FROM accounts
+             ORDER BY account_id
+        "#).fetch_all(transaction)
+           .await?;
+        Ok(all)
+    }
+
+    /// Give the account `role`, limited to `scope` unless it is `None`.
+    pub async fn update_role<'a, E>(account_id: i64, role: AccountRole, scope: Option<&[AffiliationId]>, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        let scope = scope.map(|scope| scope.iter().copied().map(i64::from).collect::<Vec<_>>());
+        // language=SQL
+        let updated = sqlx::query_as::<_, Self>(r#"
+            UPDATE accounts SET role = $2, affiliation_scope = $3, updated_at = CURRENT_TIMESTAMP
+             WHERE account_id = $1
+            RETURNING account_id, user_name, password_hash, status, role, affiliation_scope, token_version, certificate_subject, created_at, updated_at
+        "#).bind(account_id)
+           .bind(role)
+           .bind(scope)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(updated)
+    }
+
+    /// Replace the password, revoking every token issued before.
+    pub async fn update_password<'a, E>(account_id: i64, password_hash: &str, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let updated = sqlx::query_as::<_, Self>(r#"
+            UPDATE accounts
+               SET password_hash = $2, token_version = token_version + 1, updated_at = CURRENT_TIMESTAMP
+             WHERE account_id = $1
+            RETURNING account_id, user_name, password_hash, status, role, affiliation_scope, token_version, certificate_subject, created_at, updated_at
+        "#).bind(account_id)
+           .bind(password_hash)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(updated)
+    }
+
+    /// Move the account to `status`. Leaving `Active` revokes every token, so that coming back needs a login.
+    pub async fn update_status<'a, E>(account_id: i64, status: AccountStatus, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let updated = sqlx::query_as::<_, Self>(r#"
+            UPDATE accounts
+               SET status = $2,
+                   token_version = token_version + CASE WHEN $2 = 'active'::account_status THEN 0 ELSE 1 END,
+                   updated_at = CURRENT_TIMESTAMP
+             WHERE account_id = $1
+            RETURNING account_id, user_name, password_hash, status, role, affiliation_scope, token_version, certificate_subject, created_at, updated_at
+        "#).bind(account_id)
+           .bind(status)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(updated)
+    }
+
+    pub async fn fetch_from_certificate_subject<'a, E>(subject: &str, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let found = sqlx::query_as::<_, Self>(r#"
+            SELECT account_id, user_name, password_hash, status, role, affiliation_scope, token_version, certificate_subject, created_at, updated_at
+              FROM accounts
+             WHERE certificate_subject = $1
+        "#).bind(subject)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(found)
+    }
+
+    /// Map a client certificate to the account, or unmap it with `None`.
+    pub async fn update_certificate_subject<'a, E>(account_id: i64, subject: Option<&str>, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let updated = sqlx::query_as::<_, Self>(r#"
+            UPDATE accounts SET certificate_subject = $2, updated_at = CURRENT_TIMESTAMP
+             WHERE account_id = $1
+            RETURNING account_id, user_name, password_hash, status, role, affiliation_scope, token_version, certificate_subject, created_at, updated_at
+        "#).bind(account_id)
+           .bind(subject)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(updated)
+    }
+}
@@ -1,16 +1,23 @@
 #![allow(dead_code)]
 
 use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
 use sqlx::{Error, Row, Transaction};
 use sqlx::postgres::Postgres;
 
-use super::{Accessor, hash, Fetch};
+use super::{Accessor, contains_pattern, hash, Fetch, Scoped, Table};
 use super::id_object::AffiliationId;
+use super::name_object::{self, Localized, LocalizedNames, NamedEntity};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
 pub struct AffiliationObject {
     affiliation_id: AffiliationId,
     name: String,
+    /// Filled in from `localized_names`.
+    #[sqlx(default)]
+    names: LocalizedNames,
+    #[sqlx(default)]
+    updated_at: Option<DateTime<Utc>>
 }
 
 impl Display for AffiliationObject {
@@ -21,14 +28,42 @@ impl Display for AffiliationObject {
 
 impl AffiliationObject {
     pub fn new(id: impl Into<i64>, name: impl Into<String>) -> AffiliationObject {
-        Self { affiliation_id: AffiliationId::new(id), name: name.into() }
+        Self { affiliation_id: AffiliationId::new(id), name: name.into(), names: LocalizedNames::default(), updated_at: None }
+    }
+
+    pub fn with_names(self, names: LocalizedNames) -> Self {
+        Self { names, ..self }
     }
 
     pub fn affiliation_id(&self) -> AffiliationId { self.affiliation_id }
     pub fn name(&self) -> &str { &self.name }
+    pub fn names(&self) -> &LocalizedNames { &self.names }
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> { self.updated_at }
+
+    /// Name in the first of `locales` that has one, or `name`.
+    pub fn display_name(&self, locales: &[impl AsRef<str>]) -> &str {
+        self.names.pick(locales, &[]).unwrap_or(&self.name)
+    }
+}
+
+impl Localized for AffiliationObject {
+    const ENTITY: NamedEntity = NamedEntity::Affiliation;
+
+    fn entity_id(&self) -> String {
+        i64::from(self.affiliation_id).to_string()
+    }
+
+    fn names(&self) -> &LocalizedNames {
+        &self.names
+    }
+
+    fn names_mut(&mut self) -> &mut LocalizedNames {
+        &mut self.names
+    }
 }
 
 impl AffiliationObject {
+    /// Id of the affiliation whose name is the most similar to `name`.
     pub async fn fetch_id_from_name<'a, E>(
         name: impl Into<String>,
         transaction: E
@@ -36,7 +71,10 @@ impl AffiliationObject {
       where E: sqlx::Executor<'a, Database = Postgres> + Copy {
         // language=SQL
         let id = sqlx::query_as::<_, AffiliationId>(r#"
-            SELECT affiliation_id FROM affiliations WHERE name LIKE $1
+            SELECT affiliation_id FROM affiliations
+             WHERE $1 <% name OR name ILIKE '%' || $1 || '%'
+             ORDER BY word_similarity($1, name) DESC, affiliation_id
+             LIMIT 1
         "#).bind(name.into())
            .fetch_optional(transaction)
            .await?;
@@ -59,6 +97,40 @@ impl AffiliationObject {
     }
 }
 
+impl AffiliationObject {
+    pub async fn fetch_from_ids<'a, E>(ids: &[i64], transaction: E) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let searched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM affiliations WHERE affiliation_id = ANY($1)
+        "#).bind(ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(searched)
+    }
+}
+
+impl AffiliationObject {
+    /// At most `limit` affiliations whose id is `query`, or whose name contains it in any locale.
+    pub async fn fetch_matching<'a, E>(query: &str, limit: i64, transaction: E) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let matched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM affiliations
+             WHERE $1 = '' OR affiliation_id::TEXT = $1 OR name ILIKE $3 ESCAPE '\'
+                OR EXISTS(SELECT 1 FROM localized_names
+                           WHERE entity = 'affiliation' AND entity_id = affiliation_id::TEXT AND name ILIKE $3 ESCAPE '\')
+             ORDER BY affiliation_id
+             LIMIT $2
+        "#).bind(query)
+           .bind(limit)
+           .bind(contains_pattern(query))
+           .fetch_all(transaction)
+           .await?;
+        name_object::with_names(matched, transaction).await
+    }
+}
+
 #[async_trait::async_trait]
 impl Fetch for AffiliationObject {
     async fn fetch_all<'a, E>(transaction: E) -> Result<Vec<Self>, sqlx::Error>
@@ -68,12 +140,14 @@ impl Fetch for AffiliationObject {
             SELECT * FROM affiliations
         "#).fetch_all(transaction)
            .await?;
-        Ok(all)
+        name_object::with_names(all, transaction).await
     }
 }
 
 #[async_trait::async_trait]
 impl Accessor for AffiliationObject {
+    const TABLE: Table = Table::Affiliations;
+
     async fn insert(self, transaction: &mut Transaction<'_, Postgres>) -> Result<Self, Error> {
         // language=SQL
         let ins = sqlx::query_as::<_, Self>(r#"
@@ -84,7 +158,10 @@ impl Accessor for AffiliationObject {
            .bind(&self.name)
            .fetch_one(&mut *transaction)
            .await?;
-        Ok(ins)
+        if !self.names.is_empty() {
+            name_object::replace_names(&ins, &self.names, transaction).await?;
+        }
+        Ok(ins.with_names(self.names))
     }
 
     async fn delete(self, transaction: &mut Transaction<'_, Postgres>) -> Result<Self, Error> {
@@ -94,6 +171,8 @@ impl Accessor for AffiliationObject {
         "#).bind(self.affiliation_id)
            .fetch_one(&mut *transaction)
            .await?;
+        let del = name_object::attach_names(del, transaction).await?;
+        name_object::delete_names(&del, transaction).await?;
         Ok(del)
     }
 
@@ -104,16 +183,24 @@ impl Accessor for AffiliationObject {
         "#).bind(self.affiliation_id)
             .fetch_one(&mut *transaction)
             .await?;
+        let old = name_object::attach_names(old, transaction).await?;
         // language=SQL
         let update = sqlx::query_as::<_, Self>(r#"
-            UPDATE affiliations SET name = $1 WHERE affiliation_id = $2
+            UPDATE affiliations SET name = $1, updated_at = CURRENT_TIMESTAMP WHERE affiliation_id = $2
             RETURNING *
         "#).bind(&self.name)
            .bind(self.affiliation_id)
            .fetch_one(&mut *transaction)
            .await?;
-
-        Ok((old, update))
+        // Names are left as they are when absent, and removed when given as none.
+        let names = if !self.names.is_given() {
+            old.names.clone()
+        } else {
+            name_object::replace_names(&update, &self.names, transaction).await?;
+            self.names
+        };
+
+        Ok((old, update.with_names(names)))
     }
 
     async fn exists(&self, transaction: &mut Transaction<'_, Postgres>) -> Result<bool, Error> {
@@ -143,10 +230,18 @@ impl Accessor for AffiliationObject {
            .await?;
         
         let com = if let Some(db) = com {
-            let db = hash(&db);
-            let my = hash(&self);
-            db == my
+            let db = name_object::attach_names(db, transaction).await?;
+            let my = if !self.names.is_given() { self.clone().with_names(db.names.clone()) } else { self.clone() };
+            // `updated_at` is not sent, it moves whenever something else changes.
+            let my = Self { updated_at: db.updated_at, ..my };
+            hash(&db) == hash(&my)
         } else { false };
         Ok(com)
     }
-}
\ No newline at end of file
+}
+#[async_trait::async_trait]
+impl Scoped for AffiliationObject {
+    async fn affiliations(&self, _: &mut Transaction<'_, Postgres>) -> Result<Vec<Option<AffiliationId>>, Error> {
+        Ok(vec![Some(self.affiliation_id)])
+    }
+}
@@ -0,0 +1,153 @@
+#![allow(dead_code)]
+
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use sqlx::Postgres;
+
+/// Keys start with this, so that a leaked one is easy to recognize.
+const KEY_PREFIX: &str = "mtb_";
+/// Characters of a key kept in plain text to tell keys apart.
+const SHOWN_LENGTH: usize = 12;
+
+/// Key of a REST api client, with its quota.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
+pub struct ApiKeyObject {
+    key_id: i64,
+    name: String,
+    prefix: String,
+    rate_per_minute: i32,
+    burst: i32,
+    created_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>
+}
+
+impl Display for ApiKeyObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "api key >> {}: {} ({}...)", self.key_id, self.name, self.prefix)
+    }
+}
+
+impl ApiKeyObject {
+    pub fn key_id(&self) -> i64 {
+        self.key_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn rate_per_minute(&self) -> u32 {
+        self.rate_per_minute as u32
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst as u32
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn revoked_at(&self) -> Option<DateTime<Utc>> {
+        self.revoked_at
+    }
+}
+
+impl ApiKeyObject {
+    /// Store a new random key, and return it along with the stored row.
+    ///
+    /// The key can not be recovered later, as only its hash is stored.
+    pub async fn issue<'a, E>(name: &str, rate_per_minute: u32, burst: u32, transaction: E) -> Result<(Self, String), sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        let key = random_key(KEY_PREFIX, 24)?;
+        // language=SQL
+        let issued = sqlx::query_as::<_, Self>(r#"
+            INSERT INTO api_keys (name, prefix, key_hash, rate_per_minute, burst)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING key_id, name, prefix, rate_per_minute, burst, created_at, revoked_at
+        "#).bind(name)
+           .bind(&key[..SHOWN_LENGTH])
+           .bind(hash_key(&key))
+           .bind(rate_per_minute as i32)
+           .bind(burst as i32)
+           .fetch_one(transaction)
+           .await?;
+        Ok((issued, key))
+    }
+
+    /// The key that is not revoked.
+    pub async fn fetch_from_key<'a, E>(key: &str, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let found = sqlx::query_as::<_, Self>(r#"
+            SELECT key_id, name, prefix, rate_per_minute, burst, created_at, revoked_at
+              FROM api_keys
+             WHERE key_hash = $1 AND revoked_at IS NULL
+        "#).bind(hash_key(key))
+           .fetch_optional(transaction)
+           .await?;
+        Ok(found)
+    }
+
+    pub async fn fetch_all<'a, E>(transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let all = sqlx::query_as::<_, Self>(r#"
+            SELECT key_id, name, prefix, rate_per_minute, burst, created_at, revoked_at
+              FROM api_keys
+             ORDER BY key_id
+        "#).fetch_all(transaction)
+           .await?;
+        Ok(all)
+    }
+
+    pub async fn update_quota<'a, E>(key_id: i64, rate_per_minute: u32, burst: u32, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let updated = sqlx::query_as::<_, Self>(r#"
+            UPDATE api_keys SET rate_per_minute = $2, burst = $3
+             WHERE key_id = $1
+            RETURNING key_id, name, prefix, rate_per_minute, burst, created_at, revoked_at
+        "#).bind(key_id)
+           .bind(rate_per_minute as i32)
+           .bind(burst as i32)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(updated)
+    }
+
+    /// Revoking twice keeps the first revocation time.
+    pub async fn revoke<'a, E>(key_id: i64, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let revoked = sqlx::query_as::<_, Self>(r#"
+            UPDATE api_keys SET revoked_at = COALESCE(revoked_at, CURRENT_TIMESTAMP)
+             WHERE key_id = $1
+            RETURNING key_id, name, prefix, rate_per_minute, burst, created_at, revoked_at
+        "#).bind(key_id)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(revoked)
+    }
+}
+
+/// SHA-256 of the key in hex, as stored in `key_hash`.
+pub fn hash_key(key: &str) -> String {
+    hex(&openssl::sha::sha256(key.as_bytes()))
+}
+
+/// `prefix` followed by `length` random bytes in hex.
+pub fn random_key(prefix: &str, length: usize) -> Result<String, sqlx::Error> {
+    let mut random = vec![0u8; length];
+    openssl::rand::rand_bytes(&mut random)
+        .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+    Ok(format!("{}{}", prefix, hex(&random)))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
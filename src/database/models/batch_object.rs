@@ -0,0 +1,125 @@
+#![allow(dead_code)]
+
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use sqlx::Postgres;
+
+use super::Table;
+
+/// How a Salmon batch ended, stored as the `batch_outcome` enum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type)]
+#[sqlx(type_name = "batch_outcome", rename_all = "lowercase")]
+pub enum BatchOutcome {
+    Committed,
+    /// Some of it was outside the role or scope of the account, so nothing was written.
+    Denied,
+    Failed
+}
+
+impl BatchOutcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatchOutcome::Committed => "committed",
+            BatchOutcome::Denied => "denied",
+            BatchOutcome::Failed => "failed"
+        }
+    }
+}
+
+/// Salmon batch received from a collector, with the number of rows it changed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
+pub struct IngestionBatchObject {
+    batch_id: i64,
+    account_id: Option<i64>,
+    user_name: String,
+    target: String,
+    received: i32,
+    inserted: i32,
+    updated: i32,
+    deleted: i32,
+    outcome: BatchOutcome,
+    message: String,
+    started_at: DateTime<Utc>,
+    elapsed_ms: i64
+}
+
+impl Display for IngestionBatchObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "batch >> {}: {} {} by {} ({})", self.batch_id, self.received, self.target, self.user_name, self.outcome.name())
+    }
+}
+
+impl IngestionBatchObject {
+    pub fn batch_id(&self) -> i64 { self.batch_id }
+    pub fn account_id(&self) -> Option<i64> { self.account_id }
+    pub fn user_name(&self) -> &str { &self.user_name }
+    pub fn target(&self) -> &str { &self.target }
+    pub fn received(&self) -> u32 { self.received as u32 }
+    pub fn inserted(&self) -> u32 { self.inserted as u32 }
+    pub fn updated(&self) -> u32 { self.updated as u32 }
+    pub fn deleted(&self) -> u32 { self.deleted as u32 }
+    /// Rows of the batch that were already stored as they are, or deletes of missing rows.
+    pub fn unchanged(&self) -> u32 { self.received().saturating_sub(self.inserted() + self.updated() + self.deleted()) }
+    pub fn outcome(&self) -> BatchOutcome { self.outcome }
+    pub fn message(&self) -> &str { &self.message }
+    pub fn started_at(&self) -> DateTime<Utc> { self.started_at }
+    pub fn elapsed_ms(&self) -> i64 { self.elapsed_ms }
+}
+
+/// Batch to be stored once it has ended.
+#[derive(Debug, Clone)]
+pub struct InitIngestionBatchObject {
+    pub account_id: i64,
+    pub user_name: String,
+    pub target: Table,
+    pub received: usize,
+    pub inserted: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub outcome: BatchOutcome,
+    pub message: String,
+    pub started_at: DateTime<Utc>,
+    pub elapsed_ms: u128
+}
+
+impl InitIngestionBatchObject {
+    pub async fn record<'a, E>(self, transaction: E) -> Result<IngestionBatchObject, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        let count = |n: usize| i32::try_from(n).unwrap_or(i32::MAX);
+        // language=SQL
+        let recorded = sqlx::query_as::<_, IngestionBatchObject>(r#"
+            INSERT INTO ingestion_batches
+                (account_id, user_name, target, received, inserted, updated, deleted,
+                outcome, message, started_at, elapsed_ms)
+              VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            RETURNING *
+        "#).bind(self.account_id)
+           .bind(&self.user_name)
+           .bind(self.target.as_str())
+           .bind(count(self.received))
+           .bind(count(self.inserted))
+           .bind(count(self.updated))
+           .bind(count(self.deleted))
+           .bind(self.outcome)
+           .bind(&self.message)
+           .bind(self.started_at)
+           .bind(i64::try_from(self.elapsed_ms).unwrap_or(i64::MAX))
+           .fetch_one(transaction)
+           .await?;
+        Ok(recorded)
+    }
+}
+
+impl IngestionBatchObject {
+    /// The latest `limit` batches, newest first.
+    pub async fn fetch_recent<'a, E>(limit: i64, transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let recent = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM ingestion_batches ORDER BY started_at DESC, batch_id DESC LIMIT $1
+        "#).bind(limit)
+           .fetch_all(transaction)
+           .await?;
+        Ok(recent)
+    }
+}
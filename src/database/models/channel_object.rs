@@ -1,19 +1,25 @@
 #![allow(dead_code)]
 
 use std::fmt::{Display, Formatter};
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Utc};
 use sqlx::{Row, Postgres, Transaction, Error};
 
-use super::{Accessor, hash, Fetch};
-use super::id_object::{ChannelId, LiverId};
+use super::{Accessor, contains_pattern, hash, Fetch, Scoped, Table};
+use super::id_object::{AffiliationId, ChannelId, LiverId};
+use super::name_object::{self, Localized, LocalizedNames, NamedEntity};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
 pub struct ChannelObject {
     channel_id: ChannelId,
     liver_id: Option<LiverId>,
     logo_url: String,
-    published_at: DateTime<Local>,
-    description: String
+    published_at: Option<DateTime<Utc>>,
+    description: String,
+    /// Filled in from `localized_names`.
+    #[sqlx(default)]
+    names: LocalizedNames,
+    #[sqlx(default)]
+    updated_at: Option<DateTime<Utc>>
 }
 
 impl Display for ChannelObject {
@@ -23,7 +29,7 @@ impl Display for ChannelObject {
 }
 
 impl ChannelObject {
-    pub fn published_at(&self) -> DateTime<Local> {
+    pub fn published_at(&self) -> Option<DateTime<Utc>> {
         self.published_at
     }
 
@@ -42,10 +48,99 @@ impl ChannelObject {
     pub fn description(&self) -> &str {
         &self.description
     }
+
+    pub fn names(&self) -> &LocalizedNames {
+        &self.names
+    }
+
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.updated_at
+    }
+
+    /// Name in the first of `locales` that has one, channels have no name of their own.
+    pub fn display_name(&self, locales: &[impl AsRef<str>]) -> Option<&str> {
+        self.names.pick(locales, &[])
+    }
+
+    fn with_names(self, names: LocalizedNames) -> Self {
+        Self { names, ..self }
+    }
+}
+
+impl Localized for ChannelObject {
+    const ENTITY: NamedEntity = NamedEntity::Channel;
+
+    fn entity_id(&self) -> String {
+        self.channel_id.clone().into()
+    }
+
+    fn names(&self) -> &LocalizedNames {
+        &self.names
+    }
+
+    fn names_mut(&mut self) -> &mut LocalizedNames {
+        &mut self.names
+    }
+}
+
+impl ChannelObject {
+    pub async fn fetch_from_id<'a, E>(id: &ChannelId, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let searched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM channels WHERE channel_id = $1
+        "#).bind(id)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(searched)
+    }
+
+    pub async fn fetch_from_ids<'a, E>(ids: &[String], transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let searched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM channels WHERE channel_id = ANY($1)
+        "#).bind(ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(searched)
+    }
+
+    /// At most `limit` channels whose id or description contains `query`, or whose name does in any locale.
+    pub async fn fetch_matching<'a, E>(query: &str, limit: i64, transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let matched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM channels
+             WHERE $1 = '' OR channel_id ILIKE $3 ESCAPE '\' OR description ILIKE $3 ESCAPE '\'
+                OR EXISTS(SELECT 1 FROM localized_names
+                           WHERE entity = 'channel' AND entity_id = channel_id AND name ILIKE $3 ESCAPE '\')
+             ORDER BY channel_id
+             LIMIT $2
+        "#).bind(query)
+           .bind(limit)
+           .bind(contains_pattern(query))
+           .fetch_all(transaction)
+           .await?;
+        name_object::with_names(matched, transaction).await
+    }
+
+    pub async fn fetch_filtered_livers<'a, E>(ids: &[i64], transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let filtered = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM channels WHERE liver_id = ANY($1) ORDER BY channel_id
+        "#).bind(ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(filtered)
+    }
 }
 
 #[async_trait::async_trait]
 impl Accessor for ChannelObject {
+    const TABLE: Table = Table::Channels;
+
     async fn insert(self, transaction: &mut Transaction<'_, Postgres>) -> Result<Self, Error> {
         // language=SQL
         let ins = sqlx::query_as::<_, Self>(r#"
@@ -59,41 +154,58 @@ impl Accessor for ChannelObject {
            .bind(&self.description)
            .fetch_one(&mut *transaction)
            .await?;
-        Ok(ins)
+        if !self.names.is_empty() {
+            name_object::replace_names(&ins, &self.names, transaction).await?;
+        }
+        Ok(ins.with_names(self.names))
     }
 
     async fn delete(self, transaction: &mut Transaction<'_, Postgres>) -> Result<Self, Error> {
         // language=SQL
         let del = sqlx::query_as::<_, Self>(r#"
-            DELETE FROM channels WHERE channel_id LIKE $1 RETURNING *
+            DELETE FROM channels WHERE channel_id = $1 RETURNING *
         "#).bind(&self.channel_id)
            .fetch_one(&mut *transaction)
            .await?;
+        let del = name_object::attach_names(del, transaction).await?;
+        name_object::delete_names(&del, transaction).await?;
         Ok(del)
     }
 
     async fn update(self, transaction: &mut Transaction<'_, Postgres>) -> Result<(Self, Self), Error> {
         // language=SQL
         let old = sqlx::query_as::<_, Self>(r#"
-            SELECT * FROM channels WHERE channel_id LIKE $1
+            SELECT * FROM channels WHERE channel_id = $1
         "#).bind(&self.channel_id)
            .fetch_one(&mut *transaction)
            .await?;
+        let old = name_object::attach_names(old, transaction).await?;
         // language=SQL
         let new = sqlx::query_as::<_, Self>(r#"
-            UPDATE channels SET description = $1 WHERE channel_id LIKE $2
+            UPDATE channels SET liver_id = $1, logo_url = $2, published_at = $3, description = $4, updated_at = CURRENT_TIMESTAMP
+             WHERE channel_id = $5
             RETURNING *
-        "#).bind(&self.description)
+        "#).bind(self.liver_id)
+           .bind(&self.logo_url)
+           .bind(self.published_at)
+           .bind(&self.description)
            .bind(&self.channel_id)
            .fetch_one(&mut *transaction)
            .await?;
-        Ok((old, new))
+        // Names are left as they are when absent, and removed when given as none.
+        let names = if !self.names.is_given() {
+            old.names.clone()
+        } else {
+            name_object::replace_names(&new, &self.names, transaction).await?;
+            self.names
+        };
+        Ok((old, new.with_names(names)))
     }
 
     async fn exists(&self, transaction: &mut Transaction<'_, Postgres>) -> Result<bool, Error> {
         // language=SQL
         let channel_exists = sqlx::query(r#"
-            SELECT EXISTS(SELECT 1 FROM channels WHERE channel_id LIKE $1)
+            SELECT EXISTS(SELECT 1 FROM channels WHERE channel_id = $1)
         "#).bind(&self.channel_id)
            .fetch_one(&mut *transaction)
            .await?
@@ -104,15 +216,17 @@ impl Accessor for ChannelObject {
 
     async fn compare(&self, transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Result<bool, sqlx::Error> {
         let com = sqlx::query_as::<_, Self>(r#"
-            SELECT * FROM channels WHERE channel_id LIKE $1
+            SELECT * FROM channels WHERE channel_id = $1
         "#).bind(&self.channel_id)
            .fetch_optional(&mut *transaction)
            .await?;
         
         let com = if let Some(db) = com {
-            let db = hash(&db);
-            let my = hash(&self);
-            db == my
+            let db = name_object::attach_names(db, transaction).await?;
+            let my = if !self.names.is_given() { self.clone().with_names(db.names.clone()) } else { self.clone() };
+            // `updated_at` is not sent, it moves whenever something else changes.
+            let my = Self { updated_at: db.updated_at, ..my };
+            hash(&db) == hash(&my)
         } else { false };
         Ok(com)
     }
@@ -127,7 +241,7 @@ impl Fetch for ChannelObject {
             SELECT * FROM channels
         "#).fetch_all(transaction)
            .await?;
-        Ok(all)
+        name_object::with_names(all, transaction).await
     }
 }
 
@@ -135,8 +249,9 @@ pub struct InitChannelObject {
     pub channel_id: ChannelId,
     pub liver_id: Option<LiverId>,
     pub logo_url: String,
-    pub published_at: DateTime<Local>,
+    pub published_at: Option<DateTime<Utc>>,
     pub description: String,
+    pub names: LocalizedNames,
     #[doc(hidden)]
     pub init: ()
 }
@@ -147,8 +262,9 @@ impl Default for InitChannelObject {
             channel_id: ChannelId::default(),
             liver_id: None,
             logo_url: "none".to_string(),
-            published_at: Local::now(),
+            published_at: None,
             description: "none".to_string(),
+            names: LocalizedNames::default(),
             init: ()
         }
     }
@@ -162,6 +278,30 @@ impl InitChannelObject {
             logo_url: self.logo_url,
             published_at: self.published_at,
             description: self.description,
+            names: self.names,
+            updated_at: None
         }
     }
-}
\ No newline at end of file
+}
+#[async_trait::async_trait]
+impl Scoped for ChannelObject {
+    async fn affiliations(&self, transaction: &mut Transaction<'_, Postgres>) -> Result<Vec<Option<AffiliationId>>, Error> {
+        // language=SQL
+        let given = sqlx::query_scalar::<_, Option<AffiliationId>>(r#"
+            SELECT affiliation_id FROM livers WHERE liver_id = $1
+        "#).bind(self.liver_id)
+           .fetch_optional(&mut *transaction)
+           .await?
+           .flatten();
+        // language=SQL
+        let stored = sqlx::query_scalar::<_, Option<AffiliationId>>(r#"
+            SELECT livers.affiliation_id
+              FROM channels
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE channels.channel_id = $1
+        "#).bind(&self.channel_id)
+           .fetch_optional(&mut *transaction)
+           .await?;
+        Ok(std::iter::once(given).chain(stored).collect())
+    }
+}
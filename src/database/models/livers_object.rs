@@ -1,10 +1,12 @@
 #![allow(dead_code)]
 
 use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
 use sqlx::{Error, Postgres, Row, Transaction};
 
-use super::{Accessor, hash, Fetch};
+use super::{Accessor, contains_pattern, hash, Fetch, Scoped, Table};
 use super::id_object::{AffiliationId, LiverId};
+use super::name_object::{self, Localized, LocalizedNames, NamedEntity};
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq, sqlx::FromRow)]
 pub struct LiverObject {
@@ -12,6 +14,11 @@ pub struct LiverObject {
     affiliation_id: Option<AffiliationId>,
     name: String,
     localized_name: String,
+    /// Filled in from `localized_names`.
+    #[sqlx(default)]
+    names: LocalizedNames,
+    #[sqlx(default)]
+    updated_at: Option<DateTime<Utc>>
 }
 
 impl Display for LiverObject {
@@ -28,16 +35,31 @@ impl LiverObject {
         Self {
             liver_id: LiverId::new(id.into()), 
             affiliation_id: affiliation_id.into().map(AffiliationId::new),
-            name: name.into(), localized_name: localized_name.into()
+            name: name.into(), localized_name: localized_name.into(),
+            names: LocalizedNames::default(),
+            updated_at: None
         }
     }
 
+    pub fn with_names(self, names: LocalizedNames) -> Self {
+        Self { names, ..self }
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
     pub fn localized_name(&self) -> &str { &self.localized_name }
 
+    pub fn names(&self) -> &LocalizedNames { &self.names }
+
+    /// Name in the first of `locales` that has one,
+    /// where `name` and `localized_name` stand for `ja` and `en` unless localized names are set for them.
+    pub fn display_name(&self, locales: &[impl AsRef<str>]) -> &str {
+        self.names.pick(locales, &[("ja", &self.name), ("en", &self.localized_name)])
+            .unwrap_or(&self.name)
+    }
+
     pub fn liver_id(&self) -> LiverId {
         self.liver_id
     }
@@ -45,6 +67,10 @@ impl LiverObject {
     pub fn affiliation_id(&self) -> Option<AffiliationId> {
         self.affiliation_id
     }
+
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.updated_at
+    }
 }
 
 impl LiverObject {
@@ -55,7 +81,59 @@ impl LiverObject {
             SELECT * FROM livers
         "#).fetch_all(transaction)
            .await?;
-        Ok(all)
+        name_object::with_names(all, transaction).await
+    }
+
+    pub async fn fetch_from_id<'a, E>(id: LiverId, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let searched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM livers WHERE liver_id = $1
+        "#).bind(id)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(searched)
+    }
+
+    pub async fn fetch_from_ids<'a, E>(ids: &[i64], transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let searched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM livers WHERE liver_id = ANY($1)
+        "#).bind(ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(searched)
+    }
+
+    /// At most `limit` livers whose id is `query`, or whose name contains it in any locale.
+    pub async fn fetch_matching<'a, E>(query: &str, limit: i64, transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let matched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM livers
+             WHERE $1 = '' OR liver_id::TEXT = $1 OR name ILIKE $3 ESCAPE '\' OR localized_name ILIKE $3 ESCAPE '\'
+                OR EXISTS(SELECT 1 FROM localized_names
+                           WHERE entity = 'liver' AND entity_id = liver_id::TEXT AND name ILIKE $3 ESCAPE '\')
+             ORDER BY liver_id
+             LIMIT $2
+        "#).bind(query)
+           .bind(limit)
+           .bind(contains_pattern(query))
+           .fetch_all(transaction)
+           .await?;
+        name_object::with_names(matched, transaction).await
+    }
+
+    pub async fn fetch_filtered_affiliations<'a, E>(ids: &[i64], transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let filtered = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM livers WHERE affiliation_id = ANY($1) ORDER BY liver_id
+        "#).bind(ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(filtered)
     }
 
     pub async fn fetch_filtered_affiliation<'a, E>(id: i64, transaction: E) -> Result<Vec<Self>, sqlx::Error>
@@ -70,6 +148,22 @@ impl LiverObject {
     }
 }
 
+impl Localized for LiverObject {
+    const ENTITY: NamedEntity = NamedEntity::Liver;
+
+    fn entity_id(&self) -> String {
+        i64::from(self.liver_id).to_string()
+    }
+
+    fn names(&self) -> &LocalizedNames {
+        &self.names
+    }
+
+    fn names_mut(&mut self) -> &mut LocalizedNames {
+        &mut self.names
+    }
+}
+
 #[async_trait::async_trait]
 impl Fetch for LiverObject {
     async fn fetch_all<'a, E>(transaction: E) -> Result<Vec<Self>, sqlx::Error>
@@ -79,12 +173,14 @@ impl Fetch for LiverObject {
             SELECT * FROM livers
         "#).fetch_all(transaction)
            .await?;
-        Ok(all)
+        name_object::with_names(all, transaction).await
     }
 }
 
 #[async_trait::async_trait]
 impl Accessor for LiverObject {
+    const TABLE: Table = Table::Livers;
+
     async fn insert(self, transaction: &mut Transaction<'_, Postgres>) -> Result<Self, Error> {
         // language=SQL
         let ins = sqlx::query_as::<_, Self>(r#"
@@ -97,7 +193,10 @@ impl Accessor for LiverObject {
            .bind(&self.localized_name)
            .fetch_one(&mut *transaction)
            .await?;
-        Ok(ins)
+        if !self.names.is_empty() {
+            name_object::replace_names(&ins, &self.names, transaction).await?;
+        }
+        Ok(ins.with_names(self.names))
     }
 
     async fn delete(self, transaction: &mut Transaction<'_, Postgres>) -> Result<Self, Error> {
@@ -107,6 +206,8 @@ impl Accessor for LiverObject {
         "#).bind(self.liver_id)
            .fetch_one(&mut *transaction)
            .await?;
+        let del = name_object::attach_names(del, transaction).await?;
+        name_object::delete_names(&del, transaction).await?;
         Ok(del)
     }
 
@@ -117,16 +218,26 @@ impl Accessor for LiverObject {
         "#).bind(self.liver_id)
            .fetch_one(&mut *transaction)
            .await?;
+        let old = name_object::attach_names(old, transaction).await?;
         // language=SQL
         let update = sqlx::query_as::<_, Self>(r#"
-            UPDATE livers SET name = $1, affiliation_id = $2 WHERE liver_id = $3
+            UPDATE livers SET name = $1, localized_name = $2, affiliation_id = $3, updated_at = CURRENT_TIMESTAMP
+             WHERE liver_id = $4
             RETURNING *
         "#).bind(&self.name)
+           .bind(&self.localized_name)
            .bind(self.affiliation_id)
            .bind(self.liver_id)
            .fetch_one(&mut *transaction)
            .await?;
-        Ok((old, update))
+        // Names are left as they are when absent, and removed when given as none.
+        let names = if !self.names.is_given() {
+            old.names.clone()
+        } else {
+            name_object::replace_names(&update, &self.names, transaction).await?;
+            self.names
+        };
+        Ok((old, update.with_names(names)))
     }
 
     async fn exists(&self, transaction: &mut Transaction<'_, Postgres>) -> Result<bool, Error> {
@@ -155,11 +266,25 @@ impl Accessor for LiverObject {
            .await?;
         
         let com = if let Some(db) = com {
-            let db = hash(&db);
-            let my = hash(&self);
-            db == my
+            let db = name_object::attach_names(db, transaction).await?;
+            let my = if !self.names.is_given() { self.clone().with_names(db.names.clone()) } else { self.clone() };
+            // `updated_at` is not sent, it moves whenever something else changes.
+            let my = Self { updated_at: db.updated_at, ..my };
+            hash(&db) == hash(&my)
         } else { false };
         Ok(com)
     }
 
-}
\ No newline at end of file
+}
+#[async_trait::async_trait]
+impl Scoped for LiverObject {
+    async fn affiliations(&self, transaction: &mut Transaction<'_, Postgres>) -> Result<Vec<Option<AffiliationId>>, Error> {
+        // language=SQL
+        let stored = sqlx::query_scalar::<_, Option<AffiliationId>>(r#"
+            SELECT affiliation_id FROM livers WHERE liver_id = $1
+        "#).bind(self.liver_id)
+           .fetch_optional(&mut *transaction)
+           .await?;
+        Ok(std::iter::once(self.affiliation_id).chain(stored).collect())
+    }
+}
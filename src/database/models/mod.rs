@@ -3,12 +3,42 @@ pub mod affiliation_object;
 pub mod livers_object;
 pub mod upcoming_object;
 pub mod channel_object;
+pub mod schedule_object;
+pub mod search_object;
+pub mod name_object;
+pub mod api_key_object;
+pub mod account_object;
+pub mod token_object;
+pub mod batch_object;
+
+/// Tables that are written through [Accessor].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Table {
+    Affiliations,
+    Livers,
+    Channels,
+    Videos
+}
+
+impl Table {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Table::Affiliations => "affiliations",
+            Table::Livers => "livers",
+            Table::Channels => "channels",
+            Table::Videos => "videos"
+        }
+    }
+}
 
 /// Trait used to mediate basic SQL Transactions.
 ///
 /// Use the SQL statement "Returning *" to use the value of the result after the SQL is executed for the return value
 #[async_trait::async_trait]
 pub trait Accessor: Sized {
+    /// Table the value is stored in.
+    const TABLE: Table;
+
     /// Consume the value and insert it into the database.
     ///
     /// [Ok()]: `T` - Value returned by SQL statement "Returning *".
@@ -35,7 +65,7 @@ pub trait Accessor: Sized {
     /// Use
     ///
     /// ```sql
-    /// SELECT EXISTS(SELECT 1 FROM data WHERE id LIKE $1)
+    /// SELECT EXISTS(SELECT 1 FROM data WHERE id = $1)
     /// ```
     ///
     /// or otherwise return the Bool value from the database.
@@ -54,11 +84,32 @@ pub trait Accessor: Sized {
     async fn compare(&self, transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Result<bool, sqlx::Error>;
 }
 
+/// Values that belong to affiliations, which scoped accounts are limited to.
+#[async_trait::async_trait]
+pub trait Scoped {
+    /// Affiliations of the value both as given and as stored, `None` for a value that belongs to no affiliation.
+    async fn affiliations(&self, transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Result<Vec<Option<id_object::AffiliationId>>, sqlx::Error>;
+}
+
 #[async_trait::async_trait]
 pub trait Fetch: Sized {
     async fn fetch_all<'a, E>(transaction: E) -> Result<Vec<Self>, sqlx::Error> where E: sqlx::Executor<'a, Database = sqlx::Postgres> + Copy;
 }
 
+/// `%query%` for `ILIKE ... ESCAPE '\'`, where `%` and `_` typed in `query` match only themselves.
+fn contains_pattern(query: &str) -> String {
+    let mut pattern = String::with_capacity(query.len() + 2);
+    pattern.push('%');
+    for c in query.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
 pub fn hash<T: std::hash::Hash>(hash_obj: &T) -> u64 {
     use std::hash::Hasher;
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
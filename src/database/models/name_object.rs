@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use sqlx::{Decode, Postgres, Transaction, Type};
+use sqlx::postgres::{PgTypeInfo, PgValueRef};
+
+/// End of every fallback chain, so that some name is always found.
+pub const DEFAULT_LOCALES: [&str; 2] = ["ja", "en"];
+
+/// Kind of the row a localized name belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NamedEntity {
+    Affiliation,
+    Liver,
+    Channel
+}
+
+impl NamedEntity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NamedEntity::Affiliation => "affiliation",
+            NamedEntity::Liver => "liver",
+            NamedEntity::Channel => "channel"
+        }
+    }
+}
+
+/// Names of a row per locale, keyed by lowercase language tags such as `ja`, `en` or `id`.
+///
+/// Collected names are given, even when there are none, and replace the stored ones on write.
+/// [LocalizedNames::default] is absent instead, and leaves them as they are.
+/// Absent names compare equal to no names.
+#[derive(Debug, Clone, Default)]
+pub struct LocalizedNames(Option<BTreeMap<String, String>>);
+
+impl<L: AsRef<str>, N: Into<String>> FromIterator<(L, N)> for LocalizedNames {
+    fn from_iter<I: IntoIterator<Item = (L, N)>>(iter: I) -> Self {
+        Self(Some(iter.into_iter()
+            .map(|(locale, name)| (locale.as_ref().trim().to_lowercase(), name.into()))
+            .filter(|(locale, name)| !locale.is_empty() && !name.is_empty())
+            .collect()))
+    }
+}
+
+impl PartialEq for LocalizedNames {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for LocalizedNames {}
+
+impl Hash for LocalizedNames {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for (locale, name) in self.iter() {
+            locale.hash(state);
+            name.hash(state);
+        }
+    }
+}
+
+impl LocalizedNames {
+    pub fn get(&self, locale: &str) -> Option<&str> {
+        self.0.as_ref()?.get(locale).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.as_ref().map_or(true, BTreeMap::is_empty)
+    }
+
+    /// Whether the names are to replace the stored ones, even if there are none.
+    pub fn is_given(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().flatten().map(|(locale, name)| (locale.as_str(), name.as_str()))
+    }
+
+    /// Name in the first of `locales` that has one.
+    ///
+    /// `base` holds names stored in the row itself, used when no localized name is set for a locale.
+    pub fn pick<'a>(&'a self, locales: &[impl AsRef<str>], base: &[(&str, &'a str)]) -> Option<&'a str> {
+        locales.iter()
+            .map(AsRef::as_ref)
+            .find_map(|locale| self.get(locale)
+                .or_else(|| base.iter().find(|(tag, _)| *tag == locale).map(|(_, name)| *name)))
+    }
+}
+
+/// Decoded from a flat `TEXT[]` of locale and name pairs,
+/// so that rows fetched with `SELECT *` simply have no names (`#[sqlx(default)]`).
+impl Type<Postgres> for LocalizedNames {
+    fn type_info() -> PgTypeInfo {
+        <Vec<String> as Type<Postgres>>::type_info()
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for LocalizedNames {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let pairs = <Vec<String> as Decode<Postgres>>::decode(value)?;
+        Ok(pairs.chunks_exact(2)
+            .map(|pair| (pair[0].as_str(), pair[1].clone()))
+            .collect())
+    }
+}
+
+/// Rows named per locale in `localized_names`.
+pub trait Localized {
+    const ENTITY: NamedEntity;
+
+    /// Primary key of the row, as stored in `localized_names.entity_id`.
+    fn entity_id(&self) -> String;
+
+    fn names(&self) -> &LocalizedNames;
+
+    fn names_mut(&mut self) -> &mut LocalizedNames;
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct LocalizedNameRow {
+    entity_id: String,
+    locale: String,
+    name: String
+}
+
+async fn fetch_names<'a, E>(entity: NamedEntity, ids: &[String], transaction: E) -> Result<HashMap<String, LocalizedNames>, sqlx::Error>
+  where E: sqlx::Executor<'a, Database = Postgres> {
+    // language=SQL
+    let rows = sqlx::query_as::<_, LocalizedNameRow>(r#"
+        SELECT entity_id, locale, name FROM localized_names WHERE entity = $1 AND entity_id = ANY($2)
+    "#).bind(entity.as_str())
+       .bind(ids)
+       .fetch_all(transaction)
+       .await?;
+    let mut names = HashMap::<String, BTreeMap<String, String>>::new();
+    for row in rows {
+        names.entry(row.entity_id).or_default().insert(row.locale, row.name);
+    }
+    Ok(names.into_iter().map(|(id, names)| (id, LocalizedNames(Some(names)))).collect())
+}
+
+/// Fill in the localized names of rows fetched from their own table.
+pub async fn with_names<'a, T, E>(mut rows: Vec<T>, transaction: E) -> Result<Vec<T>, sqlx::Error>
+  where T: Localized,
+        E: sqlx::Executor<'a, Database = Postgres> {
+    let ids = rows.iter().map(Localized::entity_id).collect::<Vec<_>>();
+    let mut names = fetch_names(T::ENTITY, &ids, transaction).await?;
+    for row in &mut rows {
+        if let Some(found) = names.remove(&row.entity_id()) {
+            *row.names_mut() = found;
+        }
+    }
+    Ok(rows)
+}
+
+/// Fill in the localized names of a row within a transaction.
+pub(super) async fn attach_names<T: Localized>(mut row: T, transaction: &mut Transaction<'_, Postgres>) -> Result<T, sqlx::Error> {
+    let id = row.entity_id();
+    if let Some(found) = fetch_names(T::ENTITY, std::slice::from_ref(&id), &mut *transaction).await?.remove(&id) {
+        *row.names_mut() = found;
+    }
+    Ok(row)
+}
+
+/// Replace the localized names of a row with `names`.
+pub(super) async fn replace_names<T: Localized>(row: &T, names: &LocalizedNames, transaction: &mut Transaction<'_, Postgres>) -> Result<(), sqlx::Error> {
+    delete_names(row, transaction).await?;
+    let (locales, names): (Vec<_>, Vec<_>) = names.iter().unzip();
+    // language=SQL
+    sqlx::query(r#"
+        INSERT INTO localized_names (entity, entity_id, locale, name)
+        SELECT $1, $2, * FROM UNNEST($3::VARCHAR[], $4::VARCHAR[])
+    "#).bind(T::ENTITY.as_str())
+       .bind(row.entity_id())
+       .bind(locales)
+       .bind(names)
+       .execute(&mut *transaction)
+       .await?;
+    Ok(())
+}
+
+pub(super) async fn delete_names<T: Localized>(row: &T, transaction: &mut Transaction<'_, Postgres>) -> Result<(), sqlx::Error> {
+    // language=SQL
+    sqlx::query(r#"
+        DELETE FROM localized_names WHERE entity = $1 AND entity_id = $2
+    "#).bind(T::ENTITY.as_str())
+       .bind(row.entity_id())
+       .execute(&mut *transaction)
+       .await?;
+    Ok(())
+}
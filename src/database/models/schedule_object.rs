@@ -0,0 +1,314 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use sqlx::Postgres;
+
+use super::id_object::{AffiliationId, ChannelId, LiverId, VideoId};
+use super::upcoming_object::VideoObject;
+
+/// Narrows scheduled videos down to an affiliation or a set of livers.
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleFilter {
+    pub affiliation_id: Option<i64>,
+    pub liver_ids: Option<Vec<i64>>
+}
+
+/// State of a video derived from `will_start_at`, `started_at` and `ended_at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VideoStatus {
+    Upcoming,
+    Live,
+    Uploaded
+}
+
+impl VideoStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            VideoStatus::Upcoming => "upcoming",
+            VideoStatus::Live => "live",
+            VideoStatus::Uploaded => "uploaded"
+        }
+    }
+}
+
+/// Kind of videos listed by [ScheduledVideoObject::fetch_latest_published].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VideoFeed {
+    /// Streams that have `will_start_at` but not started yet.
+    Schedules,
+    /// Uploaded videos, and streams that have already ended.
+    Archives
+}
+
+/// Video joined with its channel and the liver who owns the channel.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ScheduledVideoObject {
+    #[sqlx(flatten)]
+    video: VideoObject,
+    logo_url: Option<String>,
+    liver_id: Option<LiverId>,
+    liver_name: Option<String>,
+    affiliation_id: Option<AffiliationId>
+}
+
+impl ScheduledVideoObject {
+    pub fn video(&self) -> &VideoObject {
+        &self.video
+    }
+
+    pub fn into_video(self) -> VideoObject {
+        self.video
+    }
+
+    pub fn logo_url(&self) -> Option<&str> {
+        self.logo_url.as_deref()
+    }
+
+    pub fn liver_id(&self) -> Option<LiverId> {
+        self.liver_id
+    }
+
+    pub fn liver_name(&self) -> Option<&str> {
+        self.liver_name.as_deref()
+    }
+
+    pub fn affiliation_id(&self) -> Option<AffiliationId> {
+        self.affiliation_id
+    }
+}
+
+impl ScheduledVideoObject {
+    pub async fn fetch_scheduled_since<'a, E>(
+        since: DateTime<Utc>,
+        filter: &ScheduleFilter,
+        transaction: E
+    ) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let scheduled = sqlx::query_as::<_, Self>(r#"
+            SELECT videos.*, channels.logo_url, livers.liver_id, livers.name AS liver_name, livers.affiliation_id
+              FROM videos
+              LEFT JOIN channels ON channels.channel_id = videos.channel_id
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE videos.will_start_at IS NOT NULL
+               AND videos.will_start_at >= $1
+               AND ($2::BIGINT IS NULL OR livers.affiliation_id = $2)
+               AND ($3::BIGINT[] IS NULL OR livers.liver_id = ANY($3))
+             ORDER BY videos.will_start_at
+        "#).bind(since)
+           .bind(filter.affiliation_id)
+           .bind(&filter.liver_ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(scheduled)
+    }
+
+    /// Videos starting within `[from, to)`, by `started_at` once started and by `will_start_at` until then.
+    pub async fn fetch_starting_between<'a, E>(
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        filter: &ScheduleFilter,
+        transaction: E
+    ) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let starting = sqlx::query_as::<_, Self>(r#"
+            SELECT videos.*, channels.logo_url, livers.liver_id, livers.name AS liver_name, livers.affiliation_id
+              FROM videos
+              LEFT JOIN channels ON channels.channel_id = videos.channel_id
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE COALESCE(videos.started_at, videos.will_start_at) >= $1
+               AND COALESCE(videos.started_at, videos.will_start_at) < $2
+               AND ($3::BIGINT IS NULL OR livers.affiliation_id = $3)
+               AND ($4::BIGINT[] IS NULL OR livers.liver_id = ANY($4))
+             ORDER BY COALESCE(videos.started_at, videos.will_start_at), videos.video_id
+        "#).bind(from)
+           .bind(to)
+           .bind(filter.affiliation_id)
+           .bind(&filter.liver_ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(starting)
+    }
+
+    /// Videos ordered by `will_start_at`, then `published_at`.
+    pub async fn fetch_filtered<'a, E>(
+        filter: &ScheduleFilter,
+        channel_ids: Option<&[String]>,
+        status: Option<VideoStatus>,
+        limit: i64,
+        offset: i64,
+        transaction: E
+    ) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let filtered = sqlx::query_as::<_, Self>(r#"
+            SELECT videos.*, channels.logo_url, livers.liver_id, livers.name AS liver_name, livers.affiliation_id
+              FROM videos
+              LEFT JOIN channels ON channels.channel_id = videos.channel_id
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE ($1::BIGINT IS NULL OR livers.affiliation_id = $1)
+               AND ($2::BIGINT[] IS NULL OR livers.liver_id = ANY($2))
+               AND ($3::VARCHAR[] IS NULL OR videos.channel_id = ANY($3))
+               AND CASE $4::VARCHAR
+                     WHEN 'upcoming' THEN videos.will_start_at IS NOT NULL AND videos.started_at IS NULL
+                     WHEN 'live' THEN videos.started_at IS NOT NULL AND videos.ended_at IS NULL
+                     WHEN 'uploaded' THEN videos.will_start_at IS NULL AND videos.started_at IS NULL
+                                       OR videos.ended_at IS NOT NULL
+                     ELSE TRUE END
+             ORDER BY videos.will_start_at NULLS LAST, videos.published_at DESC, videos.video_id
+             LIMIT $5 OFFSET $6
+        "#).bind(filter.affiliation_id)
+           .bind(&filter.liver_ids)
+           .bind(channel_ids)
+           .bind(status.map(|status| status.as_str()))
+           .bind(limit)
+           .bind(offset)
+           .fetch_all(transaction)
+           .await?;
+        Ok(filtered)
+    }
+
+    pub async fn fetch_from_ids<'a, E>(ids: &[String], transaction: E) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let videos = sqlx::query_as::<_, Self>(r#"
+            SELECT videos.*, channels.logo_url, livers.liver_id, livers.name AS liver_name, livers.affiliation_id
+              FROM videos
+              LEFT JOIN channels ON channels.channel_id = videos.channel_id
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE videos.video_id = ANY($1)
+        "#).bind(ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(videos)
+    }
+
+    /// The earliest upcoming video of every liver.
+    pub async fn fetch_next_upcoming_of_livers<'a, E>(ids: &[i64], transaction: E) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let upcoming = sqlx::query_as::<_, Self>(r#"
+            SELECT DISTINCT ON (livers.liver_id)
+                   videos.*, channels.logo_url, livers.liver_id, livers.name AS liver_name, livers.affiliation_id
+              FROM videos
+              JOIN channels ON channels.channel_id = videos.channel_id
+              JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE livers.liver_id = ANY($1)
+               AND videos.will_start_at IS NOT NULL
+               AND videos.started_at IS NULL
+             ORDER BY livers.liver_id, videos.will_start_at
+        "#).bind(ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(upcoming)
+    }
+
+    /// The earliest upcoming video of every channel.
+    pub async fn fetch_next_upcoming_of_channels<'a, E>(ids: &[String], transaction: E) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let upcoming = sqlx::query_as::<_, Self>(r#"
+            SELECT DISTINCT ON (videos.channel_id)
+                   videos.*, channels.logo_url, livers.liver_id, livers.name AS liver_name, livers.affiliation_id
+              FROM videos
+              JOIN channels ON channels.channel_id = videos.channel_id
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE videos.channel_id = ANY($1)
+               AND videos.will_start_at IS NOT NULL
+               AND videos.started_at IS NULL
+             ORDER BY videos.channel_id, videos.will_start_at
+        "#).bind(ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(upcoming)
+    }
+
+    pub async fn fetch_latest_published<'a, E>(
+        feed: VideoFeed,
+        filter: &ScheduleFilter,
+        limit: i64,
+        transaction: E
+    ) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let published = sqlx::query_as::<_, Self>(r#"
+            SELECT videos.*, channels.logo_url, livers.liver_id, livers.name AS liver_name, livers.affiliation_id
+              FROM videos
+              LEFT JOIN channels ON channels.channel_id = videos.channel_id
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE videos.published_at IS NOT NULL
+               AND CASE WHEN $1 THEN videos.will_start_at IS NOT NULL AND videos.started_at IS NULL
+                        ELSE videos.will_start_at IS NULL
+                          OR (videos.started_at IS NOT NULL AND videos.ended_at IS NOT NULL) END
+               AND ($2::BIGINT IS NULL OR livers.affiliation_id = $2)
+               AND ($3::BIGINT[] IS NULL OR livers.liver_id = ANY($3))
+             ORDER BY videos.published_at DESC
+             LIMIT $4
+        "#).bind(feed == VideoFeed::Schedules)
+           .bind(filter.affiliation_id)
+           .bind(&filter.liver_ids)
+           .bind(limit)
+           .fetch_all(transaction)
+           .await?;
+        Ok(published)
+    }
+}
+
+/// Video deleted by the collector.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
+pub struct VideoTombstoneObject {
+    video_id: VideoId,
+    channel_id: Option<ChannelId>,
+    title: String,
+    will_start_at: Option<DateTime<Utc>>,
+    deleted_at: DateTime<Utc>
+}
+
+impl VideoTombstoneObject {
+    pub fn video_id(&self) -> &VideoId {
+        &self.video_id
+    }
+
+    pub fn channel_id(&self) -> Option<&ChannelId> {
+        self.channel_id.as_ref()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn will_start_at(&self) -> Option<DateTime<Utc>> {
+        self.will_start_at
+    }
+
+    pub fn deleted_at(&self) -> DateTime<Utc> {
+        self.deleted_at
+    }
+}
+
+impl VideoTombstoneObject {
+    pub async fn fetch_deleted_since<'a, E>(
+        since: DateTime<Utc>,
+        filter: &ScheduleFilter,
+        transaction: E
+    ) -> Result<Vec<Self>, sqlx::Error>
+      where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let deleted = sqlx::query_as::<_, Self>(r#"
+            SELECT video_tombstones.*
+              FROM video_tombstones
+              LEFT JOIN channels ON channels.channel_id = video_tombstones.channel_id
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE video_tombstones.deleted_at >= $1
+               AND ($2::BIGINT IS NULL OR livers.affiliation_id = $2)
+               AND ($3::BIGINT[] IS NULL OR livers.liver_id = ANY($3))
+             ORDER BY video_tombstones.deleted_at
+        "#).bind(since)
+           .bind(filter.affiliation_id)
+           .bind(&filter.liver_ids)
+           .fetch_all(transaction)
+           .await?;
+        Ok(deleted)
+    }
+}
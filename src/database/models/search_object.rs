@@ -0,0 +1,256 @@
+#![allow(dead_code)]
+
+use sqlx::Postgres;
+
+use super::id_object::AffiliationId;
+use super::livers_object::LiverObject;
+use super::channel_object::ChannelObject;
+use super::schedule_object::ScheduledVideoObject;
+
+/// Channel matched by its description or by the name of its liver.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ChannelMatchObject {
+    #[sqlx(flatten)]
+    channel: ChannelObject,
+    liver_name: Option<String>,
+    liver_reading: Option<String>,
+    affiliation_id: Option<AffiliationId>,
+    /// Fragment of the description with matched terms wrapped in `<em>`.
+    highlighted_description: String
+}
+
+impl ChannelMatchObject {
+    pub fn channel(&self) -> &ChannelObject {
+        &self.channel
+    }
+
+    pub fn liver_name(&self) -> Option<&str> {
+        self.liver_name.as_deref()
+    }
+
+    pub fn liver_reading(&self) -> Option<&str> {
+        self.liver_reading.as_deref()
+    }
+
+    pub fn affiliation_id(&self) -> Option<AffiliationId> {
+        self.affiliation_id
+    }
+
+    pub fn highlighted_description(&self) -> &str {
+        &self.highlighted_description
+    }
+}
+
+/// Video matched by its title or description.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct VideoMatchObject {
+    #[sqlx(flatten)]
+    video: ScheduledVideoObject,
+    highlighted_title: String,
+    /// Fragment of the description with matched terms wrapped in `<em>`.
+    highlighted_description: String
+}
+
+impl VideoMatchObject {
+    pub fn video(&self) -> &ScheduledVideoObject {
+        &self.video
+    }
+
+    pub fn highlighted_title(&self) -> &str {
+        &self.highlighted_title
+    }
+
+    pub fn highlighted_description(&self) -> &str {
+        &self.highlighted_description
+    }
+}
+
+/// Number of matches of an affiliation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::FromRow)]
+pub struct AffiliationCountObject {
+    affiliation_id: Option<AffiliationId>,
+    count: i64
+}
+
+impl AffiliationCountObject {
+    pub fn affiliation_id(&self) -> Option<AffiliationId> {
+        self.affiliation_id
+    }
+
+    pub fn count(&self) -> i64 {
+        self.count
+    }
+}
+
+/// Livers whose name, localized name or reading is similar to the query, most similar first.
+///
+/// `reading` is the query normalised the same way as `liver_readings`.
+pub async fn search_livers<'a, E>(
+    query: &str,
+    reading: &str,
+    affiliation_id: Option<i64>,
+    limit: i64,
+    transaction: E
+) -> Result<(Vec<LiverObject>, Vec<AffiliationCountObject>), sqlx::Error>
+  where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+    // language=SQL
+    let matched = sqlx::query_as::<_, LiverObject>(r#"
+        SELECT livers.*
+          FROM livers
+          LEFT JOIN liver_readings ON liver_readings.liver_id = livers.liver_id
+         WHERE ($2::BIGINT IS NULL OR livers.affiliation_id = $2)
+           AND ($1 <% livers.name OR $1 <% livers.localized_name OR $4 <% liver_readings.reading
+                OR livers.name ILIKE '%' || $1 || '%' OR livers.localized_name ILIKE '%' || $1 || '%'
+                OR liver_readings.reading LIKE '%' || $4 || '%')
+         ORDER BY GREATEST(word_similarity($1, livers.name), word_similarity($1, livers.localized_name),
+                           word_similarity($4, liver_readings.reading)) DESC, livers.liver_id
+         LIMIT $3
+    "#).bind(query)
+       .bind(affiliation_id)
+       .bind(limit)
+       .bind(reading)
+       .fetch_all(transaction)
+       .await?;
+
+    // language=SQL
+    let counts = sqlx::query_as::<_, AffiliationCountObject>(r#"
+        SELECT livers.affiliation_id, COUNT(*) AS count
+          FROM livers
+          LEFT JOIN liver_readings ON liver_readings.liver_id = livers.liver_id
+         WHERE ($2::BIGINT IS NULL OR livers.affiliation_id = $2)
+           AND ($1 <% livers.name OR $1 <% livers.localized_name OR $3 <% liver_readings.reading
+                OR livers.name ILIKE '%' || $1 || '%' OR livers.localized_name ILIKE '%' || $1 || '%'
+                OR liver_readings.reading LIKE '%' || $3 || '%')
+         GROUP BY livers.affiliation_id
+    "#).bind(query)
+       .bind(affiliation_id)
+       .bind(reading)
+       .fetch_all(transaction)
+       .await?;
+    Ok((matched, counts))
+}
+
+/// Channels whose description matches the query, or whose liver has a similar name or reading.
+pub async fn search_channels<'a, E>(
+    query: &str,
+    reading: &str,
+    affiliation_id: Option<i64>,
+    limit: i64,
+    transaction: E
+) -> Result<(Vec<ChannelMatchObject>, Vec<AffiliationCountObject>), sqlx::Error>
+  where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+    // language=SQL
+    let matched = sqlx::query_as::<_, ChannelMatchObject>(r#"
+        SELECT channels.*, livers.name AS liver_name, liver_readings.reading AS liver_reading, livers.affiliation_id,
+               ts_headline('simple', coalesce(channels.description, ''), websearch_to_tsquery('simple', $1),
+                           'StartSel=<em>, StopSel=</em>') AS highlighted_description
+          FROM channels
+          LEFT JOIN livers ON livers.liver_id = channels.liver_id
+          LEFT JOIN liver_readings ON liver_readings.liver_id = channels.liver_id
+         WHERE ($2::BIGINT IS NULL OR livers.affiliation_id = $2)
+           AND (channels.search_vector @@ websearch_to_tsquery('simple', $1)
+                OR $1 <% livers.name OR $1 <% livers.localized_name OR $4 <% liver_readings.reading)
+         ORDER BY ts_rank(channels.search_vector, websearch_to_tsquery('simple', $1))
+                  + coalesce(GREATEST(word_similarity($1, livers.name), word_similarity($1, livers.localized_name),
+                                      word_similarity($4, liver_readings.reading)), 0) DESC,
+                  channels.channel_id
+         LIMIT $3
+    "#).bind(query)
+       .bind(affiliation_id)
+       .bind(limit)
+       .bind(reading)
+       .fetch_all(transaction)
+       .await?;
+
+    // language=SQL
+    let counts = sqlx::query_as::<_, AffiliationCountObject>(r#"
+        SELECT livers.affiliation_id, COUNT(*) AS count
+          FROM channels
+          LEFT JOIN livers ON livers.liver_id = channels.liver_id
+          LEFT JOIN liver_readings ON liver_readings.liver_id = channels.liver_id
+         WHERE ($2::BIGINT IS NULL OR livers.affiliation_id = $2)
+           AND (channels.search_vector @@ websearch_to_tsquery('simple', $1)
+                OR $1 <% livers.name OR $1 <% livers.localized_name OR $3 <% liver_readings.reading)
+         GROUP BY livers.affiliation_id
+    "#).bind(query)
+       .bind(affiliation_id)
+       .bind(reading)
+       .fetch_all(transaction)
+       .await?;
+    Ok((matched, counts))
+}
+
+/// Videos whose title or description matches the query.
+///
+/// Titles are also compared by trigram word similarity, so that small typos still match.
+pub async fn search_videos<'a, E>(
+    query: &str,
+    affiliation_id: Option<i64>,
+    limit: i64,
+    transaction: E
+) -> Result<(Vec<VideoMatchObject>, Vec<AffiliationCountObject>), sqlx::Error>
+  where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+    // language=SQL
+    let matched = sqlx::query_as::<_, VideoMatchObject>(r#"
+        SELECT videos.*, channels.logo_url, livers.liver_id, livers.name AS liver_name, livers.affiliation_id,
+               ts_headline('simple', videos.title, websearch_to_tsquery('simple', $1),
+                           'StartSel=<em>, StopSel=</em>, HighlightAll=true') AS highlighted_title,
+               ts_headline('simple', coalesce(videos.description, ''), websearch_to_tsquery('simple', $1),
+                           'StartSel=<em>, StopSel=</em>') AS highlighted_description
+          FROM videos
+          LEFT JOIN channels ON channels.channel_id = videos.channel_id
+          LEFT JOIN livers ON livers.liver_id = channels.liver_id
+         WHERE ($2::BIGINT IS NULL OR livers.affiliation_id = $2)
+           AND (videos.search_vector @@ websearch_to_tsquery('simple', $1) OR $1 <% videos.title)
+         ORDER BY ts_rank(videos.search_vector, websearch_to_tsquery('simple', $1)) + word_similarity($1, videos.title) DESC,
+                  videos.published_at DESC NULLS LAST
+         LIMIT $3
+    "#).bind(query)
+       .bind(affiliation_id)
+       .bind(limit)
+       .fetch_all(transaction)
+       .await?;
+
+    // language=SQL
+    let counts = sqlx::query_as::<_, AffiliationCountObject>(r#"
+        SELECT livers.affiliation_id, COUNT(*) AS count
+          FROM videos
+          LEFT JOIN channels ON channels.channel_id = videos.channel_id
+          LEFT JOIN livers ON livers.liver_id = channels.liver_id
+         WHERE ($2::BIGINT IS NULL OR livers.affiliation_id = $2)
+           AND (videos.search_vector @@ websearch_to_tsquery('simple', $1) OR $1 <% videos.title)
+         GROUP BY livers.affiliation_id
+    "#).bind(query)
+       .bind(affiliation_id)
+       .fetch_all(transaction)
+       .await?;
+    Ok((matched, counts))
+}
+
+/// Livers whose reading has not been written yet, e.g. those created before `liver_readings`.
+pub async fn fetch_livers_without_reading<'a, E>(transaction: E) -> Result<Vec<LiverObject>, sqlx::Error>
+  where E: sqlx::Executor<'a, Database = Postgres> {
+    // language=SQL
+    sqlx::query_as::<_, LiverObject>(r#"
+        SELECT livers.*
+          FROM livers
+         WHERE NOT EXISTS(SELECT 1 FROM liver_readings WHERE liver_readings.liver_id = livers.liver_id)
+         ORDER BY livers.liver_id
+    "#).fetch_all(transaction)
+       .await
+}
+
+/// Writes readings of livers, `readings` in the same order as `liver_ids`.
+pub async fn upsert_liver_readings<'a, E>(liver_ids: &[i64], readings: &[String], transaction: E) -> Result<(), sqlx::Error>
+  where E: sqlx::Executor<'a, Database = Postgres> {
+    // language=SQL
+    sqlx::query(r#"
+        INSERT INTO liver_readings (liver_id, reading)
+        SELECT * FROM UNNEST($1::BIGINT[], $2::TEXT[])
+            ON CONFLICT (liver_id) DO UPDATE SET reading = excluded.reading
+    "#).bind(liver_ids)
+       .bind(readings)
+       .execute(transaction)
+       .await?;
+    Ok(())
+}
@@ -0,0 +1,177 @@
+#![allow(dead_code)]
+
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use sqlx::Postgres;
+
+use super::api_key_object::{hash_key, random_key};
+
+/// Refresh tokens start with this, so that they are told apart from access tokens.
+pub const REFRESH_TOKEN_PREFIX: &str = "mtr_";
+
+/// Refresh token of a Cage account. Only its hash is stored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
+pub struct RefreshTokenObject {
+    token_id: i64,
+    account_id: i64,
+    expires_at: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>
+}
+
+impl Display for RefreshTokenObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "refresh token >> {}: account {}", self.token_id, self.account_id)
+    }
+}
+
+impl RefreshTokenObject {
+    pub fn token_id(&self) -> i64 {
+        self.token_id
+    }
+
+    pub fn account_id(&self) -> i64 {
+        self.account_id
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn revoked_at(&self) -> Option<DateTime<Utc>> {
+        self.revoked_at
+    }
+}
+
+impl RefreshTokenObject {
+    /// Store a new random token of the account, and return it along with the stored row.
+    pub async fn issue<'a, E>(account_id: i64, expires_at: DateTime<Utc>, transaction: E) -> Result<(Self, String), sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        let token = random_key(REFRESH_TOKEN_PREFIX, 32)?;
+        // language=SQL
+        let issued = sqlx::query_as::<_, Self>(r#"
+            INSERT INTO refresh_tokens (account_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            RETURNING token_id, account_id, expires_at, created_at, revoked_at
+        "#).bind(account_id)
+           .bind(hash_key(&token))
+           .bind(expires_at)
+           .fetch_one(transaction)
+           .await?;
+        Ok((issued, token))
+    }
+
+    /// The token as stored, whether it is still usable or not.
+    pub async fn fetch_from_token<'a, E>(token: &str, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let found = sqlx::query_as::<_, Self>(r#"
+            SELECT token_id, account_id, expires_at, created_at, revoked_at
+              FROM refresh_tokens
+             WHERE token_hash = $1
+        "#).bind(hash_key(token))
+           .fetch_optional(transaction)
+           .await?;
+        Ok(found)
+    }
+
+    /// Revoke the token when it is neither revoked nor expired yet, so that it is used only once.
+    ///
+    /// [Ok()]: `None` - The token is unknown, revoked or expired.
+    pub async fn consume<'a, E>(token: &str, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let consumed = sqlx::query_as::<_, Self>(r#"
+            UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP
+             WHERE token_hash = $1 AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            RETURNING token_id, account_id, expires_at, created_at, revoked_at
+        "#).bind(hash_key(token))
+           .fetch_optional(transaction)
+           .await?;
+        Ok(consumed)
+    }
+
+    /// Revoke every token of the account, returning how many were still usable.
+    pub async fn revoke_all<'a, E>(account_id: i64, transaction: E) -> Result<u64, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let revoked = sqlx::query(r#"
+            UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP
+             WHERE account_id = $1 AND revoked_at IS NULL
+        "#).bind(account_id)
+           .execute(transaction)
+           .await?;
+        Ok(revoked.rows_affected())
+    }
+}
+
+/// Access token revoked before it expires, by its `jti` claim.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
+pub struct RevokedTokenObject {
+    jti: String,
+    account_id: i64,
+    expires_at: DateTime<Utc>,
+    revoked_at: DateTime<Utc>
+}
+
+impl Display for RevokedTokenObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "revoked token >> {}: account {}", self.jti, self.account_id)
+    }
+}
+
+impl RevokedTokenObject {
+    pub fn jti(&self) -> &str {
+        &self.jti
+    }
+
+    pub fn account_id(&self) -> i64 {
+        self.account_id
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    pub fn revoked_at(&self) -> DateTime<Utc> {
+        self.revoked_at
+    }
+}
+
+impl RevokedTokenObject {
+    /// Add the token to the revocation list. Tokens that expired meanwhile are dropped from it.
+    pub async fn revoke(jti: &str, account_id: i64, expires_at: DateTime<Utc>, transaction: &mut sqlx::Transaction<'_, Postgres>) -> Result<Self, sqlx::Error> {
+        // language=SQL
+        sqlx::query(r#"
+            DELETE FROM revoked_tokens WHERE expires_at < CURRENT_TIMESTAMP
+        "#).execute(&mut *transaction)
+           .await?;
+        // language=SQL
+        let revoked = sqlx::query_as::<_, Self>(r#"
+            INSERT INTO revoked_tokens (jti, account_id, expires_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (jti) DO UPDATE SET jti = EXCLUDED.jti
+            RETURNING jti, account_id, expires_at, revoked_at
+        "#).bind(jti)
+           .bind(account_id)
+           .bind(expires_at)
+           .fetch_one(&mut *transaction)
+           .await?;
+        Ok(revoked)
+    }
+
+    pub async fn is_revoked<'a, E>(jti: &str, transaction: E) -> Result<bool, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> {
+        // language=SQL
+        let revoked = sqlx::query_scalar::<_, bool>(r#"
+            SELECT EXISTS(SELECT 1 FROM revoked_tokens WHERE jti = $1)
+        "#).bind(jti)
+           .fetch_one(transaction)
+           .await?;
+        Ok(revoked)
+    }
+}
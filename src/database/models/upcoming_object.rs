@@ -1,11 +1,11 @@
 #![allow(dead_code)]
 
 use std::fmt::{Display, Formatter};
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Utc};
 use sqlx::{Row, Postgres, Transaction};
 
-use super::{Accessor, hash, Fetch};
-use super::id_object::{ChannelId, VideoId};
+use super::{Accessor, contains_pattern, hash, Fetch, Scoped, Table};
+use super::id_object::{AffiliationId, ChannelId, VideoId};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
 pub struct VideoObject {
@@ -13,10 +13,11 @@ pub struct VideoObject {
     channel_id: Option<ChannelId>,
     title: String,
     description: String,
-    published_at: Option<DateTime<Local>>,
-    updated_at: Option<DateTime<Local>>,
-    will_start_at: Option<DateTime<Local>>,
-    started_at: Option<DateTime<Local>>,
+    published_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    will_start_at: Option<DateTime<Utc>>,
+    started_at: Option<DateTime<Utc>>,
+    ended_at: Option<DateTime<Utc>>,
     thumbnail_url: String
 }
 
@@ -37,27 +38,62 @@ impl VideoObject {
         &self.description
     }
 
-    pub fn published_at(&self) -> Option<DateTime<Local>> {
+    pub fn published_at(&self) -> Option<DateTime<Utc>> {
         self.published_at
     }
 
-    pub fn updated_at(&self) -> Option<DateTime<Local>> {
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
         self.updated_at
     }
 
-    pub fn will_start_at(&self) -> Option<DateTime<Local>> {
+    pub fn will_start_at(&self) -> Option<DateTime<Utc>> {
         self.will_start_at
     }
 
-    pub fn started_at(&self) -> Option<DateTime<Local>> {
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
         self.started_at
     }
 
+    /// Set once a stream has ended and its archive is up.
+    pub fn ended_at(&self) -> Option<DateTime<Utc>> {
+        self.ended_at
+    }
+
     pub fn thumbnail_url(&self) -> &str {
         &self.thumbnail_url
     }
 }
 
+impl VideoObject {
+    pub async fn fetch_from_id<'a, E>(id: &VideoId, transaction: E) -> Result<Option<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let searched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM videos WHERE video_id = $1
+        "#).bind(id)
+           .fetch_optional(transaction)
+           .await?;
+        Ok(searched)
+    }
+
+    /// At most `limit` videos whose id is `query` or whose title contains it, the latest first.
+    pub async fn fetch_matching<'a, E>(query: &str, limit: i64, transaction: E) -> Result<Vec<Self>, sqlx::Error>
+        where E: sqlx::Executor<'a, Database = Postgres> + Copy {
+        // language=SQL
+        let matched = sqlx::query_as::<_, Self>(r#"
+            SELECT * FROM videos
+             WHERE $1 = '' OR video_id = $1 OR channel_id = $1 OR title ILIKE $3 ESCAPE '\'
+             ORDER BY COALESCE(will_start_at, published_at) DESC NULLS LAST, video_id
+             LIMIT $2
+        "#).bind(query)
+           .bind(limit)
+           .bind(contains_pattern(query))
+           .fetch_all(transaction)
+           .await?;
+        Ok(matched)
+    }
+}
+
 impl Display for VideoObject {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "live(video) >> {}, title: {}", self.video_id, self.title)
@@ -66,14 +102,16 @@ impl Display for VideoObject {
 
 #[async_trait::async_trait]
 impl Accessor for VideoObject {
+    const TABLE: Table = Table::Videos;
+
     async fn insert(self, transaction: &mut Transaction<'_, Postgres>) -> Result<Self, sqlx::Error> {
         // language=SQL
         let insert = sqlx::query_as::<_, Self>(r#"
             INSERT INTO videos
                 (video_id, channel_id, title, description,
-                published_at, updated_at, will_start_at, started_at,
+                published_at, updated_at, will_start_at, started_at, ended_at,
                 thumbnail_url)
-              VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+              VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
             RETURNING *
         "#).bind(&self.video_id)
            .bind(&self.channel_id)
@@ -83,38 +121,65 @@ impl Accessor for VideoObject {
            .bind(self.updated_at)
            .bind(self.will_start_at)
            .bind(self.started_at)
+           .bind(self.ended_at)
            .bind(&self.thumbnail_url)
            .fetch_one(&mut *transaction)
            .await?;
+        // language=SQL
+        sqlx::query(r#"
+            DELETE FROM video_tombstones WHERE video_id = $1
+        "#).bind(&self.video_id)
+           .execute(&mut *transaction)
+           .await?;
         Ok(insert)
     }
 
     async fn delete(self, transaction: &mut Transaction<'_, Postgres>) -> Result<Self, sqlx::Error> {
         // language=SQL
         let delete = sqlx::query_as::<_, Self>(r#"
-            DELETE FROM videos WHERE video_id LIKE $1 RETURNING *
+            DELETE FROM videos WHERE video_id = $1 RETURNING *
         "#).bind(&self.video_id)
            .fetch_one(&mut *transaction)
            .await?;
+        // language=SQL
+        sqlx::query(r#"
+            INSERT INTO video_tombstones (video_id, channel_id, title, will_start_at)
+             VALUES ($1, $2, $3, $4)
+            ON CONFLICT (video_id) DO UPDATE
+              SET channel_id = $2, title = $3, will_start_at = $4, deleted_at = CURRENT_TIMESTAMP
+        "#).bind(&delete.video_id)
+           .bind(&delete.channel_id)
+           .bind(&delete.title)
+           .bind(delete.will_start_at)
+           .execute(&mut *transaction)
+           .await?;
         Ok(delete)
     }
 
     async fn update(self, transaction: &mut Transaction<'_, Postgres>) -> Result<(Self, Self), sqlx::Error> {
         // language=SQL
         let old = sqlx::query_as::<_, Self>(r#"
-            SELECT * FROM videos WHERE video_id LIKE $1
+            SELECT * FROM videos WHERE video_id = $1
         "#).bind(&self.video_id)
             .fetch_one(&mut *transaction)
             .await?;
+        // `updated_at` never moves back, and rescheduling moves it forward even if the collector did not send a newer one.
         // language=SQL
         let new = sqlx::query_as::<_, Self>(r#"
-            UPDATE videos SET title = $1, description = $2, updated_at = $3, will_start_at = $4, started_at = $5 WHERE video_id LIKE $6
+            UPDATE videos SET title = $1, description = $2,
+              updated_at = GREATEST($3, updated_at, CASE WHEN will_start_at IS DISTINCT FROM $4 THEN CURRENT_TIMESTAMP END),
+              will_start_at = $4, started_at = $5, ended_at = $6,
+              channel_id = $7, published_at = $8, thumbnail_url = $9 WHERE video_id = $10
             RETURNING *
         "#).bind(&self.title)
            .bind(&self.description)
            .bind(self.updated_at)
            .bind(self.will_start_at)
            .bind(self.started_at)
+           .bind(self.ended_at)
+           .bind(&self.channel_id)
+           .bind(self.published_at)
+           .bind(&self.thumbnail_url)
            .bind(&self.video_id)
            .fetch_one(&mut *transaction)
            .await?;
@@ -124,7 +189,7 @@ impl Accessor for VideoObject {
     async fn exists(&self, transaction: &mut Transaction<'_, Postgres>) -> Result<bool, sqlx::Error> {
         // language=SQL
         let video_exists = sqlx::query(r#"
-            SELECT EXISTS(SELECT 1 FROM videos WHERE video_id LIKE $1)
+            SELECT EXISTS(SELECT 1 FROM videos WHERE video_id = $1)
         "#).bind(&self.video_id)
             .fetch_one(&mut *transaction)
             .await?
@@ -135,14 +200,14 @@ impl Accessor for VideoObject {
     async fn compare(&self, transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Result<bool, sqlx::Error> {
         let com = sqlx::query_as::<_, Self>(r#"
             SELECT * FROM videos WHERE video_id = $1
-        "#).bind(&self.channel_id)
+        "#).bind(&self.video_id)
            .fetch_optional(&mut *transaction)
            .await?;
         
         let com = if let Some(db) = com {
-            let db = hash(&db);
-            let my = hash(&self);
-            db == my
+            // A stored `updated_at` newer than the one sent is kept by `update`, so it is no change.
+            let my = Self { updated_at: self.updated_at.max(db.updated_at), ..self.clone() };
+            hash(&db) == hash(&my)
         } else { false };
         Ok(com)
     }
@@ -166,10 +231,11 @@ pub struct InitVideoObject {
     pub channel_id: Option<ChannelId>,
     pub title: String,
     pub description: String,
-    pub published_at: Option<DateTime<Local>>,
-    pub updated_at: Option<DateTime<Local>>,
-    pub will_start_at: Option<DateTime<Local>>,
-    pub started_at: Option<DateTime<Local>>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub will_start_at: Option<DateTime<Utc>>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
     pub thumbnail_url: String,
     #[doc(hidden)]
     pub init: ()
@@ -186,6 +252,7 @@ impl Default for InitVideoObject {
             updated_at: None,
             will_start_at: None,
             started_at: None,
+            ended_at: None,
             thumbnail_url: "none".to_string(),
             init: ()
         }
@@ -203,6 +270,7 @@ impl InitVideoObject {
             updated_at: self.updated_at,
             will_start_at: self.will_start_at,
             started_at: self.started_at,
+            ended_at: self.ended_at,
             thumbnail_url: self.thumbnail_url,
         }
     }
@@ -219,8 +287,35 @@ impl VideoObject {
             updated_at: self.updated_at,
             will_start_at: self.will_start_at,
             started_at: self.started_at,
+            ended_at: self.ended_at,
             thumbnail_url: self.thumbnail_url,
             init: ()
         }
     }
-}
\ No newline at end of file
+}
+#[async_trait::async_trait]
+impl Scoped for VideoObject {
+    async fn affiliations(&self, transaction: &mut Transaction<'_, Postgres>) -> Result<Vec<Option<AffiliationId>>, sqlx::Error> {
+        // language=SQL
+        let given = sqlx::query_scalar::<_, Option<AffiliationId>>(r#"
+            SELECT livers.affiliation_id
+              FROM channels
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE channels.channel_id = $1
+        "#).bind(&self.channel_id)
+           .fetch_optional(&mut *transaction)
+           .await?
+           .flatten();
+        // language=SQL
+        let stored = sqlx::query_scalar::<_, Option<AffiliationId>>(r#"
+            SELECT livers.affiliation_id
+              FROM videos
+              LEFT JOIN channels ON channels.channel_id = videos.channel_id
+              LEFT JOIN livers ON livers.liver_id = channels.liver_id
+             WHERE videos.video_id = $1
+        "#).bind(&self.video_id)
+           .fetch_optional(&mut *transaction)
+           .await?;
+        Ok(std::iter::once(given).chain(stored).collect())
+    }
+}
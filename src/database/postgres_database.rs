@@ -1,4 +1,5 @@
-use sqlx::{Postgres, Connection, PgConnection};
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Connection, Executor, PgConnection};
 use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::migrate::{Migrate, MigrateDatabase, Migrator};
@@ -20,6 +21,11 @@ pub async fn connect() -> Result<PgPool, sqlx::Error> {
             .and_then(|f| f.parse().ok())
             .unwrap_or(8)
         )
+        .after_connect(|connection, _| Box::pin(async move {
+            // Thresholds of the `<%` operator of pg_trgm, low enough to tolerate a typo in a word.
+            connection.execute("SET pg_trgm.word_similarity_threshold = 0.5").await?;
+            Ok(())
+        }))
         .connect(&url)
         .await?;
     tracing::info!("Build successful database connection.");
@@ -63,4 +69,25 @@ pub async fn migration() -> Result<(), sqlx::Error> {
     }
     tracing::info!("Migration successful.");
     Ok(())
-}
\ No newline at end of file
+}
+/// Newest migration applied to the database, and the number of applied ones.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    pub applied: i64
+}
+
+pub async fn latest_migration<'a, E>(transaction: E) -> Result<Option<AppliedMigration>, sqlx::Error>
+  where E: sqlx::Executor<'a, Database = Postgres> {
+    // language=SQL
+    sqlx::query_as::<_, AppliedMigration>(r#"
+        SELECT version, description, installed_on, COUNT(*) OVER () AS applied
+          FROM _sqlx_migrations
+         WHERE success
+         ORDER BY version DESC
+         LIMIT 1
+    "#).fetch_optional(transaction)
+       .await
+}
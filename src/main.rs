@@ -7,6 +7,7 @@ mod database;
 mod models;
 mod server;
 mod routing;
+mod cli;
 
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
@@ -32,7 +33,31 @@ async fn main() -> Result<(), std::io::Error> {
         .await
         .expect("An Error occurred by database connection pool.");
 
-    server::server_run(pool).await;
+    let args = std::env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("reindex") => {
+            let search = server::search::SearchEngine::from_env();
+            search.configure(&pool)
+                .await
+                .expect("An Error occurred by search index configuration.");
+            search.reindex(&pool)
+                .await
+                .expect("An Error occurred by search reindexing.");
+        }
+        Some("api-key") => {
+            if let Err(reason) = cli::api_key(&args[2..], &pool).await {
+                eprintln!("{}", reason);
+                std::process::exit(2);
+            }
+        }
+        Some("account") => {
+            if let Err(reason) = cli::account(&args[2..], &pool).await {
+                eprintln!("{}", reason);
+                std::process::exit(2);
+            }
+        }
+        _ => server::server_run(pool).await
+    }
 
     Ok(())
 }
\ No newline at end of file
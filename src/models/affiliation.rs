@@ -1,12 +1,21 @@
+use std::collections::BTreeMap;
 use serde::{Serialize, Deserialize};
-use crate::database::{AffiliationId, AffiliationObject};
+use utoipa::ToSchema;
+use crate::database::{AffiliationId, AffiliationObject, DEFAULT_LOCALES};
 
 use super::NumId;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Affiliation {
+    #[schema(value_type = i64)]
     pub affiliation_id: NumId<Affiliation>,
-    pub name: String
+    pub name: String,
+    /// Name in the requested language, or the nearest one in the fallback chain.
+    #[serde(default)]
+    pub display_name: String,
+    /// Names keyed by language tag.
+    #[serde(default)]
+    pub names: BTreeMap<String, String>
 }
 
 impl From<AffiliationId> for NumId<Affiliation> {
@@ -15,11 +24,19 @@ impl From<AffiliationId> for NumId<Affiliation> {
     }
 }
 
-impl From<AffiliationObject> for Affiliation {
-    fn from(obj: AffiliationObject) -> Self {
+impl Affiliation {
+    pub fn localized(obj: AffiliationObject, locales: &[impl AsRef<str>]) -> Self {
         Self {
             affiliation_id: NumId::from(obj.affiliation_id()),
-            name: obj.name().to_owned()
+            name: obj.name().to_owned(),
+            display_name: obj.display_name(locales).to_owned(),
+            names: obj.names().iter().map(|(locale, name)| (locale.to_owned(), name.to_owned())).collect()
         }
     }
+}
+
+impl From<AffiliationObject> for Affiliation {
+    fn from(obj: AffiliationObject) -> Self {
+        Self::localized(obj, &DEFAULT_LOCALES)
+    }
 }
\ No newline at end of file
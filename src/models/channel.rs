@@ -1,17 +1,29 @@
-use chrono::{DateTime, Local};
+use std::collections::BTreeMap;
+use chrono::{DateTime, FixedOffset};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
-use crate::database::{ChannelId, ChannelObject};
+use utoipa::ToSchema;
+use crate::database::{ChannelId, ChannelObject, DEFAULT_LOCALES};
 
 use super::{NumId, StringId};
 use super::liver::Liver;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Channel {
+    #[schema(value_type = String)]
     pub channel_id: StringId<Channel>,
+    #[schema(value_type = Option<i64>)]
     pub liver_id: Option<NumId<Liver>>,
     pub logo_url: String,
-    pub published_at: DateTime<Local>,
-    pub description: String
+    /// In the requested time zone, UTC by default.
+    pub published_at: Option<DateTime<FixedOffset>>,
+    pub description: String,
+    /// Name in the requested language, or the nearest one in the fallback chain.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Names keyed by language tag.
+    #[serde(default)]
+    pub names: BTreeMap<String, String>
 }
 
 impl From<ChannelId> for StringId<Channel> {
@@ -20,14 +32,28 @@ impl From<ChannelId> for StringId<Channel> {
     }
 }
 
-impl From<ChannelObject> for Channel {
-    fn from(obj: ChannelObject) -> Self {
+impl Channel {
+    pub fn localized(obj: ChannelObject, locales: &[impl AsRef<str>]) -> Self {
         Self {
             channel_id: StringId::from(obj.channel_id().to_owned()),
             liver_id: obj.liver_id().map(NumId::from),
             logo_url: obj.logo_url().to_owned(),
-            published_at: obj.published_at(),
-            description: obj.description().to_owned()
+            published_at: obj.published_at().map(|at| at.fixed_offset()),
+            description: obj.description().to_owned(),
+            display_name: obj.display_name(locales).map(str::to_owned),
+            names: obj.names().iter().map(|(locale, name)| (locale.to_owned(), name.to_owned())).collect()
         }
     }
+
+    /// Give the timestamps in `zone`.
+    pub fn in_zone(mut self, zone: &Tz) -> Self {
+        self.published_at = self.published_at.map(|at| at.with_timezone(zone).fixed_offset());
+        self
+    }
+}
+
+impl From<ChannelObject> for Channel {
+    fn from(obj: ChannelObject) -> Self {
+        Self::localized(obj, &DEFAULT_LOCALES)
+    }
 }
\ No newline at end of file
@@ -1,15 +1,25 @@
+use std::collections::BTreeMap;
 use serde::{Serialize, Deserialize};
-use crate::database::{LiverId, LiverObject};
+use utoipa::ToSchema;
+use crate::database::{LiverId, LiverObject, DEFAULT_LOCALES};
 
 use super::NumId;
 use super::affiliation::Affiliation;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Liver {
+    #[schema(value_type = i64)]
     pub liver_id: NumId<Liver>,
+    #[schema(value_type = Option<i64>)]
     pub affiliation: Option<NumId<Affiliation>>,
     pub name: String,
     pub localized_name: String,
+    /// Name in the requested language, or the nearest one in the fallback chain.
+    #[serde(default)]
+    pub display_name: String,
+    /// Names keyed by language tag.
+    #[serde(default)]
+    pub names: BTreeMap<String, String>,
     pub logo_url: String,
 }
 
@@ -19,14 +29,22 @@ impl From<LiverId> for NumId<Liver> {
     }
 }
 
-impl From<LiverObject> for Liver {
-    fn from(obj: LiverObject) -> Self {
+impl Liver {
+    pub fn localized(obj: LiverObject, locales: &[impl AsRef<str>]) -> Self {
         Self {
             liver_id: NumId::from(obj.liver_id()),
             affiliation: obj.affiliation_id().map(NumId::from),
             name: obj.name().to_owned(),
             localized_name: obj.localized_name().to_owned(),
+            display_name: obj.display_name(locales).to_owned(),
+            names: obj.names().iter().map(|(locale, name)| (locale.to_owned(), name.to_owned())).collect(),
             logo_url: format!("[unimplemented feature] https://reiva.dev/api/resources/logos/{}", i64::from(obj.liver_id()))
         }
     }
+}
+
+impl From<LiverObject> for Liver {
+    fn from(obj: LiverObject) -> Self {
+        Self::localized(obj, &DEFAULT_LOCALES)
+    }
 }
\ No newline at end of file
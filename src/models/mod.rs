@@ -2,12 +2,22 @@ mod affiliation;
 mod channel;
 mod liver;
 mod upcoming;
+mod search;
+mod suggest;
+mod schedule;
+mod version;
+mod record;
 
 pub use self::{
     affiliation::Affiliation,
     channel::Channel,
     liver::Liver,
     upcoming::Video,
+    search::{AffiliationFacet, SearchHit, SearchHits, SearchResult},
+    suggest::{AffiliationSuggestion, ChannelSuggestion, LiverSuggestion, Suggestions},
+    schedule::{Schedule, ScheduleDay, ScheduleEntry, ScheduleHour},
+    version::{ApiInfo, ApiVersionInfo, BuildInfo, MigrationInfo},
+    record::{AffiliationRecord, ChannelRecord, LiverRecord, VideoRecord},
 
     id::{NumId, StringId}
 };
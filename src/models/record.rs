@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+use crate::database::{
+    AffiliationObject, ChannelId, ChannelObject, InitChannelObject, InitVideoObject,
+    LiverId, LiverObject, LocalizedNames, VideoId, VideoObject
+};
+
+// Records are the stored rows as they are read and written through the admin api,
+// without the fields that are derived for display.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AffiliationRecord {
+    pub affiliation_id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub names: BTreeMap<String, String>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct LiverRecord {
+    pub liver_id: i64,
+    #[serde(default)]
+    pub affiliation_id: Option<i64>,
+    pub name: String,
+    pub localized_name: String,
+    #[serde(default)]
+    pub names: BTreeMap<String, String>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ChannelRecord {
+    pub channel_id: String,
+    #[serde(default)]
+    pub liver_id: Option<i64>,
+    pub logo_url: String,
+    #[serde(default)]
+    pub published_at: Option<DateTime<Utc>>,
+    pub description: String,
+    #[serde(default)]
+    pub names: BTreeMap<String, String>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct VideoRecord {
+    pub video_id: String,
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    pub title: String,
+    pub description: String,
+    #[serde(default)]
+    pub published_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub will_start_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Defaults to the thumbnail of the video on Youtube.
+    #[serde(default)]
+    pub thumbnail_url: Option<String>
+}
+
+fn names(names: &LocalizedNames) -> BTreeMap<String, String> {
+    names.iter()
+        .map(|(locale, name)| (locale.to_owned(), name.to_owned()))
+        .collect()
+}
+
+impl From<AffiliationObject> for AffiliationRecord {
+    fn from(obj: AffiliationObject) -> Self {
+        Self {
+            affiliation_id: obj.affiliation_id().into(),
+            name: obj.name().to_owned(),
+            names: names(obj.names())
+        }
+    }
+}
+
+impl From<AffiliationRecord> for AffiliationObject {
+    fn from(record: AffiliationRecord) -> Self {
+        AffiliationObject::new(record.affiliation_id, record.name)
+            .with_names(record.names.into_iter().collect())
+    }
+}
+
+impl From<LiverObject> for LiverRecord {
+    fn from(obj: LiverObject) -> Self {
+        Self {
+            liver_id: obj.liver_id().into(),
+            affiliation_id: obj.affiliation_id().map(Into::into),
+            name: obj.name().to_owned(),
+            localized_name: obj.localized_name().to_owned(),
+            names: names(obj.names())
+        }
+    }
+}
+
+impl From<LiverRecord> for LiverObject {
+    fn from(record: LiverRecord) -> Self {
+        LiverObject::new(record.liver_id, record.affiliation_id, record.name, record.localized_name)
+            .with_names(record.names.into_iter().collect())
+    }
+}
+
+impl From<ChannelObject> for ChannelRecord {
+    fn from(obj: ChannelObject) -> Self {
+        Self {
+            channel_id: obj.channel_id().to_owned().into(),
+            liver_id: obj.liver_id().map(Into::into),
+            logo_url: obj.logo_url().to_owned(),
+            published_at: obj.published_at(),
+            description: obj.description().to_owned(),
+            names: names(obj.names())
+        }
+    }
+}
+
+impl From<ChannelRecord> for ChannelObject {
+    fn from(record: ChannelRecord) -> Self {
+        InitChannelObject {
+            channel_id: ChannelId::new(record.channel_id),
+            liver_id: record.liver_id.map(LiverId::new),
+            logo_url: record.logo_url,
+            published_at: record.published_at,
+            description: record.description,
+            names: record.names.into_iter().collect(),
+            ..Default::default()
+        }.build()
+    }
+}
+
+impl From<VideoObject> for VideoRecord {
+    fn from(obj: VideoObject) -> Self {
+        let pubs = obj.decompose();
+        Self {
+            video_id: pubs.video_id.into(),
+            channel_id: pubs.channel_id.map(Into::into),
+            title: pubs.title,
+            description: pubs.description,
+            published_at: pubs.published_at,
+            updated_at: pubs.updated_at,
+            will_start_at: pubs.will_start_at,
+            started_at: pubs.started_at,
+            ended_at: pubs.ended_at,
+            thumbnail_url: Some(pubs.thumbnail_url)
+        }
+    }
+}
+
+impl From<VideoRecord> for VideoObject {
+    fn from(record: VideoRecord) -> Self {
+        let thumbnail_url = record.thumbnail_url
+            .unwrap_or_else(|| format!("https://img.youtube.com/vi/{}/maxresdefault.jpg", record.video_id));
+        InitVideoObject {
+            video_id: VideoId::new(record.video_id),
+            channel_id: record.channel_id.map(ChannelId::new),
+            title: record.title,
+            description: record.description,
+            published_at: record.published_at,
+            updated_at: record.updated_at,
+            will_start_at: record.will_start_at,
+            started_at: record.started_at,
+            ended_at: record.ended_at,
+            thumbnail_url,
+            ..Default::default()
+        }.build()
+    }
+}
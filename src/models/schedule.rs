@@ -0,0 +1,42 @@
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use super::channel::Channel;
+use super::liver::Liver;
+use super::upcoming::Video;
+
+/// Videos grouped by the local day and hour they start at.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Schedule {
+    /// IANA time zone the days are cut in.
+    pub time_zone: String,
+    pub days: Vec<ScheduleDay>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleDay {
+    #[schema(value_type = String, format = Date)]
+    pub date: NaiveDate,
+    /// Hours having a video starting in them, in order.
+    pub hours: Vec<ScheduleHour>,
+    /// Streams started on an earlier day that may still be running at midnight.
+    pub continued: Vec<ScheduleEntry>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleHour {
+    /// Local hour, from 0 to 23.
+    pub hour: u32,
+    pub entries: Vec<ScheduleEntry>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleEntry {
+    /// `started_at` once the stream started, `will_start_at` until then.
+    #[schema(value_type = String, format = DateTime)]
+    pub starts_at: DateTime<FixedOffset>,
+    pub video: Video,
+    pub channel: Option<Channel>,
+    pub liver: Option<Liver>
+}
@@ -0,0 +1,40 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use super::NumId;
+use super::affiliation::Affiliation;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchResult {
+    pub query: String,
+    pub livers: SearchHits,
+    pub channels: SearchHits,
+    pub videos: SearchHits,
+    /// Number of matches per affiliation, summed over every index.
+    pub affiliations: Vec<AffiliationFacet>
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchHits {
+    pub estimated_total_hits: usize,
+    pub hits: Vec<SearchHit>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchHit {
+    /// Indexed document.
+    #[schema(value_type = Object)]
+    pub document: Value,
+    /// Same document with matched terms wrapped in `<em>`.
+    #[schema(value_type = Object)]
+    pub highlighted: Value
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AffiliationFacet {
+    #[schema(value_type = i64)]
+    pub affiliation_id: NumId<Affiliation>,
+    pub name: Option<String>,
+    pub count: usize
+}
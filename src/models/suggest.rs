@@ -0,0 +1,43 @@
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use super::{NumId, StringId};
+use super::affiliation::Affiliation;
+use super::channel::Channel;
+use super::liver::Liver;
+
+/// Suggestions grouped by type, best first in each group.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Suggestions {
+    pub query: String,
+    pub affiliations: Vec<AffiliationSuggestion>,
+    pub livers: Vec<LiverSuggestion>,
+    pub channels: Vec<ChannelSuggestion>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AffiliationSuggestion {
+    #[schema(value_type = i64)]
+    pub affiliation_id: NumId<Affiliation>,
+    pub name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LiverSuggestion {
+    #[schema(value_type = i64)]
+    pub liver_id: NumId<Liver>,
+    #[schema(value_type = Option<i64>)]
+    pub affiliation_id: Option<NumId<Affiliation>>,
+    /// Name in the requested language, or the nearest one in the fallback chain.
+    pub name: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChannelSuggestion {
+    #[schema(value_type = String)]
+    pub channel_id: StringId<Channel>,
+    #[schema(value_type = Option<i64>)]
+    pub liver_id: Option<NumId<Liver>>,
+    /// Name of the channel in the requested language, or that of its liver when the channel has none.
+    pub name: Option<String>
+}
@@ -1,21 +1,27 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, FixedOffset, Utc};
+use chrono_tz::Tz;
 use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
 use crate::database::{VideoId, VideoObject};
 
 use super::StringId;
 use super::channel::Channel;
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Video {
+    #[schema(value_type = String)]
     pub video_id: StringId<Video>,
+    #[schema(value_type = Option<String>)]
     pub channel_id: Option<StringId<Channel>>,
     pub title: String,
     pub description: String,
-    pub published_at: Option<DateTime<Local>>,
-    pub updated_at: Option<DateTime<Local>>,
-    pub will_start_at: Option<DateTime<Local>>,
-    pub started_at: Option<DateTime<Local>>,
+    /// Timestamps are in the requested time zone, UTC by default.
+    pub published_at: Option<DateTime<FixedOffset>>,
+    pub updated_at: Option<DateTime<FixedOffset>>,
+    pub will_start_at: Option<DateTime<FixedOffset>>,
+    pub started_at: Option<DateTime<FixedOffset>>,
+    pub ended_at: Option<DateTime<FixedOffset>>,
     pub thumbnail_url: String
 }
 
@@ -25,18 +31,30 @@ impl From<VideoId> for StringId<Video> {
     }
 }
 
+impl Video {
+    /// Give the timestamps in `zone`.
+    pub fn in_zone(mut self, zone: &Tz) -> Self {
+        for at in [&mut self.published_at, &mut self.updated_at, &mut self.will_start_at, &mut self.started_at, &mut self.ended_at] {
+            *at = at.map(|at| at.with_timezone(zone).fixed_offset());
+        }
+        self
+    }
+}
+
 impl From<VideoObject> for Video {
     fn from(database: VideoObject) -> Self {
         let pubs = database.decompose();
+        let render = |at: Option<DateTime<Utc>>| at.map(|at| at.fixed_offset());
         Self {
             video_id: StringId::from(pubs.video_id.clone()),
             channel_id: pubs.channel_id.clone().map(StringId::from),
             title: pubs.title.clone(),
             description: pubs.description.clone(),
-            published_at: pubs.published_at,
-            updated_at: pubs.updated_at,
-            will_start_at: pubs.will_start_at,
-            started_at: pubs.started_at,
+            published_at: render(pubs.published_at),
+            updated_at: render(pubs.updated_at),
+            will_start_at: render(pubs.will_start_at),
+            started_at: render(pubs.started_at),
+            ended_at: render(pubs.ended_at),
             thumbnail_url: pubs.thumbnail_url
         }
     }
@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+/// What is running behind the api, taken from the build and the database.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiInfo {
+    pub api_name: String,
+    /// Version of the api the request was served by, such as `v1`.
+    pub api_version: String,
+    pub versions: Vec<ApiVersionInfo>,
+    /// Version of the package in `Cargo.toml`.
+    pub package_version: String,
+    pub build: BuildInfo,
+    /// `None` when the database could not be reached.
+    pub migration: Option<MigrationInfo>,
+    pub repository: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiVersionInfo {
+    pub name: String,
+    /// Prefix of the paths, empty for the unversioned paths.
+    pub prefix: String,
+    pub deprecated_at: Option<DateTime<Utc>>,
+    /// When the version stops being served.
+    pub sunset_at: Option<DateTime<Utc>>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BuildInfo {
+    /// `None` when built outside of a git work tree.
+    pub git_commit: Option<String>,
+    /// Whether tracked files had uncommitted changes.
+    pub git_dirty: bool,
+    pub rustc: String,
+    pub built_at: Option<DateTime<Utc>>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MigrationInfo {
+    /// Newest migration applied to the database.
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    /// Number of migrations applied.
+    pub applied: i64
+}
@@ -0,0 +1,313 @@
+use std::fmt::Display;
+use axum::{async_trait, Extension, Json};
+use axum::extract::{FromRequestParts, Path};
+use axum::http::StatusCode;
+use axum::http::request::Parts;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+
+use crate::database::{
+    with_names, Accessor, Scoped,
+    AffiliationObject, LiverId, LiverObject, ChannelId, ChannelObject, VideoId, VideoObject
+};
+use crate::models::{AffiliationRecord, ChannelRecord, LiverRecord, VideoRecord};
+use crate::server::{Principal, Write};
+use crate::server::salmon::propagate;
+use crate::server::search::{Indexed, SearchEngine};
+
+use super::{ApiError, Changed, ErrorResponse, EventHub, Notify, ResponseCache, Suggest, SuggestIndex};
+
+/// The account the request was authenticated as.
+pub async fn get_admin_account(Extension(principal): Extension<Principal>) -> Json<Principal> {
+    Json(principal)
+}
+
+/// Rows written through the admin api, addressed by their primary key.
+#[async_trait]
+pub trait Editable: Accessor + Scoped + Notify + Indexed + Suggest + Clone + PartialEq + Display + Sync + 'static {
+    type Id: DeserializeOwned + PartialEq + Display + Send + Sync;
+    /// Shape the row is read and written in as JSON.
+    type Record: Serialize + DeserializeOwned + From<Self> + Into<Self> + Send;
+
+    fn key(record: &Self::Record) -> Self::Id;
+
+    /// The row of `id` with its localized names.
+    async fn fetch(id: &Self::Id, pool: &PgPool) -> Result<Option<Self>, sqlx::Error>;
+}
+
+#[async_trait]
+impl Editable for AffiliationObject {
+    type Id = i64;
+    type Record = AffiliationRecord;
+
+    fn key(record: &Self::Record) -> Self::Id {
+        record.affiliation_id
+    }
+
+    async fn fetch(id: &Self::Id, pool: &PgPool) -> Result<Option<Self>, sqlx::Error> {
+        let found = AffiliationObject::fetch_name_from_id(*id, pool).await?;
+        Ok(with_names(found.into_iter().collect(), pool).await?.pop())
+    }
+}
+
+#[async_trait]
+impl Editable for LiverObject {
+    type Id = i64;
+    type Record = LiverRecord;
+
+    fn key(record: &Self::Record) -> Self::Id {
+        record.liver_id
+    }
+
+    async fn fetch(id: &Self::Id, pool: &PgPool) -> Result<Option<Self>, sqlx::Error> {
+        let found = LiverObject::fetch_from_id(LiverId::new(*id), pool).await?;
+        Ok(with_names(found.into_iter().collect(), pool).await?.pop())
+    }
+}
+
+#[async_trait]
+impl Editable for ChannelObject {
+    type Id = String;
+    type Record = ChannelRecord;
+
+    fn key(record: &Self::Record) -> Self::Id {
+        record.channel_id.clone()
+    }
+
+    async fn fetch(id: &Self::Id, pool: &PgPool) -> Result<Option<Self>, sqlx::Error> {
+        let found = ChannelObject::fetch_from_id(&ChannelId::new(id.as_str()), pool).await?;
+        Ok(with_names(found.into_iter().collect(), pool).await?.pop())
+    }
+}
+
+#[async_trait]
+impl Editable for VideoObject {
+    type Id = String;
+    type Record = VideoRecord;
+
+    fn key(record: &Self::Record) -> Self::Id {
+        record.video_id.clone()
+    }
+
+    async fn fetch(id: &Self::Id, pool: &PgPool) -> Result<Option<Self>, sqlx::Error> {
+        VideoObject::fetch_from_id(&VideoId::new(id.as_str()), pool).await
+    }
+}
+
+/// Writes of an authenticated account, checked against its role and scope as Salmon does,
+/// and reflected into the cache, the suggestions, the search indexes and the events once committed.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    principal: Principal,
+    pool: PgPool,
+    cache: ResponseCache,
+    events: EventHub,
+    search: SearchEngine,
+    suggest: SuggestIndex
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Editor {
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let principal = parts.extensions.get::<Principal>().cloned()
+            .ok_or_else(|| ApiError::reason("Authorization with a Cage token is required.").report(StatusCode::UNAUTHORIZED))?;
+        Ok(Self {
+            principal,
+            pool: extension(parts)?,
+            cache: extension(parts)?,
+            events: extension(parts)?,
+            search: extension(parts)?,
+            suggest: extension(parts)?
+        })
+    }
+}
+
+fn extension<T: Clone + Send + Sync + 'static>(parts: &Parts) -> Result<T, ErrorResponse> {
+    parts.extensions.get::<T>().cloned()
+        .ok_or_else(|| ApiError::reason(format!("{} is not configured.", std::any::type_name::<T>()))
+            .report(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+impl Editor {
+    pub fn principal(&self) -> &Principal {
+        &self.principal
+    }
+
+    pub async fn insert<T: Editable>(&self, item: T) -> Result<T, ErrorResponse> {
+        let mut transaction = self.pool.begin().await.map_err(rejected)?;
+        self.permit(&item, Write::Upsert, &mut transaction).await?;
+        let ins = item.insert(&mut transaction).await.map_err(rejected)?;
+        transaction.commit().await.map_err(rejected)?;
+        tracing::info!("{:<10} {} by {}", yansi::Paint::cyan("insert"), ins, self.principal);
+        self.propagate(vec![Changed::Inserted(ins.clone())]);
+        Ok(ins)
+    }
+
+    /// Apply a JSON Merge Patch to the row of `id`, returning it as it was and as it is.
+    pub async fn patch<T: Editable>(&self, id: T::Id, patch: Value) -> Result<(T, T), ErrorResponse> {
+        let current = self.fetch::<T>(&id).await?;
+        let mut document = serde_json::to_value(T::Record::from(current.clone()))
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        merge_patch(&mut document, patch);
+        let record = serde_json::from_value::<T::Record>(document)
+            .map_err(|e| ApiError::new(e).report(StatusCode::UNPROCESSABLE_ENTITY))?;
+        if T::key(&record) != id {
+            return Err(ApiError::reason(format!("Id of {} cannot be changed.", id)).report(StatusCode::BAD_REQUEST));
+        }
+        let item: T = record.into();
+
+        let mut transaction = self.pool.begin().await.map_err(rejected)?;
+        self.permit(&item, Write::Upsert, &mut transaction).await?;
+        if item.compare(&mut transaction).await.map_err(rejected)? {
+            // Nothing to write, and `updated_at` stays where it is.
+            return Ok((current.clone(), current));
+        }
+        let (old, new) = item.update(&mut transaction).await.map_err(rejected)?;
+        transaction.commit().await.map_err(rejected)?;
+        tracing::info!("{:<10} ┌ {}", yansi::Paint::yellow("update old"), old);
+        tracing::info!("{:<10} ┕ {} by {}", yansi::Paint::yellow("update new"), new, self.principal);
+        if old != new {
+            self.propagate(vec![Changed::Updated(old.clone(), new.clone())]);
+        }
+        Ok((old, new))
+    }
+
+    pub async fn delete<T: Editable>(&self, id: T::Id) -> Result<T, ErrorResponse> {
+        let current = self.fetch::<T>(&id).await?;
+        let mut transaction = self.pool.begin().await.map_err(rejected)?;
+        self.permit(&current, Write::Delete, &mut transaction).await?;
+        let del = current.delete(&mut transaction).await.map_err(rejected)?;
+        transaction.commit().await.map_err(rejected)?;
+        tracing::info!("{:<10} {} by {}", yansi::Paint::magenta("delete"), del, self.principal);
+        self.propagate(vec![Changed::Deleted(del.clone())]);
+        Ok(del)
+    }
+
+    pub async fn fetch<T: Editable>(&self, id: &T::Id) -> Result<T, ErrorResponse> {
+        T::fetch(id, &self.pool).await
+            .map_err(rejected)?
+            .ok_or_else(|| ApiError::reason(format!("{} is not found.", id)).report(StatusCode::NOT_FOUND))
+    }
+
+    async fn permit<T: Editable>(&self, item: &T, write: Write, transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Result<(), ErrorResponse> {
+        if self.principal.permits(item, write, transaction).await.map_err(rejected)? {
+            return Ok(());
+        }
+        tracing::debug!("{:<10} {} for {}", yansi::Paint::red("denied"), item, self.principal);
+        Err(ApiError::reason(format!("{} is not permitted for {}.", item, self.principal)).report(StatusCode::FORBIDDEN))
+    }
+
+    fn propagate<T: Editable>(&self, changes: Vec<Changed<T>>) {
+        propagate(changes, &self.pool, &self.cache, &self.events, &self.search, &self.suggest);
+    }
+}
+
+/// Constraint violations are the client's fault, anything else is the database's.
+fn rejected(e: sqlx::Error) -> ErrorResponse {
+    let status = match &e {
+        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+        sqlx::Error::Database(database) => match database.code().as_deref() {
+            // unique_violation, foreign_key_violation
+            Some("23505") | Some("23503") => StatusCode::CONFLICT,
+            // string_data_right_truncation, not_null_violation
+            Some("22001") | Some("23502") => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR
+        },
+        _ => StatusCode::INTERNAL_SERVER_ERROR
+    };
+    ApiError::new(e).report(status)
+}
+
+/// Apply `patch` to `target` as a JSON Merge Patch (RFC 7396).
+///
+/// Members of an object patch are merged recursively and removed when `null`, anything else replaces the target.
+fn merge_patch(target: &mut Value, patch: Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            if let Value::Object(target) = target {
+                for (key, value) in patch {
+                    if value.is_null() {
+                        target.remove(&key);
+                    } else {
+                        merge_patch(target.entry(key).or_insert(Value::Null), value);
+                    }
+                }
+            }
+        }
+        patch => *target = patch
+    }
+}
+
+/// Value of a row before and after a patch.
+#[derive(Debug, Serialize)]
+pub struct Patched<R> {
+    pub old: R,
+    pub new: R
+}
+
+/// Insert a row, answering with it as stored.
+pub async fn post_record<T: Editable>(
+    editor: Editor,
+    Json(record): Json<T::Record>
+) -> Result<(StatusCode, Json<T::Record>), ErrorResponse> {
+    let ins = editor.insert::<T>(record.into()).await?;
+    Ok((StatusCode::CREATED, Json(ins.into())))
+}
+
+/// Update a row with a JSON Merge Patch, answering with the old and the new value.
+///
+/// Localized names are patched per locale, so `{"names": {"en": null}}` removes one and `{"names": null}` all of them.
+pub async fn patch_record<T: Editable>(
+    Path(id): Path<T::Id>,
+    editor: Editor,
+    Json(patch): Json<Value>
+) -> Result<Json<Patched<T::Record>>, ErrorResponse> {
+    let (old, new) = editor.patch::<T>(id, patch).await?;
+    Ok(Json(Patched { old: old.into(), new: new.into() }))
+}
+
+/// Delete a row, answering with it as it was.
+pub async fn delete_record<T: Editable>(
+    Path(id): Path<T::Id>,
+    editor: Editor
+) -> Result<Json<T::Record>, ErrorResponse> {
+    let del = editor.delete::<T>(id).await?;
+    Ok(Json(del.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::merge_patch;
+
+    fn merged(mut target: Value, patch: Value) -> Value {
+        merge_patch(&mut target, patch);
+        target
+    }
+
+    #[test]
+    fn merge_patch_follows_rfc_7396() {
+        assert_eq!(merged(json!({"a": "b"}), json!({"a": "c"})), json!({"a": "c"}));
+        assert_eq!(merged(json!({"a": "b"}), json!({"b": "c"})), json!({"a": "b", "b": "c"}));
+        assert_eq!(merged(json!({"a": "b"}), json!({"a": null})), json!({}));
+        assert_eq!(merged(json!({"a": "b", "b": "c"}), json!({"a": null})), json!({"b": "c"}));
+        assert_eq!(merged(json!({"a": ["b"]}), json!({"a": "c"})), json!({"a": "c"}));
+        assert_eq!(merged(json!({"a": "c"}), json!({"a": ["b"]})), json!({"a": ["b"]}));
+        assert_eq!(merged(json!({"a": {"b": "c"}}), json!({"a": {"b": "d", "c": null}})), json!({"a": {"b": "d"}}));
+        assert_eq!(merged(json!({"a": [{"b": "c"}]}), json!({"a": [1]})), json!({"a": [1]}));
+        assert_eq!(merged(json!(["a", "b"]), json!(["c", "d"])), json!(["c", "d"]));
+        assert_eq!(merged(json!({"a": "b"}), json!(["c"])), json!(["c"]));
+        assert_eq!(merged(json!({"a": "foo"}), json!(null)), json!(null));
+        assert_eq!(merged(json!({"e": null}), json!({"a": 1})), json!({"e": null, "a": 1}));
+        assert_eq!(merged(json!([1, 2]), json!({"a": "b", "c": null})), json!({"a": "b"}));
+        assert_eq!(merged(json!({}), json!({"a": {"bb": {"ccc": null}}})), json!({"a": {"bb": {}}}));
+    }
+}
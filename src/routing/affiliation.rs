@@ -1,32 +1,80 @@
-use axum::{Extension, Json};
+use axum::Extension;
 use axum::extract::Path;
 use axum::http::StatusCode;
+use axum::response::Response;
 use sqlx::PgPool;
-use crate::database::{Fetch, AffiliationObject};
+use crate::database::{with_names, Fetch, AffiliationObject, Table};
 use crate::models::Affiliation;
 
 use super::ApiError;
 use super::ErrorResponse;
+use super::{Locales, Precondition, Representation, ResponseCache};
 
+#[utoipa::path(
+    get,
+    path = "/affiliations",
+    tag = "affiliation",
+    params(
+        ("lang" = Option<String>, Query, description = "Comma separated language tags to pick `display_name` in, overriding `Accept-Language`."),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages of `display_name`, falling back to `ja` and `en`."),
+    ),
+    responses(
+        (status = 200, description = "All affiliations.", body = [Affiliation]),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
 pub async fn get_affiliations(
-    Extension(pool): Extension<PgPool>
-) -> Result<Json<Vec<Affiliation>>, ErrorResponse> {
-    let aff_all = AffiliationObject::fetch_all(&pool).await
-        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?
-        .into_iter()
-        .map(Affiliation::from)
-        .collect();
-    Ok(Json(aff_all))
+    precondition: Precondition,
+    locales: Locales,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let repr = cache.get_or_fetch(Table::Affiliations, format!("all;{}", locales.key()), || async {
+        let aff_all = AffiliationObject::fetch_all(&pool).await
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        let last_modified = aff_all.iter().filter_map(AffiliationObject::updated_at).max();
+        let aff_all = aff_all.into_iter()
+            .map(|affiliation| Affiliation::localized(affiliation, locales.as_slice()))
+            .collect::<Vec<_>>();
+        Representation::json(&aff_all, last_modified)
+    }).await?;
+    Ok(Locales::vary(precondition.respond(repr)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/affiliations/{id}",
+    tag = "affiliation",
+    params(
+        ("id" = u64, Path, description = "Affiliation id."),
+        ("lang" = Option<String>, Query, description = "Comma separated language tags to pick `display_name` in, overriding `Accept-Language`."),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages of `display_name`, falling back to `ja` and `en`."),
+    ),
+    responses(
+        (status = 200, description = "Affiliation matching the id.", body = Affiliation),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 404, description = "Affiliation is not found.", body = ApiError),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
 pub async fn get_affiliation_from_id(
     Path(id): Path<u64>,
-    Extension(pool): Extension<PgPool>
-) -> Result<Json<Affiliation>, ErrorResponse> {
-    let aff = AffiliationObject::fetch_name_from_id(id as i64, &pool).await
-        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?
-        .map(Affiliation::from)
-        .ok_or_else(|| ApiError::reason(format!("{} is not found.", id)).report(StatusCode::NOT_FOUND))?;
-    Ok(Json(aff))
+    precondition: Precondition,
+    locales: Locales,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let repr = cache.get_or_fetch(Table::Affiliations, format!("id:{};{}", id, locales.key()), || async {
+        let aff = AffiliationObject::fetch_name_from_id(id as i64, &pool).await
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        let aff = with_names(aff.into_iter().collect(), &pool).await
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?
+            .pop()
+            .ok_or_else(|| ApiError::reason(format!("{} is not found.", id)).report(StatusCode::NOT_FOUND))?;
+        let last_modified = aff.updated_at();
+        Representation::json(&Affiliation::localized(aff, locales.as_slice()), last_modified)
+    }).await?;
+    Ok(Locales::vary(precondition.respond(repr)))
 }
 
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio::time::Instant;
+
+use crate::database::Table;
+
+use super::{ErrorResponse, Representation};
+
+type Slot = Arc<OnceCell<(Instant, Representation)>>;
+
+/// Keys are built from the query and headers of requests, so the number of entries is capped.
+const MAX_ENTRIES: usize = 10_000;
+
+/// In-process cache of REST representations.
+///
+/// Concurrent misses on the same key share one initialization, so only one query reaches the database.
+/// Entries of a table are dropped by [ResponseCache::invalidate] when Salmon commits changes to it,
+/// the TTL only guards against changes that did not go through Salmon.
+/// Once [MAX_ENTRIES] are cached, expired entries are swept and the oldest one makes room for a new one.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    entries: Arc<RwLock<HashMap<(Table, String), Slot>>>,
+    ttl: Arc<HashMap<Table, Duration>>
+}
+
+impl ResponseCache {
+    /// Read TTLs from `.env`.
+    ///
+    /// `CACHE_TTL_SECONDS` is the default for every table,
+    /// and can be overridden per table with `CACHE_TTL_SECONDS_{AFFILIATIONS, LIVERS, CHANNELS, VIDEOS}`.
+    pub fn from_env() -> Self {
+        let default = dotenv::var("CACHE_TTL_SECONDS")
+            .ok()
+            .and_then(|f| f.parse().ok())
+            .unwrap_or(300);
+        let ttl = [
+            (Table::Affiliations, "CACHE_TTL_SECONDS_AFFILIATIONS"),
+            (Table::Livers, "CACHE_TTL_SECONDS_LIVERS"),
+            (Table::Channels, "CACHE_TTL_SECONDS_CHANNELS"),
+            (Table::Videos, "CACHE_TTL_SECONDS_VIDEOS"),
+        ].into_iter()
+         .map(|(table, key)| {
+             let secs = dotenv::var(key)
+                 .ok()
+                 .and_then(|f| f.parse().ok())
+                 .unwrap_or(default);
+             (table, Duration::from_secs(secs))
+         })
+         .collect::<HashMap<_, _>>();
+        Self { entries: Arc::new(RwLock::new(HashMap::new())), ttl: Arc::new(ttl) }
+    }
+
+    /// Return the cached representation of `key`, or build it with `fetch` on a miss.
+    pub async fn get_or_fetch<F, Fut>(&self, table: Table, key: impl Into<String>, fetch: F) -> Result<Representation, ErrorResponse>
+        where F: FnOnce() -> Fut,
+              Fut: Future<Output = Result<Representation, ErrorResponse>>
+    {
+        let key = (table, key.into());
+        let slot = self.slot(&key);
+        let (_, repr) = slot.get_or_try_init(|| async {
+            tracing::trace!("{:<10} {:?}", yansi::Paint::red("cache miss"), key);
+            Ok::<_, ErrorResponse>((Instant::now(), fetch().await?))
+        }).await?;
+        Ok(repr.clone())
+    }
+
+    /// Drop every entry belonging to `table`, and those of the tables that show its rows.
+    pub fn invalidate(&self, table: Table) {
+        let mut entries = self.entries.write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let joined = Self::joined(table);
+        entries.retain(|(cached, _), _| *cached != table && !joined.contains(cached));
+        tracing::debug!("{:<10} {:?} {:?}", yansi::Paint::red("invalidate"), table, joined);
+    }
+
+    /// Tables whose representations also show rows of `table`,
+    /// as schedules, feeds and calendars of videos carry the names of their livers and channels and are filtered by them.
+    fn joined(table: Table) -> &'static [Table] {
+        match table {
+            Table::Livers | Table::Channels => &[Table::Videos],
+            Table::Affiliations | Table::Videos => &[]
+        }
+    }
+
+    fn slot(&self, key: &(Table, String)) -> Slot {
+        let ttl = self.ttl.get(&key.0).copied().unwrap_or_default();
+        let cached = self.entries.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(key)
+            .filter(|slot| !Self::expired(slot, ttl))
+            .map(Arc::clone);
+        if let Some(slot) = cached {
+            return slot;
+        }
+        let mut entries = self.entries.write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(key) {
+            self.sweep(&mut entries);
+        }
+        let slot = entries.entry(key.clone()).or_default();
+        // Another task may have replaced the expired slot while the lock was released.
+        if Self::expired(slot, ttl) {
+            *slot = Slot::default();
+        }
+        Arc::clone(slot)
+    }
+
+    /// Drop expired entries and failed fetches nobody waits for, then the oldest entry when it is still full.
+    fn sweep(&self, entries: &mut HashMap<(Table, String), Slot>) {
+        entries.retain(|(table, _), slot| {
+            let ttl = self.ttl.get(table).copied().unwrap_or_default();
+            match slot.get() {
+                Some(_) => !Self::expired(slot, ttl),
+                None => Arc::strong_count(slot) > 1
+            }
+        });
+        if entries.len() >= MAX_ENTRIES {
+            let oldest = entries.iter()
+                .filter_map(|(key, slot)| slot.get().map(|(cached_at, _)| (*cached_at, key)))
+                .min_by_key(|(cached_at, _)| *cached_at)
+                .map(|(_, key)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        tracing::trace!("{:<10} {} entries left", yansi::Paint::red("sweep"), entries.len());
+    }
+
+    fn expired(slot: &Slot, ttl: Duration) -> bool {
+        slot.get()
+            .map(|(cached_at, _)| cached_at.elapsed() >= ttl)
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt::Write;
+use axum::Extension;
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::Response;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::database::{ScheduleFilter, ScheduledVideoObject, Table, VideoTombstoneObject};
+
+use super::{ApiError, ErrorResponse, Precondition, Representation, ResponseCache};
+
+/// Streams scheduled before this are no longer published.
+const RETENTION_DAYS: i64 = 30;
+const CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+#[derive(Debug, Deserialize)]
+pub struct CalendarQuery {
+    /// Comma separated liver ids.
+    livers: Option<String>
+}
+
+#[utoipa::path(
+    get,
+    path = "/upcomings.ics",
+    tag = "calendar",
+    params(
+        ("livers" = Option<String>, Query, description = "Comma separated liver ids to build the feed from.")
+    ),
+    responses(
+        (status = 200, description = "iCalendar feed of scheduled streams.", content_type = "text/calendar"),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 400, description = "Liver ids are malformed.", body = ApiError),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
+pub async fn get_upcomings_calendar(
+    Query(query): Query<CalendarQuery>,
+    precondition: Precondition,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let liver_ids = query.livers
+        .map(|ids| ids.split(',')
+            .map(|id| id.trim().parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ApiError::new(e).report(StatusCode::BAD_REQUEST)))
+        .transpose()?;
+    let key = match &liver_ids {
+        Some(ids) => format!("ics:livers:{:?}", ids),
+        None => "ics:all".to_string()
+    };
+    let filter = ScheduleFilter { liver_ids, ..Default::default() };
+    let repr = cache.get_or_fetch(Table::Videos, key, || calendar("Matatabi upcomings", filter, &pool)).await?;
+    Ok(precondition.respond(repr))
+}
+
+#[utoipa::path(
+    get,
+    path = "/affiliations/{id}/upcomings.ics",
+    tag = "calendar",
+    params(
+        ("id" = i64, Path, description = "Affiliation id.")
+    ),
+    responses(
+        (status = 200, description = "iCalendar feed of streams scheduled by livers of the affiliation.", content_type = "text/calendar"),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
+pub async fn get_affiliation_calendar(
+    Path(id): Path<i64>,
+    precondition: Precondition,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let filter = ScheduleFilter { affiliation_id: Some(id), ..Default::default() };
+    let repr = cache.get_or_fetch(Table::Videos, format!("ics:affiliation:{}", id), || calendar("Matatabi upcomings (affiliation)", filter, &pool)).await?;
+    Ok(precondition.respond(repr))
+}
+
+#[utoipa::path(
+    get,
+    path = "/livers/{id}/upcomings.ics",
+    tag = "calendar",
+    params(
+        ("id" = i64, Path, description = "Liver id.")
+    ),
+    responses(
+        (status = 200, description = "iCalendar feed of streams scheduled by the liver.", content_type = "text/calendar"),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
+pub async fn get_liver_calendar(
+    Path(id): Path<i64>,
+    precondition: Precondition,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let filter = ScheduleFilter { liver_ids: Some(vec![id]), ..Default::default() };
+    let repr = cache.get_or_fetch(Table::Videos, format!("ics:liver:{}", id), || calendar("Matatabi upcomings (liver)", filter, &pool)).await?;
+    Ok(precondition.respond(repr))
+}
+
+async fn calendar(name: &str, filter: ScheduleFilter, pool: &PgPool) -> Result<Representation, ErrorResponse> {
+    let since = Utc::now() - Duration::days(RETENTION_DAYS);
+    let scheduled = ScheduledVideoObject::fetch_scheduled_since(since, &filter, pool).await
+        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let deleted = VideoTombstoneObject::fetch_deleted_since(since, &filter, pool).await
+        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    let mut ics = IcsWriter::default();
+    ics.line("BEGIN", "VCALENDAR");
+    ics.line("VERSION", "2.0");
+    ics.line("PRODID", "-//matatabi//upcomings//EN");
+    ics.line("CALSCALE", "GREGORIAN");
+    ics.line("METHOD", "PUBLISH");
+    ics.text("X-WR-CALNAME", name);
+
+    for scheduled in &scheduled {
+        let video = scheduled.video();
+        let start = match video.will_start_at() {
+            Some(start) => start,
+            None => continue
+        };
+        let modified = video.updated_at().or_else(|| video.published_at()).unwrap_or(start);
+        let video_id = String::from(video.video_id().to_owned());
+        confirmed_event(&mut ics, &video_id, video.title(), scheduled.liver_name(), start, modified);
+    }
+
+    for deleted in &deleted {
+        let start = match deleted.will_start_at() {
+            Some(start) => start,
+            None => continue
+        };
+        let video_id = String::from(deleted.video_id().to_owned());
+        cancelled_event(&mut ics, &video_id, deleted.title(), start, deleted.deleted_at());
+    }
+
+    ics.line("END", "VCALENDAR");
+
+    let last_modified = scheduled.iter()
+        .filter_map(|scheduled| scheduled.video().updated_at())
+        .chain(deleted.iter().map(VideoTombstoneObject::deleted_at))
+        .max();
+    Ok(Representation::new(ics.finish(), CONTENT_TYPE, last_modified))
+}
+
+fn confirmed_event(ics: &mut IcsWriter, video_id: &str, title: &str, liver: Option<&str>, start: DateTime<Utc>, modified: DateTime<Utc>) {
+    let url = format!("https://www.youtube.com/watch?v={}", video_id);
+
+    ics.line("BEGIN", "VEVENT");
+    ics.line("UID", &format!("{}@matatabi", video_id));
+    ics.line("DTSTAMP", &format_datetime(modified));
+    ics.line("LAST-MODIFIED", &format_datetime(modified));
+    // Rescheduling bumps `updated_at`, so calendar clients replace the existing event.
+    ics.line("SEQUENCE", &sequence(modified).to_string());
+    ics.line("DTSTART", &format_datetime(start));
+    ics.line("DURATION", "PT1H");
+    ics.text("SUMMARY", title);
+    let description = match liver {
+        Some(liver) => format!("{}\n{}", liver, url),
+        None => url.clone()
+    };
+    ics.text("DESCRIPTION", &description);
+    ics.line("URL", &url);
+    ics.line("STATUS", "CONFIRMED");
+    ics.line("END", "VEVENT");
+}
+
+/// The event of a deleted stream, which replaces the confirmed one as its SEQUENCE is later.
+fn cancelled_event(ics: &mut IcsWriter, video_id: &str, title: &str, start: DateTime<Utc>, deleted_at: DateTime<Utc>) {
+    ics.line("BEGIN", "VEVENT");
+    ics.line("UID", &format!("{}@matatabi", video_id));
+    ics.line("DTSTAMP", &format_datetime(deleted_at));
+    ics.line("LAST-MODIFIED", &format_datetime(deleted_at));
+    ics.line("SEQUENCE", &sequence(deleted_at).to_string());
+    ics.line("DTSTART", &format_datetime(start));
+    ics.line("DURATION", "PT1H");
+    ics.text("SUMMARY", title);
+    ics.line("STATUS", "CANCELLED");
+    ics.line("END", "VEVENT");
+}
+
+fn format_datetime(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// SEQUENCE has to increase on every revision, seconds from the epoch always does.
+fn sequence(modified: DateTime<Utc>) -> i64 {
+    modified.timestamp()
+}
+
+/// Writes content lines of RFC 5545.
+#[derive(Debug, Default)]
+struct IcsWriter {
+    buf: String
+}
+
+impl IcsWriter {
+    fn line(&mut self, name: &str, value: &str) {
+        let line = format!("{}:{}", name, value);
+        // Content lines are folded at 75 octets, without splitting a character.
+        let mut width = 0;
+        for c in line.chars() {
+            if width + c.len_utf8() > 75 {
+                self.buf.push_str("\r\n ");
+                width = 1;
+            }
+            self.buf.push(c);
+            width += c.len_utf8();
+        }
+        self.buf.push_str("\r\n");
+    }
+
+    fn text(&mut self, name: &str, value: &str) {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            match c {
+                '\\' | ';' | ',' => { let _ = write!(escaped, "\\{}", c); }
+                '\n' => escaped.push_str("\\n"),
+                '\r' => {}
+                _ => escaped.push(c)
+            }
+        }
+        self.line(name, &escaped);
+    }
+
+    fn finish(self) -> String {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(at: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(at).unwrap().with_timezone(&Utc)
+    }
+
+    fn lines(ics: &str) -> Vec<&str> {
+        ics.strip_suffix("\r\n").unwrap().split("\r\n").collect()
+    }
+
+    /// Content lines as they were before folding.
+    fn unfold(ics: &str) -> String {
+        ics.replace("\r\n ", "")
+    }
+
+    #[test]
+    fn long_lines_are_folded_at_75_octets_between_characters() {
+        let title = "【歌枠】初見さん大歓迎！みんなでいっしょに歌おう～リクエストもあるよ【ホロライブ/兎田ぺこら】";
+        let mut ics = IcsWriter::default();
+        ics.text("SUMMARY", title);
+        let ics = ics.finish();
+
+        let lines = lines(&ics);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= 75), "{:?}", lines);
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+        // A line one octet short of the limit is wrapped before a character of three octets.
+        assert!(lines[..lines.len() - 1].iter().all(|line| line.len() > 72));
+        assert_eq!(unfold(&ics), format!("SUMMARY:{}\r\n", title));
+    }
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        let mut ics = IcsWriter::default();
+        ics.line("BEGIN", "VEVENT");
+        assert_eq!(ics.finish(), "BEGIN:VEVENT\r\n");
+    }
+
+    #[test]
+    fn text_values_are_escaped() {
+        let mut ics = IcsWriter::default();
+        ics.text("DESCRIPTION", "a,b;c\\d\r\ne\nf");
+        assert_eq!(ics.finish(), "DESCRIPTION:a\\,b\\;c\\\\d\\ne\\nf\r\n");
+    }
+
+    #[test]
+    fn a_deleted_stream_is_cancelled_with_a_later_sequence() {
+        let start = utc("2022-01-31T12:00:00Z");
+        let modified = utc("2022-01-20T09:00:00Z");
+        let deleted_at = utc("2022-01-25T09:00:00Z");
+
+        let mut confirmed = IcsWriter::default();
+        confirmed_event(&mut confirmed, "dQw4w9WgXcQ", "歌枠", Some("兎田ぺこら"), start, modified);
+        let confirmed = unfold(&confirmed.finish());
+        let mut cancelled = IcsWriter::default();
+        cancelled_event(&mut cancelled, "dQw4w9WgXcQ", "歌枠", start, deleted_at);
+        let cancelled = unfold(&cancelled.finish());
+
+        let field = |ics: &str, name: &str| lines(ics).into_iter()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':').map(str::to_owned))
+            .unwrap();
+        assert_eq!(field(&confirmed, "STATUS"), "CONFIRMED");
+        assert_eq!(field(&cancelled, "STATUS"), "CANCELLED");
+        assert_eq!(field(&cancelled, "UID"), field(&confirmed, "UID"));
+        assert_eq!(field(&cancelled, "DTSTART"), "20220131T120000Z");
+        assert!(field(&cancelled, "SEQUENCE").parse::<i64>().unwrap() > field(&confirmed, "SEQUENCE").parse::<i64>().unwrap());
+        assert_eq!(field(&confirmed, "DESCRIPTION"), "兎田ぺこら\\nhttps://www.youtube.com/watch?v=dQw4w9WgXcQ");
+    }
+}
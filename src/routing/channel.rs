@@ -1,19 +1,45 @@
 use axum::http::StatusCode;
-use axum::{Extension, Json};
+use axum::Extension;
+use axum::response::Response;
 use sqlx::PgPool;
 
 use crate::models::Channel;
-use crate::database::{ChannelObject, Fetch};
+use crate::database::{ChannelObject, Fetch, Table};
 
-use super::{ErrorResponse, ApiError};
+use super::{ErrorResponse, ApiError, Locales, OutputZone, Precondition, Representation, ResponseCache};
 
+#[utoipa::path(
+    get,
+    path = "/channels",
+    tag = "channel",
+    params(
+        ("lang" = Option<String>, Query, description = "Comma separated language tags to pick `display_name` in, overriding `Accept-Language`."),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages of `display_name`, falling back to `ja` and `en`."),
+        ("tz" = Option<String>, Query, description = "IANA time zone to give timestamps in, such as `Asia/Tokyo`, overriding `Time-Zone`."),
+        ("Time-Zone" = Option<String>, Header, description = "IANA time zone to give timestamps in. Defaults to UTC."),
+    ),
+    responses(
+        (status = 200, description = "All channels.", body = [Channel]),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 400, description = "Unknown time zone.", body = ApiError),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
 pub async fn get_channels(
-    Extension(pool): Extension<PgPool>
-) -> Result<Json<Vec<Channel>>, ErrorResponse> {
-    let ch_all = ChannelObject::fetch_all(&pool).await
-        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?
-        .into_iter()
-        .map(Channel::from)
-        .collect::<Vec<_>>();
-    Ok(Json(ch_all))
+    precondition: Precondition,
+    locales: Locales,
+    zone: OutputZone,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let repr = cache.get_or_fetch(Table::Channels, format!("all;{};{}", locales.key(), zone.key()), || async {
+        let ch_all = ChannelObject::fetch_all(&pool).await
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        let last_modified = ch_all.iter().filter_map(ChannelObject::updated_at).max();
+        let ch_all = ch_all.into_iter()
+            .map(|channel| Channel::localized(channel, locales.as_slice()).in_zone(zone.tz()))
+            .collect::<Vec<_>>();
+        Representation::json(&ch_all, last_modified)
+    }).await?;
+    Ok(OutputZone::vary(Locales::vary(precondition.respond(repr))))
 }
\ No newline at end of file
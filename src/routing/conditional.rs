@@ -0,0 +1,121 @@
+use std::convert::Infallible;
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::extract::FromRequestParts;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+
+use super::{ApiError, ErrorResponse};
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Serialized response body with the validators used by conditional requests.
+///
+/// The `ETag` is a strong validator, a SHA-256 digest of the serialized body,
+/// `Last-Modified` is the newest `updated_at` of the rows it shows.
+/// A deleted row does not move it, so clients sending both validators are answered by the `ETag`.
+#[derive(Debug, Clone)]
+pub struct Representation {
+    body: Bytes,
+    content_type: &'static str,
+    etag: String,
+    last_modified: Option<DateTime<Utc>>
+}
+
+impl Representation {
+    pub fn new(body: impl Into<Bytes>, content_type: &'static str, last_modified: Option<DateTime<Utc>>) -> Self {
+        let body = body.into();
+        let digest = openssl::sha::sha256(&body);
+        let etag = format!("\"{}\"", digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>());
+        // HTTP-date has a resolution of one second.
+        let last_modified = last_modified
+            .and_then(|at| Utc.timestamp_opt(at.timestamp(), 0).single());
+        Self { body, content_type, etag, last_modified }
+    }
+
+    pub fn json<T: Serialize>(value: &T, last_modified: Option<DateTime<Utc>>) -> Result<Self, ErrorResponse> {
+        let body = serde_json::to_vec(value)
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        Ok(Self::new(body, "application/json", last_modified))
+    }
+
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    pub fn last_modified(&self) -> Option<DateTime<Utc>> {
+        self.last_modified
+    }
+
+    fn validators(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Some(Ok(modified)) = self.last_modified
+            .map(|at| HeaderValue::from_str(&at.format(HTTP_DATE_FORMAT).to_string())) {
+            headers.insert(header::LAST_MODIFIED, modified);
+        }
+        headers
+    }
+}
+
+impl IntoResponse for Representation {
+    fn into_response(self) -> Response {
+        let mut headers = self.validators();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(self.content_type));
+        (StatusCode::OK, headers, self.body).into_response()
+    }
+}
+
+/// Validators sent by the client in `If-None-Match` and `If-Modified-Since`.
+#[derive(Debug, Clone, Default)]
+pub struct Precondition {
+    if_none_match: Option<Vec<String>>,
+    if_modified_since: Option<DateTime<Utc>>
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Precondition {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let if_none_match = parts.headers.get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .map(|tags| tags.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/").to_owned())
+                .filter(|tag| !tag.is_empty())
+                .collect::<Vec<_>>());
+        let if_modified_since = parts.headers.get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+            .map(|date| date.with_timezone(&Utc));
+        Ok(Self { if_none_match, if_modified_since })
+    }
+}
+
+impl Precondition {
+    /// Whether the client already holds the current representation.
+    ///
+    /// `If-None-Match` takes precedence, `If-Modified-Since` is only evaluated without it. (RFC 7232 Section 6)
+    pub fn is_fresh(&self, repr: &Representation) -> bool {
+        if let Some(tags) = &self.if_none_match {
+            return tags.iter().any(|tag| tag == "*" || tag == repr.etag());
+        }
+        match (self.if_modified_since, repr.last_modified()) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false
+        }
+    }
+
+    pub fn respond(&self, repr: Representation) -> Response {
+        if self.is_fresh(&repr) {
+            (StatusCode::NOT_MODIFIED, repr.validators()).into_response()
+        } else {
+            repr.into_response()
+        }
+    }
+}
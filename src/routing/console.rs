@@ -0,0 +1,689 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use axum::{async_trait, Extension, Form};
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+
+use crate::database::{
+    AccountRole, IngestionBatchObject, DEFAULT_LOCALES,
+    AffiliationObject, LiverObject, ChannelObject, VideoObject
+};
+use crate::server::{Authenticator, Principal};
+use crate::server::cage::CageService;
+
+use super::{Editable, Editor, ErrorResponse};
+
+/// Where the console is mounted, which its links and redirects point under.
+pub const CONSOLE_ROOT: &str = "/console";
+const SESSION_COOKIE: &str = "matatabi_console";
+/// Rows shown in a list, narrowed down by searching.
+const LIST_LIMIT: i64 = 100;
+const BATCH_LIMIT: i64 = 50;
+
+/// How a field of an [Editable::Record] is shown in the console and read back from its form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Primary key, shown but not editable.
+    Key,
+    Text,
+    LongText,
+    OptionalText,
+    OptionalInteger,
+    /// RFC 3339, empty for none.
+    Timestamp,
+    /// Localized names, one `locale = name` a line.
+    Names
+}
+
+/// Rows listed and edited in the admin console.
+#[async_trait]
+pub trait Console: Editable {
+    /// Fields of the record in the order they are shown, starting with the key.
+    const FIELDS: &'static [(&'static str, Field)];
+
+    /// At most `limit` rows matching `query`, any rows for an empty one.
+    async fn list(query: &str, limit: i64, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error>;
+
+    /// Key of the row as it appears in its url.
+    fn path_id(&self) -> String;
+
+    /// What the row is, in a line.
+    fn summary(&self) -> String;
+}
+
+#[async_trait]
+impl Console for AffiliationObject {
+    const FIELDS: &'static [(&'static str, Field)] = &[
+        ("affiliation_id", Field::Key),
+        ("name", Field::Text),
+        ("names", Field::Names)
+    ];
+
+    async fn list(query: &str, limit: i64, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        AffiliationObject::fetch_matching(query, limit, pool).await
+    }
+
+    fn path_id(&self) -> String {
+        i64::from(self.affiliation_id()).to_string()
+    }
+
+    fn summary(&self) -> String {
+        self.name().to_owned()
+    }
+}
+
+#[async_trait]
+impl Console for LiverObject {
+    const FIELDS: &'static [(&'static str, Field)] = &[
+        ("liver_id", Field::Key),
+        ("affiliation_id", Field::OptionalInteger),
+        ("name", Field::Text),
+        ("localized_name", Field::Text),
+        ("names", Field::Names)
+    ];
+
+    async fn list(query: &str, limit: i64, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        LiverObject::fetch_matching(query, limit, pool).await
+    }
+
+    fn path_id(&self) -> String {
+        i64::from(self.liver_id()).to_string()
+    }
+
+    fn summary(&self) -> String {
+        match self.affiliation_id() {
+            Some(affiliation_id) => format!("{} / {} (affiliation {})", self.name(), self.localized_name(), i64::from(affiliation_id)),
+            None => format!("{} / {}", self.name(), self.localized_name())
+        }
+    }
+}
+
+#[async_trait]
+impl Console for ChannelObject {
+    const FIELDS: &'static [(&'static str, Field)] = &[
+        ("channel_id", Field::Key),
+        ("liver_id", Field::OptionalInteger),
+        ("logo_url", Field::Text),
+        ("published_at", Field::Timestamp),
+        ("description", Field::LongText),
+        ("names", Field::Names)
+    ];
+
+    async fn list(query: &str, limit: i64, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        ChannelObject::fetch_matching(query, limit, pool).await
+    }
+
+    fn path_id(&self) -> String {
+        self.channel_id().to_owned().into()
+    }
+
+    fn summary(&self) -> String {
+        let name = self.display_name(&DEFAULT_LOCALES).unwrap_or_default();
+        match self.liver_id() {
+            Some(liver_id) => format!("{} (liver {})", name, i64::from(liver_id)),
+            None => name.to_owned()
+        }
+    }
+}
+
+#[async_trait]
+impl Console for VideoObject {
+    const FIELDS: &'static [(&'static str, Field)] = &[
+        ("video_id", Field::Key),
+        ("channel_id", Field::OptionalText),
+        ("title", Field::Text),
+        ("description", Field::LongText),
+        ("published_at", Field::Timestamp),
+        ("updated_at", Field::Timestamp),
+        ("will_start_at", Field::Timestamp),
+        ("started_at", Field::Timestamp),
+        ("ended_at", Field::Timestamp),
+        ("thumbnail_url", Field::OptionalText)
+    ];
+
+    async fn list(query: &str, limit: i64, pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        VideoObject::fetch_matching(query, limit, pool).await
+    }
+
+    fn path_id(&self) -> String {
+        self.video_id().to_owned().into()
+    }
+
+    fn summary(&self) -> String {
+        match self.will_start_at() {
+            Some(at) => format!("{} ({})", self.title(), at.to_rfc3339()),
+            None => self.title().to_owned()
+        }
+    }
+}
+
+/// Lets in requests with the session cookie of an account that may write, and sends the others to the login form.
+///
+/// Forms posted from another origin are refused, on top of the cookie being `SameSite=Strict`.
+pub async fn console_session<B>(State(authenticator): State<Authenticator>, mut request: Request<B>, next: Next<B>) -> Response {
+    if request.method() != Method::GET && !same_origin(request.headers()) {
+        return failed(StatusCode::FORBIDDEN, "Forms can only be posted from the console.");
+    }
+    let token = match session_token(request.headers()) {
+        Some(token) => token,
+        None => return Redirect::to(&format!("{}/login", CONSOLE_ROOT)).into_response()
+    };
+    match authenticator.authenticate_token(&token).await {
+        Ok(principal) if principal.role > AccountRole::Reader => {
+            request.extensions_mut().insert(principal);
+            next.run(request).await
+        }
+        Ok(principal) => failed(StatusCode::FORBIDDEN, &format!("{} may not write, so it has no use for the console.", principal)),
+        Err(rejection) => {
+            tracing::debug!("{:<10} {} {}", yansi::Paint::red("reject"), request.uri().path(), rejection);
+            Redirect::to(&format!("{}/login", CONSOLE_ROOT)).into_response()
+        }
+    }
+}
+
+/// Signs accounts in and out of the console, keeping their access token in a cookie.
+#[derive(Debug, Clone)]
+pub struct Session {
+    cage: CageService,
+    /// Whether the cookie is only sent over HTTPS.
+    secure: bool
+}
+
+impl Session {
+    pub fn new(cage: CageService, secure: bool) -> Self {
+        Self { cage, secure }
+    }
+
+    fn cookie(&self, token: &str, max_age: i64) -> HeaderValue {
+        let secure = if self.secure { "; Secure" } else { "" };
+        let cookie = format!("{}={}; Path={}; Max-Age={}; HttpOnly; SameSite=Strict{}", SESSION_COOKIE, token, CONSOLE_ROOT, max_age, secure);
+        HeaderValue::from_str(&cookie).expect("Cage tokens are valid in a header.")
+    }
+}
+
+fn session_token(headers: &HeaderMap) -> Option<String> {
+    headers.get_all(header::COOKIE).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, value)| *name == SESSION_COOKIE && !value.is_empty())
+        .map(|(_, value)| value.to_owned())
+}
+
+/// Whether `Origin`, when sent, is the host the request was sent to.
+fn same_origin(headers: &HeaderMap) -> bool {
+    let origin = match headers.get(header::ORIGIN).and_then(|value| value.to_str().ok()) {
+        Some(origin) => origin,
+        None => return true
+    };
+    let host = headers.get(header::HOST).and_then(|value| value.to_str().ok());
+    let origin_host = origin.split_once("://").map(|(_, host)| host);
+    host.is_some() && origin_host == host
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Login {
+    user_name: String,
+    user_pass: String
+}
+
+pub async fn get_console_login() -> Html<String> {
+    page("Sign in", None, login_form(None))
+}
+
+pub async fn post_console_login(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Extension(session): Extension<Session>,
+    Form(login): Form<Login>
+) -> Response {
+    match session.cage.sign_in(peer.ip(), &login.user_name, login.user_pass).await {
+        Ok((_, token)) => {
+            let mut response = Redirect::to(CONSOLE_ROOT).into_response();
+            response.headers_mut().insert(header::SET_COOKIE, session.cookie(&token, session.cage.access_ttl_seconds()));
+            response
+        }
+        Err(status) => {
+            let code = match status.code() {
+                tonic::Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+                _ => StatusCode::UNAUTHORIZED
+            };
+            (code, page("Sign in", None, login_form(Some(status.message())))).into_response()
+        }
+    }
+}
+
+pub async fn post_console_logout(Extension(session): Extension<Session>, headers: HeaderMap) -> Response {
+    if !same_origin(&headers) {
+        return failed(StatusCode::FORBIDDEN, "Forms can only be posted from the console.");
+    }
+    if let Some(token) = session_token(&headers) {
+        if let Err(status) = session.cage.sign_out(&token).await {
+            tracing::debug!("{:<10} {}", yansi::Paint::red("sign out"), status.message());
+        }
+    }
+    let mut response = Redirect::to(&format!("{}/login", CONSOLE_ROOT)).into_response();
+    response.headers_mut().insert(header::SET_COOKIE, session.cookie("", 0));
+    response
+}
+
+fn login_form(error: Option<&str>) -> String {
+    let mut body = String::new();
+    if let Some(error) = error {
+        let _ = write!(body, r#"<p class="error">{}</p>"#, escape(error));
+    }
+    let _ = write!(body, r#"<form method="post" action="{root}/login">
+<label>User name <input name="user_name" autocomplete="username" required></label>
+<label>Password <input name="user_pass" type="password" autocomplete="current-password" required></label>
+<button>Sign in</button>
+</form>"#, root = CONSOLE_ROOT);
+    body
+}
+
+/// Tables of the console and the latest Salmon batches with how they ended.
+pub async fn get_console(Extension(principal): Extension<Principal>, Extension(pool): Extension<PgPool>) -> Response {
+    let batches = match IngestionBatchObject::fetch_recent(BATCH_LIMIT, &pool).await {
+        Ok(batches) => batches,
+        Err(e) => return failed(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+    };
+    let mut body = String::from("<h2>Recent ingestion batches</h2>");
+    if batches.is_empty() {
+        body.push_str("<p>No batches have been received yet.</p>");
+    } else {
+        body.push_str("<table><tr><th>Started</th><th>Account</th><th>Table</th><th>Received</th><th>Inserted</th><th>Updated</th><th>Deleted</th><th>Unchanged</th><th>Outcome</th><th>Elapsed</th><th>Message</th></tr>");
+        for batch in &batches {
+            let _ = write!(body, r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class="{outcome}">{outcome}</td><td>{}ms</td><td>{}</td></tr>"#,
+                timestamp(batch.started_at()), escape(batch.user_name()), escape(batch.target()),
+                batch.received(), batch.inserted(), batch.updated(), batch.deleted(), batch.unchanged(),
+                batch.elapsed_ms(), escape(batch.message()), outcome = batch.outcome().name());
+        }
+        body.push_str("</table>");
+    }
+    page("Console", Some(&principal), body).into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ConsoleSearch {
+    #[serde(default)]
+    q: String
+}
+
+pub async fn get_console_list<T: Console>(
+    Query(search): Query<ConsoleSearch>,
+    Extension(principal): Extension<Principal>,
+    Extension(pool): Extension<PgPool>
+) -> Response {
+    let query = search.q.trim();
+    let rows = match T::list(query, LIST_LIMIT, &pool).await {
+        Ok(rows) => rows,
+        Err(e) => return failed(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+    };
+    let table = T::TABLE.as_str();
+    let mut body = String::new();
+    let _ = write!(body, r#"<form method="get" action="{root}/{table}"><input name="q" value="{q}" placeholder="Id or name" autofocus><button>Search</button></form>"#,
+        root = CONSOLE_ROOT, table = table, q = escape(query));
+    if rows.is_empty() {
+        body.push_str("<p>Nothing matches.</p>");
+    } else {
+        body.push_str("<table>");
+        for row in &rows {
+            let _ = write!(body, r#"<tr><td><a href="{root}/{table}/{id}">{id_text}</a></td><td>{summary}</td><td><a href="{root}/{table}/{id}/delete">delete</a></td></tr>"#,
+                root = CONSOLE_ROOT, table = table, id = escape(&path_segment(&row.path_id())),
+                id_text = escape(&row.path_id()), summary = escape(&row.summary()));
+        }
+        body.push_str("</table>");
+        if rows.len() as i64 == LIST_LIMIT {
+            let _ = write!(body, "<p>Only the first {} are shown, search to narrow them down.</p>", LIST_LIMIT);
+        }
+    }
+    page(table, Some(&principal), body).into_response()
+}
+
+pub async fn get_console_edit<T: Console>(Path(id): Path<T::Id>, editor: Editor) -> Response {
+    match editor.fetch::<T>(&id).await {
+        Ok(row) => edit_page(row, editor.principal(), None),
+        Err(error) => error_page(error)
+    }
+}
+
+/// Patch the row with the fields that differ from it, as the JSON api does with a merge patch.
+pub async fn post_console_edit<T: Console>(Path(id): Path<T::Id>, editor: Editor, Form(form): Form<HashMap<String, String>>) -> Response {
+    let current = match editor.fetch::<T>(&id).await {
+        Ok(current) => current,
+        Err(error) => return error_page(error)
+    };
+    let document = match serde_json::to_value(T::Record::from(current.clone())) {
+        Ok(document) => document,
+        Err(e) => return failed(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+    };
+    let patch = match form_patch(T::FIELDS, &document, &form) {
+        Ok(patch) => patch,
+        Err(reason) => return edit_page(current, editor.principal(), Some(Err(reason)))
+    };
+    if patch.is_empty() {
+        return edit_page(current, editor.principal(), Some(Ok("Nothing has changed.")));
+    }
+    match editor.patch::<T>(id, Value::Object(patch)).await {
+        Ok((_, new)) => edit_page(new, editor.principal(), Some(Ok("Saved."))),
+        Err((_, error)) => edit_page(current, editor.principal(), Some(Err(error.0.reason)))
+    }
+}
+
+pub async fn get_console_delete<T: Console>(Path(id): Path<T::Id>, editor: Editor) -> Response {
+    let row = match editor.fetch::<T>(&id).await {
+        Ok(row) => row,
+        Err(error) => return error_page(error)
+    };
+    let table = T::TABLE.as_str();
+    let href = format!("{}/{}/{}", CONSOLE_ROOT, table, path_segment(&row.path_id()));
+    let body = format!(r#"<p>Delete <strong>{id}</strong>, {summary}? This can not be undone.</p>
+<form method="post" action="{href}/delete"><button class="danger">Delete</button> <a href="{href}">Cancel</a></form>"#,
+        id = escape(&row.path_id()), summary = escape(&row.summary()), href = escape(&href));
+    page(&format!("Delete from {}", table), Some(editor.principal()), body).into_response()
+}
+
+pub async fn post_console_delete<T: Console>(Path(id): Path<T::Id>, editor: Editor) -> Response {
+    match editor.delete::<T>(id).await {
+        Ok(_) => Redirect::to(&format!("{}/{}", CONSOLE_ROOT, T::TABLE.as_str())).into_response(),
+        Err(error) => error_page(error)
+    }
+}
+
+fn edit_page<T: Console>(row: T, principal: &Principal, notice: Option<Result<&str, String>>) -> Response {
+    let table = T::TABLE.as_str();
+    let href = format!("{}/{}/{}", CONSOLE_ROOT, table, path_segment(&row.path_id()));
+    let title = format!("{} {}", table, row.path_id());
+    let document = match serde_json::to_value(T::Record::from(row)) {
+        Ok(document) => document,
+        Err(e) => return failed(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+    };
+    let mut body = String::new();
+    match notice {
+        Some(Ok(notice)) => { let _ = write!(body, r#"<p class="notice">{}</p>"#, escape(notice)); }
+        Some(Err(error)) => { let _ = write!(body, r#"<p class="error">{}</p>"#, escape(&error)); }
+        None => {}
+    }
+    let _ = write!(body, r#"<form method="post" action="{}">"#, escape(&href));
+    for (name, field) in T::FIELDS {
+        let value = escape(&field_text(*field, document.get(name).unwrap_or(&Value::Null)));
+        let input = match field {
+            Field::Key => format!(r#"<input value="{}" disabled>"#, value),
+            Field::LongText | Field::Names => format!(r#"<textarea name="{}" rows="6">{}</textarea>"#, name, value),
+            Field::Timestamp => format!(r#"<input name="{}" value="{}" placeholder="2022-01-31T21:00:00+09:00">"#, name, value),
+            _ => format!(r#"<input name="{}" value="{}">"#, name, value)
+        };
+        let _ = write!(body, "<label>{} {}</label>", name, input);
+    }
+    let _ = write!(body, r#"<button>Save</button> <a href="{href}/delete" class="danger">Delete</a></form>"#, href = escape(&href));
+    page(&title, Some(principal), body).into_response()
+}
+
+/// Value of a field as it is shown in its input.
+fn field_text(field: Field, value: &Value) -> String {
+    match (field, value) {
+        (_, Value::Null) => String::new(),
+        (_, Value::String(text)) => text.to_owned(),
+        (Field::Names, Value::Object(names)) => names.iter()
+            .map(|(locale, name)| format!("{} = {}\n", locale, name.as_str().unwrap_or_default()))
+            .collect(),
+        (_, value) => value.to_string()
+    }
+}
+
+/// Merge patch of the fields whose submitted value differs from `document`.
+fn form_patch(fields: &[(&str, Field)], document: &Value, form: &HashMap<String, String>) -> Result<Map<String, Value>, String> {
+    let mut patch = Map::new();
+    for (name, field) in fields {
+        let submitted = form.get(*name).map(String::as_str).unwrap_or_default();
+        let current = document.get(name).unwrap_or(&Value::Null);
+        let value = match field {
+            Field::Key => continue,
+            Field::Text => Value::String(submitted.trim().to_owned()),
+            Field::LongText => Value::String(submitted.replace("\r\n", "\n")),
+            Field::OptionalText => match submitted.trim() {
+                "" => Value::Null,
+                text => Value::String(text.to_owned())
+            },
+            Field::OptionalInteger => match submitted.trim() {
+                "" => Value::Null,
+                number => number.parse::<i64>()
+                    .map(Value::from)
+                    .map_err(|_| format!("{} must be a number.", name))?
+            },
+            Field::Timestamp => match submitted.trim() {
+                "" => Value::Null,
+                at => DateTime::parse_from_rfc3339(at)
+                    .map(|at| Value::String(at.with_timezone(&Utc).to_rfc3339()))
+                    .map_err(|e| format!("{} is not an RFC 3339 timestamp: {}", name, e))?
+            },
+            Field::Names => {
+                let names = names_patch(submitted, current)
+                    .map_err(|line| format!("{} must be `locale = name` a line, not `{}`.", name, line))?;
+                if !names.is_empty() {
+                    patch.insert(name.to_string(), Value::Object(names));
+                }
+                continue;
+            }
+        };
+        if !same_value(*field, &value, current) {
+            patch.insert(name.to_string(), value);
+        }
+    }
+    Ok(patch)
+}
+
+/// Timestamps are compared as instants, as the stored ones may be written with another offset.
+fn same_value(field: Field, value: &Value, current: &Value) -> bool {
+    let instant = |value: &Value| value.as_str().and_then(|at| DateTime::parse_from_rfc3339(at).ok());
+    match field {
+        Field::Timestamp if !value.is_null() && !current.is_null() => instant(value) == instant(current),
+        _ => value == current
+    }
+}
+
+/// Names to set and, as `null`, names to remove. Errors with the first line that is not `locale = name`.
+fn names_patch(submitted: &str, current: &Value) -> Result<Map<String, Value>, String> {
+    let mut names = Map::new();
+    for line in submitted.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (locale, name) = line.split_once('=')
+            .map(|(locale, name)| (locale.trim().to_lowercase(), name.trim()))
+            .filter(|(locale, name)| !locale.is_empty() && !name.is_empty())
+            .ok_or_else(|| line.to_owned())?;
+        names.insert(locale, Value::String(name.to_owned()));
+    }
+    let current = current.as_object().cloned().unwrap_or_default();
+    let mut patch = Map::new();
+    for locale in current.keys().filter(|locale| !names.contains_key(*locale)) {
+        patch.insert(locale.to_owned(), Value::Null);
+    }
+    for (locale, name) in names {
+        if current.get(&locale) != Some(&name) {
+            patch.insert(locale, name);
+        }
+    }
+    Ok(patch)
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+/// Ids are Youtube ids or numbers, only these are left for the url.
+fn path_segment(id: &str) -> String {
+    id.chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '-' | '_' | '.' | '~' => c.to_string(),
+            c => c.to_string().bytes().map(|byte| format!("%{:02X}", byte)).collect()
+        })
+        .collect()
+}
+
+fn error_page((status, error): ErrorResponse) -> Response {
+    failed(status, &error.0.reason)
+}
+
+fn failed(status: StatusCode, reason: &str) -> Response {
+    let title = status.canonical_reason().unwrap_or("Error");
+    let body = format!(r#"<p class="error">{}</p><p><a href="{}">Back to the console</a></p>"#, escape(reason), CONSOLE_ROOT);
+    (status, page(title, None, body)).into_response()
+}
+
+fn page(title: &str, principal: Option<&Principal>, body: String) -> Html<String> {
+    let mut html = String::new();
+    let _ = write!(html, r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>{title} - matatabi</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+nav a, nav form {{ margin-right: 1em; display: inline; }}
+table {{ border-collapse: collapse; }}
+td, th {{ border: 1px solid #ccc; padding: .25em .5em; text-align: left; vertical-align: top; }}
+label {{ display: block; margin: .5em 0; }}
+input, textarea {{ display: block; width: 40em; }}
+.error, .failed, .denied, .danger {{ color: #b00; }}
+.notice, .committed {{ color: #070; }}
+</style></head><body>"#, title = escape(title));
+    if let Some(principal) = principal {
+        let _ = write!(html, r#"<nav><a href="{root}">console</a>"#, root = CONSOLE_ROOT);
+        for table in ["affiliations", "livers", "channels", "videos"] {
+            let _ = write!(html, r#"<a href="{root}/{table}">{table}</a>"#, root = CONSOLE_ROOT, table = table);
+        }
+        let _ = write!(html, r#"<form method="post" action="{root}/logout">{} ({}) <button>Sign out</button></form></nav>"#,
+            escape(&principal.user_name), principal.role.name(), root = CONSOLE_ROOT);
+    }
+    let _ = write!(html, "<h1>{}</h1>{}</body></html>", escape(title), body);
+    Html(html)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    const FIELDS: &[(&str, Field)] = &[
+        ("video_id", Field::Key),
+        ("title", Field::Text),
+        ("description", Field::LongText),
+        ("thumbnail_url", Field::OptionalText),
+        ("liver_id", Field::OptionalInteger),
+        ("will_start_at", Field::Timestamp),
+        ("names", Field::Names)
+    ];
+
+    fn document() -> Value {
+        json!({
+            "video_id": "dQw4w9WgXcQ",
+            "title": "歌枠",
+            "description": "line\nline",
+            "thumbnail_url": null,
+            "liver_id": 1,
+            "will_start_at": "2022-01-31T12:00:00+00:00",
+            "names": { "ja": "ぺこら", "en": "Pekora" }
+        })
+    }
+
+    fn form(fields: &[(&str, &str)]) -> HashMap<String, String> {
+        let unchanged = [
+            ("title", "歌枠"),
+            ("description", "line\r\nline"),
+            ("thumbnail_url", ""),
+            ("liver_id", "1"),
+            ("will_start_at", "2022-01-31T21:00:00+09:00"),
+            ("names", "ja = ぺこら\nen = Pekora\n")
+        ];
+        unchanged.iter().chain(fields)
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs.iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn an_unchanged_form_is_no_patch() {
+        assert_eq!(form_patch(FIELDS, &document(), &form(&[])), Ok(Map::new()));
+    }
+
+    #[test]
+    fn only_changed_fields_are_patched() {
+        let patch = form_patch(FIELDS, &document(), &form(&[
+            ("video_id", "changed"),
+            ("title", " 雑談 "),
+            ("thumbnail_url", "https://example.com/1.jpg"),
+            ("liver_id", "")
+        ])).unwrap();
+        assert_eq!(Value::Object(patch), json!({
+            "title": "雑談",
+            "thumbnail_url": "https://example.com/1.jpg",
+            "liver_id": null
+        }));
+    }
+
+    #[test]
+    fn timestamps_are_compared_as_instants() {
+        let patch = form_patch(FIELDS, &document(), &form(&[("will_start_at", "2022-01-31T13:00:00+01:00")])).unwrap();
+        assert!(patch.is_empty());
+        let patch = form_patch(FIELDS, &document(), &form(&[("will_start_at", "2022-01-31T13:00:00Z")])).unwrap();
+        assert_eq!(patch.get("will_start_at"), Some(&json!("2022-01-31T13:00:00+00:00")));
+    }
+
+    #[test]
+    fn malformed_values_are_refused() {
+        assert!(form_patch(FIELDS, &document(), &form(&[("liver_id", "one")])).is_err());
+        assert!(form_patch(FIELDS, &document(), &form(&[("will_start_at", "tomorrow")])).is_err());
+        assert!(form_patch(FIELDS, &document(), &form(&[("names", "ja ぺこら")])).is_err());
+    }
+
+    #[test]
+    fn a_removed_name_line_becomes_null() {
+        let current = json!({ "ja": "ぺこら", "en": "Pekora" });
+        assert_eq!(names_patch("ja = ぺこら", &current), Ok(Map::from_iter([("en".to_owned(), Value::Null)])));
+        assert_eq!(names_patch("JA = ぺこら\n\nen=Peko\n", &current), Ok(Map::from_iter([("en".to_owned(), json!("Peko"))])));
+        assert_eq!(names_patch("zh = 佩可拉", &Value::Null), Ok(Map::from_iter([("zh".to_owned(), json!("佩可拉"))])));
+        assert_eq!(names_patch("ja = ", &current), Err("ja =".to_owned()));
+    }
+
+    #[test]
+    fn cross_origin_posts_are_refused() {
+        assert!(same_origin(&headers(&[(header::HOST, "matatabi.example")])));
+        assert!(same_origin(&headers(&[(header::HOST, "matatabi.example"), (header::ORIGIN, "https://matatabi.example")])));
+        assert!(!same_origin(&headers(&[(header::HOST, "matatabi.example"), (header::ORIGIN, "https://evil.example")])));
+        assert!(!same_origin(&headers(&[(header::HOST, "matatabi.example"), (header::ORIGIN, "null")])));
+        assert!(!same_origin(&headers(&[(header::ORIGIN, "https://matatabi.example")])));
+    }
+
+    #[test]
+    fn markup_is_escaped() {
+        assert_eq!(escape(r#"<a href="x">'&'</a>"#), "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn ids_are_percent_encoded_in_paths() {
+        assert_eq!(path_segment("dQw4w9WgXcQ-_.~"), "dQw4w9WgXcQ-_.~");
+        assert_eq!(path_segment("a/b?c"), "a%2Fb%3Fc");
+        assert_eq!(path_segment("ぺ"), "%E3%81%BA");
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use axum::Extension;
+use axum::extract::Query;
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use chrono_tz::Tz;
+use futures::{Stream, StreamExt};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+
+use crate::database::{AffiliationObject, ChannelObject, LiverObject, VideoObject};
+use crate::models::{Affiliation, Channel, Liver, Video};
+
+use super::OutputZone;
+
+/// Number of events kept for `Last-Event-ID` resume.
+const HISTORY_CAPACITY: usize = 1024;
+
+/// Change committed by Salmon.
+#[derive(Debug, Clone)]
+pub enum Changed<T> {
+    Inserted(T),
+    Updated(T, T),
+    Deleted(T)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Inserted,
+    Updated,
+    Deleted,
+    /// Video inserted with `will_start_at`.
+    Scheduled,
+    /// `will_start_at` of a video has changed.
+    Rescheduled,
+    /// `started_at` of a video is set.
+    Live
+}
+
+impl ChangeKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Inserted => "inserted",
+            ChangeKind::Updated => "updated",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::Scheduled => "scheduled",
+            ChangeKind::Rescheduled => "rescheduled",
+            ChangeKind::Live => "live"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ChangePayload {
+    Affiliation {
+        affiliation: Affiliation
+    },
+    Liver {
+        liver: Liver
+    },
+    Channel {
+        channel: Channel,
+        liver: Option<Liver>
+    },
+    Video {
+        video: Video,
+        channel: Option<Channel>,
+        liver: Option<Liver>
+    }
+}
+
+impl ChangePayload {
+    fn entity(&self) -> &'static str {
+        match self {
+            ChangePayload::Affiliation { .. } => "affiliation",
+            ChangePayload::Liver { .. } => "liver",
+            ChangePayload::Channel { .. } => "channel",
+            ChangePayload::Video { .. } => "video"
+        }
+    }
+}
+
+/// Ids the changed object belongs to, used to filter subscriptions.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeScope {
+    pub affiliation_id: Option<i64>,
+    pub liver_id: Option<i64>,
+    pub channel_id: Option<String>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    pub id: u64,
+    pub kind: ChangeKind,
+    #[serde(flatten)]
+    pub payload: ChangePayload,
+    #[serde(skip)]
+    pub scope: ChangeScope
+}
+
+impl ChangeEvent {
+    /// Event name, such as `video.scheduled`.
+    pub fn name(&self) -> String {
+        format!("{}.{}", self.payload.entity(), self.kind.as_str())
+    }
+
+    /// Copy of the event with its timestamps in `zone`.
+    pub fn in_zone(&self, zone: &Tz) -> Self {
+        let payload = match self.payload.clone() {
+            ChangePayload::Channel { channel, liver } => ChangePayload::Channel { channel: channel.in_zone(zone), liver },
+            ChangePayload::Video { video, channel, liver } => ChangePayload::Video {
+                video: video.in_zone(zone),
+                channel: channel.map(|channel| channel.in_zone(zone)),
+                liver
+            },
+            payload => payload
+        };
+        Self { payload, scope: self.scope.clone(), ..*self }
+    }
+}
+
+/// Convert a committed change into an event, looking up the related objects.
+#[async_trait::async_trait]
+pub trait Notify: Sized + Send {
+    async fn notify(changed: Changed<Self>, pool: &PgPool) -> Result<(ChangeKind, ChangePayload, ChangeScope), sqlx::Error>;
+}
+
+/// Events of a committed batch, built once the related objects are looked up.
+type PendingEvents = BoxFuture<'static, Vec<(ChangeKind, ChangePayload, ChangeScope)>>;
+
+/// Broadcasts the changes committed by Salmon, and keeps the latest ones for resume.
+#[derive(Debug, Clone)]
+pub struct EventHub {
+    sender: broadcast::Sender<Arc<ChangeEvent>>,
+    history: Arc<Mutex<(u64, VecDeque<Arc<ChangeEvent>>)>>,
+    queue: mpsc::UnboundedSender<PendingEvents>
+}
+
+impl EventHub {
+    /// Spawns the task giving ids to published changes, so it needs a tokio runtime.
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(HISTORY_CAPACITY);
+        let (queue, pending) = mpsc::unbounded_channel();
+        // Ids start from the boot time so that they keep increasing across restarts.
+        let origin = chrono::Utc::now().timestamp_millis() as u64 * 1000;
+        let history = Arc::new(Mutex::new((origin, VecDeque::with_capacity(HISTORY_CAPACITY))));
+        tokio::spawn(Self::assign_ids(pending, sender.clone(), Arc::clone(&history)));
+        Self { sender, history, queue }
+    }
+
+    /// Queue the changes of a committed batch.
+    ///
+    /// Batches are built one after another by a single task, so event ids follow the order they are published in.
+    pub fn publish<T: Notify + 'static>(&self, changes: Vec<Changed<T>>, pool: &PgPool) {
+        let pool = pool.clone();
+        let pending: PendingEvents = Box::pin(async move {
+            let mut events = Vec::with_capacity(changes.len());
+            for changed in changes {
+                match T::notify(changed, &pool).await {
+                    Ok(event) => events.push(event),
+                    Err(e) => tracing::error!("Failed to build change event: {:?}", e)
+                }
+            }
+            events
+        });
+        // Sending fails only when the runtime is shutting down.
+        let _ = self.queue.send(pending);
+    }
+
+    async fn assign_ids(
+        mut pending: mpsc::UnboundedReceiver<PendingEvents>,
+        sender: broadcast::Sender<Arc<ChangeEvent>>,
+        history: Arc<Mutex<(u64, VecDeque<Arc<ChangeEvent>>)>>
+    ) {
+        while let Some(events) = pending.recv().await {
+            for (kind, payload, scope) in events.await {
+                let mut history = history.lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                history.0 += 1;
+                let event = Arc::new(ChangeEvent { id: history.0, kind, payload, scope });
+                if history.1.len() == HISTORY_CAPACITY {
+                    history.1.pop_front();
+                }
+                history.1.push_back(Arc::clone(&event));
+                // Sending fails only when nobody is subscribed.
+                let _ = sender.send(event);
+            }
+        }
+    }
+
+    /// Subscribe to new events.
+    ///
+    /// Returns the kept events after `last_event_id`, or `None` if some of them were already dropped.
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> (Option<Vec<Arc<ChangeEvent>>>, broadcast::Receiver<Arc<ChangeEvent>>) {
+        let history = self.history.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let receiver = self.sender.subscribe();
+        let backlog = match last_event_id {
+            None => Some(Vec::new()),
+            Some(last) => {
+                let oldest = history.1.front().map(|event| event.id).unwrap_or(history.0 + 1);
+                if last + 1 < oldest || last > history.0 {
+                    None
+                } else {
+                    Some(history.1.iter().filter(|event| event.id > last).cloned().collect())
+                }
+            }
+        };
+        (backlog, receiver)
+    }
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EventFilter {
+    pub affiliation: Option<i64>,
+    pub liver: Option<i64>,
+    pub channel: Option<String>
+}
+
+impl EventFilter {
+    pub fn matches(&self, scope: &ChangeScope) -> bool {
+        self.affiliation.map_or(true, |id| scope.affiliation_id == Some(id))
+            && self.liver.map_or(true, |id| scope.liver_id == Some(id))
+            && self.channel.as_ref().map_or(true, |id| scope.channel_id.as_ref() == Some(id))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "event",
+    params(
+        ("affiliation" = Option<i64>, Query, description = "Only events under the affiliation."),
+        ("liver" = Option<i64>, Query, description = "Only events under the liver."),
+        ("channel" = Option<String>, Query, description = "Only events under the channel."),
+        ("Last-Event-ID" = Option<u64>, Header, description = "Resume after the event."),
+        ("tz" = Option<String>, Query, description = "IANA time zone to give timestamps in, such as `Asia/Tokyo`, overriding `Time-Zone`."),
+        ("Time-Zone" = Option<String>, Header, description = "IANA time zone to give timestamps in. Defaults to UTC."),
+    ),
+    responses(
+        (status = 200, description = "Stream of changes, named `{entity}.{kind}`. \
+            `resync` is sent when the events after `Last-Event-ID` are no longer kept.", content_type = "text/event-stream"),
+        (status = 400, description = "Unknown time zone.", body = ApiError)
+    )
+)]
+pub async fn get_events(
+    Query(filter): Query<EventFilter>,
+    headers: HeaderMap,
+    zone: OutputZone,
+    Extension(hub): Extension<EventHub>
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let last_event_id = headers.get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse::<u64>().ok());
+    let (backlog, receiver) = hub.subscribe(last_event_id);
+
+    let backlog = match backlog {
+        Some(events) => events.into_iter().map(Ok).collect::<Vec<_>>(),
+        None => vec![Err(BroadcastStreamRecvError::Lagged(0))]
+    };
+    let stream = futures::stream::iter(backlog)
+        .chain(BroadcastStream::new(receiver))
+        .filter_map(move |received| {
+            let event = match received {
+                Ok(event) if filter.matches(&event.scope) => serde_json::to_string(&event.in_zone(zone.tz()))
+                    .map(|data| Event::default().id(event.id.to_string()).event(event.name()).data(data))
+                    .ok(),
+                Ok(_) => None,
+                // Client missed some events and has to fetch the current state again.
+                Err(BroadcastStreamRecvError::Lagged(_)) => Some(Event::default().event("resync").data("{}"))
+            };
+            futures::future::ready(event.map(Ok))
+        });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn liver_scope(liver: Option<&LiverObject>) -> ChangeScope {
+    ChangeScope {
+        affiliation_id: liver.and_then(LiverObject::affiliation_id).map(Into::into),
+        liver_id: liver.map(|liver| liver.liver_id().into()),
+        channel_id: None
+    }
+}
+
+#[async_trait::async_trait]
+impl Notify for AffiliationObject {
+    async fn notify(changed: Changed<Self>, _: &PgPool) -> Result<(ChangeKind, ChangePayload, ChangeScope), sqlx::Error> {
+        let (kind, affiliation) = match changed {
+            Changed::Inserted(new) => (ChangeKind::Inserted, new),
+            Changed::Updated(_, new) => (ChangeKind::Updated, new),
+            Changed::Deleted(old) => (ChangeKind::Deleted, old)
+        };
+        let scope = ChangeScope { affiliation_id: Some(affiliation.affiliation_id().into()), ..Default::default() };
+        Ok((kind, ChangePayload::Affiliation { affiliation: Affiliation::from(affiliation) }, scope))
+    }
+}
+
+#[async_trait::async_trait]
+impl Notify for LiverObject {
+    async fn notify(changed: Changed<Self>, _: &PgPool) -> Result<(ChangeKind, ChangePayload, ChangeScope), sqlx::Error> {
+        let (kind, liver) = match changed {
+            Changed::Inserted(new) => (ChangeKind::Inserted, new),
+            Changed::Updated(_, new) => (ChangeKind::Updated, new),
+            Changed::Deleted(old) => (ChangeKind::Deleted, old)
+        };
+        let scope = liver_scope(Some(&liver));
+        Ok((kind, ChangePayload::Liver { liver: Liver::from(liver) }, scope))
+    }
+}
+
+#[async_trait::async_trait]
+impl Notify for ChannelObject {
+    async fn notify(changed: Changed<Self>, pool: &PgPool) -> Result<(ChangeKind, ChangePayload, ChangeScope), sqlx::Error> {
+        let (kind, channel) = match changed {
+            Changed::Inserted(new) => (ChangeKind::Inserted, new),
+            Changed::Updated(_, new) => (ChangeKind::Updated, new),
+            Changed::Deleted(old) => (ChangeKind::Deleted, old)
+        };
+        let liver = match channel.liver_id() {
+            Some(id) => LiverObject::fetch_from_id(id, pool).await?,
+            None => None
+        };
+        let scope = ChangeScope {
+            channel_id: Some(channel.channel_id().to_owned().into()),
+            ..liver_scope(liver.as_ref())
+        };
+        let payload = ChangePayload::Channel {
+            channel: Channel::from(channel),
+            liver: liver.map(Liver::from)
+        };
+        Ok((kind, payload, scope))
+    }
+}
+
+#[async_trait::async_trait]
+impl Notify for VideoObject {
+    async fn notify(changed: Changed<Self>, pool: &PgPool) -> Result<(ChangeKind, ChangePayload, ChangeScope), sqlx::Error> {
+        let (kind, video) = match changed {
+            Changed::Inserted(new) if new.started_at().is_some() => (ChangeKind::Live, new),
+            Changed::Inserted(new) if new.will_start_at().is_some() => (ChangeKind::Scheduled, new),
+            Changed::Inserted(new) => (ChangeKind::Inserted, new),
+            Changed::Updated(old, new) if old.started_at().is_none() && new.started_at().is_some() => (ChangeKind::Live, new),
+            Changed::Updated(old, new) if old.will_start_at() != new.will_start_at() => (ChangeKind::Rescheduled, new),
+            Changed::Updated(_, new) => (ChangeKind::Updated, new),
+            Changed::Deleted(old) => (ChangeKind::Deleted, old)
+        };
+        let channel = match video.channel_id() {
+            Some(id) => ChannelObject::fetch_from_id(id, pool).await?,
+            None => None
+        };
+        let liver = match channel.as_ref().and_then(ChannelObject::liver_id) {
+            Some(id) => LiverObject::fetch_from_id(id, pool).await?,
+            None => None
+        };
+        let scope = ChangeScope {
+            channel_id: video.channel_id().map(|id| id.to_owned().into()),
+            ..liver_scope(liver.as_ref())
+        };
+        let payload = ChangePayload::Video {
+            video: Video::from(video),
+            channel: channel.map(Channel::from),
+            liver: liver.map(Liver::from)
+        };
+        Ok((kind, payload, scope))
+    }
+}
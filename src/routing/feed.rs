@@ -0,0 +1,189 @@
+use axum::Extension;
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::database::{ScheduleFilter, ScheduledVideoObject, Table, VideoFeed};
+
+use super::{ApiError, ErrorResponse, Precondition, Representation, ResponseCache};
+
+const ENTRY_LIMIT: i64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FeedFormat {
+    Atom,
+    Rss
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeedQuery {
+    liver: Option<i64>,
+    affiliation: Option<i64>
+}
+
+#[utoipa::path(
+    get,
+    path = "/feeds/{feed}",
+    tag = "feed",
+    params(
+        ("feed" = String, Path, description = "One of `schedules.atom`, `schedules.rss`, `archives.atom` or `archives.rss`."),
+        ("liver" = Option<i64>, Query, description = "Only videos of the liver."),
+        ("affiliation" = Option<i64>, Query, description = "Only videos of livers in the affiliation.")
+    ),
+    responses(
+        (status = 200, description = "Newly scheduled streams or newly published archives, latest first.", content_type = "application/atom+xml"),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 404, description = "Feed is not found.", body = ApiError),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
+pub async fn get_feed(
+    Path(feed): Path<String>,
+    Query(query): Query<FeedQuery>,
+    precondition: Precondition,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let (kind, format) = match feed.as_str() {
+        "schedules.atom" => (VideoFeed::Schedules, FeedFormat::Atom),
+        "schedules.rss" => (VideoFeed::Schedules, FeedFormat::Rss),
+        "archives.atom" => (VideoFeed::Archives, FeedFormat::Atom),
+        "archives.rss" => (VideoFeed::Archives, FeedFormat::Rss),
+        _ => return Err(ApiError::reason(format!("{} is not found.", feed)).report(StatusCode::NOT_FOUND))
+    };
+    let filter = ScheduleFilter {
+        affiliation_id: query.affiliation,
+        liver_ids: query.liver.map(|id| vec![id])
+    };
+    let key = format!("feed:{}:{:?}:{:?}", feed, filter.affiliation_id, filter.liver_ids);
+    let repr = cache.get_or_fetch(Table::Videos, key, || async {
+        let entries = ScheduledVideoObject::fetch_latest_published(kind, &filter, ENTRY_LIMIT, &pool).await
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        let feed_id = format!("urn:matatabi:feed:{}:{}", feed, scope_of(&filter));
+        let title = match kind {
+            VideoFeed::Schedules => "Matatabi - newly scheduled streams",
+            VideoFeed::Archives => "Matatabi - newly published archives"
+        };
+        let updated = entries.iter()
+            .filter_map(|entry| entry.video().updated_at().or_else(|| entry.video().published_at()))
+            .max();
+        // Feed of a single liver shows the logo of the channel.
+        let icon = filter.liver_ids.as_ref()
+            .and_then(|_| entries.iter().find_map(ScheduledVideoObject::logo_url));
+        let body = match format {
+            FeedFormat::Atom => atom(&feed_id, title, icon, updated, &entries),
+            FeedFormat::Rss => rss(title, &entries)
+        };
+        let content_type = match format {
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8"
+        };
+        Ok(Representation::new(body, content_type, updated))
+    }).await?;
+    Ok(precondition.respond(repr))
+}
+
+fn scope_of(filter: &ScheduleFilter) -> String {
+    match (filter.affiliation_id, &filter.liver_ids) {
+        (Some(affiliation), _) => format!("affiliation:{}", affiliation),
+        (None, Some(livers)) => format!("liver:{}", livers.iter().map(i64::to_string).collect::<Vec<_>>().join(",")),
+        (None, None) => "all".to_string()
+    }
+}
+
+fn video_url(entry: &ScheduledVideoObject) -> String {
+    format!("https://www.youtube.com/watch?v={}", String::from(entry.video().video_id().to_owned()))
+}
+
+fn summary(entry: &ScheduledVideoObject) -> String {
+    match entry.video().will_start_at() {
+        Some(start) if entry.video().started_at().is_none() => format!("Starts at {}\n\n{}", start.to_rfc3339(), entry.video().description()),
+        _ => entry.video().description().to_owned()
+    }
+}
+
+fn atom(feed_id: &str, title: &str, icon: Option<&str>, updated: Option<DateTime<Utc>>, entries: &[ScheduledVideoObject]) -> String {
+    let updated = updated.unwrap_or_else(Utc::now);
+    let mut xml = String::new();
+    xml.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    xml.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+    xml.push_str(&format!("<id>{}</id>", escape(feed_id)));
+    xml.push_str(&format!("<title>{}</title>", escape(title)));
+    xml.push_str(&format!("<updated>{}</updated>", updated.to_rfc3339()));
+    xml.push_str("<generator>matatabi</generator>");
+    if let Some(icon) = icon {
+        xml.push_str(&format!("<icon>{}</icon>", escape(icon)));
+    }
+    for entry in entries {
+        let video = entry.video();
+        let url = video_url(entry);
+        let published = video.published_at().unwrap_or(updated);
+        let entry_updated = video.updated_at().unwrap_or(published);
+        xml.push_str("<entry>");
+        xml.push_str(&format!("<id>urn:youtube:video:{}</id>", escape(&String::from(video.video_id().to_owned()))));
+        xml.push_str(&format!("<title>{}</title>", escape(video.title())));
+        xml.push_str(&format!(r#"<link rel="alternate" href="{}"/>"#, escape(&url)));
+        xml.push_str(&format!(r#"<link rel="enclosure" type="image/jpeg" href="{}"/>"#, escape(video.thumbnail_url())));
+        xml.push_str(&format!("<published>{}</published>", published.to_rfc3339()));
+        xml.push_str(&format!("<updated>{}</updated>", entry_updated.to_rfc3339()));
+        xml.push_str("<author>");
+        xml.push_str(&format!("<name>{}</name>", escape(entry.liver_name().unwrap_or("unknown"))));
+        if let Some(channel) = video.channel_id() {
+            xml.push_str(&format!("<uri>https://www.youtube.com/channel/{}</uri>", escape(&String::from(channel.to_owned()))));
+        }
+        xml.push_str("</author>");
+        xml.push_str(&format!(r#"<summary type="text">{}</summary>"#, escape(&summary(entry))));
+        xml.push_str("</entry>");
+    }
+    xml.push_str("</feed>");
+    xml
+}
+
+fn rss(title: &str, entries: &[ScheduledVideoObject]) -> String {
+    let mut xml = String::new();
+    xml.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    xml.push_str(r#"<rss version="2.0"><channel>"#);
+    xml.push_str(&format!("<title>{}</title>", escape(title)));
+    xml.push_str("<link>https://www.youtube.com/</link>");
+    xml.push_str(&format!("<description>{}</description>", escape(title)));
+    xml.push_str("<generator>matatabi</generator>");
+    for entry in entries {
+        let video = entry.video();
+        let url = video_url(entry);
+        xml.push_str("<item>");
+        xml.push_str(&format!("<title>{}</title>", escape(video.title())));
+        xml.push_str(&format!("<link>{}</link>", escape(&url)));
+        xml.push_str(&format!(r#"<guid isPermaLink="false">urn:youtube:video:{}</guid>"#, escape(&String::from(video.video_id().to_owned()))));
+        if let Some(published) = video.published_at() {
+            xml.push_str(&format!("<pubDate>{}</pubDate>", published.to_rfc2822()));
+        }
+        if let Some(liver) = entry.liver_name() {
+            xml.push_str(&format!("<category>{}</category>", escape(liver)));
+        }
+        xml.push_str(&format!("<description>{}</description>", escape(&summary(entry))));
+        xml.push_str(&format!(r#"<enclosure url="{}" length="0" type="image/jpeg"/>"#, escape(video.thumbnail_url())));
+        xml.push_str("</item>");
+    }
+    xml.push_str("</channel></rss>");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0.
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
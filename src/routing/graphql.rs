@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Enum, InputObject, Object, Result, Schema};
+use async_graphql::connection::{query, Connection, Edge};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::http::GraphiQLSource;
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::Extension;
+use axum::response::Html;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::database::{
+    AffiliationId, AffiliationObject,
+    LiverId, LiverObject,
+    ChannelId, ChannelObject,
+    VideoId, VideoObject,
+    ScheduleFilter, ScheduledVideoObject, VideoStatus,
+    Fetch
+};
+
+use super::ApiVersion;
+
+/// Upper limit of nodes returned by a connection.
+const MAX_PAGE_SIZE: usize = 100;
+
+pub type MatatabiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn graphql_schema(pool: PgPool) -> MatatabiSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(DataLoader::new(DatabaseLoader { pool: pool.clone() }, tokio::spawn))
+        .data(pool)
+        .limit_depth(12)
+        .limit_complexity(2048)
+        .finish()
+}
+
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    tag = "graphql",
+    request_body(content = Object, description = "GraphQL request.", content_type = "application/json"),
+    responses(
+        (status = 200, description = "GraphQL response.", body = Object)
+    )
+)]
+pub async fn post_graphql(
+    Extension(schema): Extension<MatatabiSchema>,
+    request: GraphQLRequest
+) -> GraphQLResponse {
+    schema.execute(request.into_inner()).await.into()
+}
+
+#[utoipa::path(
+    get,
+    path = "/graphql",
+    tag = "graphql",
+    responses(
+        (status = 200, description = "GraphiQL playground.", content_type = "text/html")
+    )
+)]
+pub async fn get_graphiql(Extension(version): Extension<ApiVersion>) -> Html<String> {
+    let endpoint = format!("{}/graphql", version.prefix());
+    Html(GraphiQLSource::build().endpoint(&endpoint).finish())
+}
+
+/// Batches lookups issued by the resolvers of sibling nodes into one query.
+pub struct DatabaseLoader {
+    pool: PgPool
+}
+
+type LoaderError = Arc<sqlx::Error>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LiversOf(AffiliationId);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ChannelsOf(LiverId);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct NextUpcomingOfLiver(LiverId);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct NextUpcomingOfChannel(ChannelId);
+
+#[async_trait::async_trait]
+impl Loader<AffiliationId> for DatabaseLoader {
+    type Value = AffiliationObject;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[AffiliationId]) -> Result<HashMap<AffiliationId, Self::Value>, Self::Error> {
+        let ids = keys.iter().copied().map(i64::from).collect::<Vec<_>>();
+        Ok(AffiliationObject::fetch_from_ids(&ids, &self.pool).await?
+            .into_iter()
+            .map(|affiliation| (affiliation.affiliation_id(), affiliation))
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<LiverId> for DatabaseLoader {
+    type Value = LiverObject;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[LiverId]) -> Result<HashMap<LiverId, Self::Value>, Self::Error> {
+        let ids = keys.iter().copied().map(i64::from).collect::<Vec<_>>();
+        Ok(LiverObject::fetch_from_ids(&ids, &self.pool).await?
+            .into_iter()
+            .map(|liver| (liver.liver_id(), liver))
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<ChannelId> for DatabaseLoader {
+    type Value = ChannelObject;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[ChannelId]) -> Result<HashMap<ChannelId, Self::Value>, Self::Error> {
+        let ids = keys.iter().cloned().map(String::from).collect::<Vec<_>>();
+        Ok(ChannelObject::fetch_from_ids(&ids, &self.pool).await?
+            .into_iter()
+            .map(|channel| (channel.channel_id().to_owned(), channel))
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<LiversOf> for DatabaseLoader {
+    type Value = Vec<LiverObject>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[LiversOf]) -> Result<HashMap<LiversOf, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|LiversOf(id)| i64::from(*id)).collect::<Vec<_>>();
+        let mut grouped = HashMap::<LiversOf, Vec<LiverObject>>::new();
+        for liver in LiverObject::fetch_filtered_affiliations(&ids, &self.pool).await? {
+            if let Some(affiliation_id) = liver.affiliation_id() {
+                grouped.entry(LiversOf(affiliation_id)).or_default().push(liver);
+            }
+        }
+        Ok(grouped)
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<ChannelsOf> for DatabaseLoader {
+    type Value = Vec<ChannelObject>;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[ChannelsOf]) -> Result<HashMap<ChannelsOf, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|ChannelsOf(id)| i64::from(*id)).collect::<Vec<_>>();
+        let mut grouped = HashMap::<ChannelsOf, Vec<ChannelObject>>::new();
+        for channel in ChannelObject::fetch_filtered_livers(&ids, &self.pool).await? {
+            if let Some(liver_id) = channel.liver_id() {
+                grouped.entry(ChannelsOf(liver_id)).or_default().push(channel);
+            }
+        }
+        Ok(grouped)
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<NextUpcomingOfLiver> for DatabaseLoader {
+    type Value = VideoObject;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[NextUpcomingOfLiver]) -> Result<HashMap<NextUpcomingOfLiver, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|NextUpcomingOfLiver(id)| i64::from(*id)).collect::<Vec<_>>();
+        Ok(ScheduledVideoObject::fetch_next_upcoming_of_livers(&ids, &self.pool).await?
+            .into_iter()
+            .filter_map(|upcoming| upcoming.liver_id().map(|id| (NextUpcomingOfLiver(id), upcoming.into_video())))
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<NextUpcomingOfChannel> for DatabaseLoader {
+    type Value = VideoObject;
+    type Error = LoaderError;
+
+    async fn load(&self, keys: &[NextUpcomingOfChannel]) -> Result<HashMap<NextUpcomingOfChannel, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|NextUpcomingOfChannel(id)| String::from(id.to_owned())).collect::<Vec<_>>();
+        Ok(ScheduledVideoObject::fetch_next_upcoming_of_channels(&ids, &self.pool).await?
+            .into_iter()
+            .map(ScheduledVideoObject::into_video)
+            .filter_map(|video| video.channel_id().cloned().map(|id| (NextUpcomingOfChannel(id), video)))
+            .collect())
+    }
+}
+
+fn loader<'a>(ctx: &Context<'a>) -> &'a DataLoader<DatabaseLoader> {
+    ctx.data_unchecked::<DataLoader<DatabaseLoader>>()
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[graphql(name = "VideoStatus")]
+pub enum VideoStatusInput {
+    Upcoming,
+    Live,
+    Uploaded
+}
+
+impl From<VideoStatusInput> for VideoStatus {
+    fn from(status: VideoStatusInput) -> Self {
+        match status {
+            VideoStatusInput::Upcoming => VideoStatus::Upcoming,
+            VideoStatusInput::Live => VideoStatus::Live,
+            VideoStatusInput::Uploaded => VideoStatus::Uploaded
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, InputObject)]
+pub struct VideoFilter {
+    affiliation_id: Option<i64>,
+    liver_ids: Option<Vec<i64>>,
+    channel_ids: Option<Vec<String>>,
+    status: Option<VideoStatusInput>
+}
+
+async fn videos_connection(
+    pool: &PgPool,
+    filter: VideoFilter,
+    after: Option<String>,
+    before: Option<String>,
+    first: Option<i32>,
+    last: Option<i32>
+) -> Result<Connection<usize, VideoNode>> {
+    query(after, before, first, last, |after: Option<usize>, before: Option<usize>, first, last| async move {
+        let mut start = match after {
+            Some(after) => after.checked_add(1)
+                .ok_or_else(|| async_graphql::Error::new("\"after\" is out of range."))?,
+            None => 0
+        };
+        let mut end = before.unwrap_or(usize::MAX);
+        if let Some(first) = first {
+            end = end.min(start.saturating_add(first));
+        }
+        if let Some(last) = last {
+            if end == usize::MAX {
+                return Err(async_graphql::Error::new("\"last\" requires \"before\"."));
+            }
+            start = start.max(end.saturating_sub(last));
+        }
+        // Nothing lies between an `after` at or past `before`.
+        if end <= start {
+            return Ok(Connection::new(start > 0, false));
+        }
+        let limit = (end - start).min(MAX_PAGE_SIZE);
+        let offset = i64::try_from(start)
+            .map_err(|_| async_graphql::Error::new("\"after\" is out of range."))?;
+
+        let schedule = ScheduleFilter { affiliation_id: filter.affiliation_id, liver_ids: filter.liver_ids };
+        // One extra row tells whether the next page exists.
+        let mut videos = ScheduledVideoObject::fetch_filtered(
+            &schedule,
+            filter.channel_ids.as_deref(),
+            filter.status.map(VideoStatus::from),
+            limit as i64 + 1,
+            offset,
+            pool
+        ).await?;
+        let has_next_page = videos.len() > limit;
+        videos.truncate(limit);
+
+        let mut connection = Connection::new(start > 0, has_next_page);
+        connection.edges.extend(videos.into_iter()
+            .enumerate()
+            .map(|(index, video)| Edge::new(start + index, VideoNode(video.into_video()))));
+        Ok::<_, async_graphql::Error>(connection)
+    }).await
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn affiliations(&self, ctx: &Context<'_>, name: Option<String>) -> Result<Vec<AffiliationNode>> {
+        let pool = ctx.data_unchecked::<PgPool>();
+        Ok(AffiliationObject::fetch_all(pool).await?
+            .into_iter()
+            .filter(|affiliation| name.as_ref().map_or(true, |name| contains_ignore_case(affiliation.name(), name)))
+            .map(AffiliationNode)
+            .collect())
+    }
+
+    async fn affiliation(&self, ctx: &Context<'_>, id: i64) -> Result<Option<AffiliationNode>> {
+        Ok(loader(ctx).load_one(AffiliationId::new(id)).await?.map(AffiliationNode))
+    }
+
+    async fn livers(&self, ctx: &Context<'_>, affiliation_id: Option<i64>, name: Option<String>) -> Result<Vec<LiverNode>> {
+        let pool = ctx.data_unchecked::<PgPool>();
+        let livers = match affiliation_id {
+            Some(id) => LiverObject::fetch_filtered_affiliation(id, pool).await?,
+            None => <LiverObject as Fetch>::fetch_all(pool).await?
+        };
+        Ok(livers.into_iter()
+            .filter(|liver| name.as_ref().map_or(true, |name| {
+                contains_ignore_case(liver.name(), name) || contains_ignore_case(liver.localized_name(), name)
+            }))
+            .map(LiverNode)
+            .collect())
+    }
+
+    async fn liver(&self, ctx: &Context<'_>, id: i64) -> Result<Option<LiverNode>> {
+        Ok(loader(ctx).load_one(LiverId::new(id)).await?.map(LiverNode))
+    }
+
+    async fn channels(&self, ctx: &Context<'_>, liver_id: Option<i64>) -> Result<Vec<ChannelNode>> {
+        let channels = match liver_id {
+            Some(id) => loader(ctx).load_one(ChannelsOf(LiverId::new(id))).await?.unwrap_or_default(),
+            None => ChannelObject::fetch_all(ctx.data_unchecked::<PgPool>()).await?
+        };
+        Ok(channels.into_iter().map(ChannelNode).collect())
+    }
+
+    async fn channel(&self, ctx: &Context<'_>, id: String) -> Result<Option<ChannelNode>> {
+        Ok(loader(ctx).load_one(ChannelId::new(id)).await?.map(ChannelNode))
+    }
+
+    async fn videos(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<VideoFilter>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>
+    ) -> Result<Connection<usize, VideoNode>> {
+        videos_connection(ctx.data_unchecked::<PgPool>(), filter.unwrap_or_default(), after, before, first, last).await
+    }
+
+    async fn video(&self, ctx: &Context<'_>, id: String) -> Result<Option<VideoNode>> {
+        let pool = ctx.data_unchecked::<PgPool>();
+        Ok(VideoObject::fetch_from_id(&VideoId::new(id), pool).await?.map(VideoNode))
+    }
+}
+
+pub struct AffiliationNode(AffiliationObject);
+
+#[Object(name = "Affiliation")]
+impl AffiliationNode {
+    async fn affiliation_id(&self) -> i64 {
+        self.0.affiliation_id().into()
+    }
+
+    async fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    async fn livers(&self, ctx: &Context<'_>) -> Result<Vec<LiverNode>> {
+        Ok(loader(ctx).load_one(LiversOf(self.0.affiliation_id())).await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(LiverNode)
+            .collect())
+    }
+}
+
+pub struct LiverNode(LiverObject);
+
+#[Object(name = "Liver")]
+impl LiverNode {
+    async fn liver_id(&self) -> i64 {
+        self.0.liver_id().into()
+    }
+
+    async fn affiliation_id(&self) -> Option<i64> {
+        self.0.affiliation_id().map(Into::into)
+    }
+
+    async fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    async fn localized_name(&self) -> &str {
+        self.0.localized_name()
+    }
+
+    async fn affiliation(&self, ctx: &Context<'_>) -> Result<Option<AffiliationNode>> {
+        match self.0.affiliation_id() {
+            Some(id) => Ok(loader(ctx).load_one(id).await?.map(AffiliationNode)),
+            None => Ok(None)
+        }
+    }
+
+    async fn channels(&self, ctx: &Context<'_>) -> Result<Vec<ChannelNode>> {
+        Ok(loader(ctx).load_one(ChannelsOf(self.0.liver_id())).await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(ChannelNode)
+            .collect())
+    }
+
+    /// The earliest upcoming video in the channels of the liver.
+    async fn next_upcoming(&self, ctx: &Context<'_>) -> Result<Option<VideoNode>> {
+        Ok(loader(ctx).load_one(NextUpcomingOfLiver(self.0.liver_id())).await?.map(VideoNode))
+    }
+
+    async fn videos(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<VideoStatusInput>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>
+    ) -> Result<Connection<usize, VideoNode>> {
+        let filter = VideoFilter { liver_ids: Some(vec![self.0.liver_id().into()]), status, ..Default::default() };
+        videos_connection(ctx.data_unchecked::<PgPool>(), filter, after, before, first, last).await
+    }
+}
+
+pub struct ChannelNode(ChannelObject);
+
+#[Object(name = "Channel")]
+impl ChannelNode {
+    async fn channel_id(&self) -> String {
+        self.0.channel_id().to_owned().into()
+    }
+
+    async fn liver_id(&self) -> Option<i64> {
+        self.0.liver_id().map(Into::into)
+    }
+
+    async fn logo_url(&self) -> &str {
+        self.0.logo_url()
+    }
+
+    async fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.0.published_at()
+    }
+
+    async fn description(&self) -> &str {
+        self.0.description()
+    }
+
+    async fn liver(&self, ctx: &Context<'_>) -> Result<Option<LiverNode>> {
+        match self.0.liver_id() {
+            Some(id) => Ok(loader(ctx).load_one(id).await?.map(LiverNode)),
+            None => Ok(None)
+        }
+    }
+
+    /// The earliest upcoming video in the channel.
+    async fn next_upcoming(&self, ctx: &Context<'_>) -> Result<Option<VideoNode>> {
+        Ok(loader(ctx).load_one(NextUpcomingOfChannel(self.0.channel_id().to_owned())).await?.map(VideoNode))
+    }
+
+    async fn videos(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<VideoStatusInput>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>
+    ) -> Result<Connection<usize, VideoNode>> {
+        let filter = VideoFilter { channel_ids: Some(vec![self.0.channel_id().to_owned().into()]), status, ..Default::default() };
+        videos_connection(ctx.data_unchecked::<PgPool>(), filter, after, before, first, last).await
+    }
+}
+
+pub struct VideoNode(VideoObject);
+
+#[Object(name = "Video")]
+impl VideoNode {
+    async fn video_id(&self) -> String {
+        self.0.video_id().to_owned().into()
+    }
+
+    async fn channel_id(&self) -> Option<String> {
+        self.0.channel_id().map(|id| id.to_owned().into())
+    }
+
+    async fn title(&self) -> &str {
+        self.0.title()
+    }
+
+    async fn description(&self) -> &str {
+        self.0.description()
+    }
+
+    async fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.0.published_at()
+    }
+
+    async fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.0.updated_at()
+    }
+
+    async fn will_start_at(&self) -> Option<DateTime<Utc>> {
+        self.0.will_start_at()
+    }
+
+    async fn started_at(&self) -> Option<DateTime<Utc>> {
+        self.0.started_at()
+    }
+
+    async fn ended_at(&self) -> Option<DateTime<Utc>> {
+        self.0.ended_at()
+    }
+
+    async fn thumbnail_url(&self) -> &str {
+        self.0.thumbnail_url()
+    }
+
+    async fn channel(&self, ctx: &Context<'_>) -> Result<Option<ChannelNode>> {
+        match self.0.channel_id() {
+            Some(id) => Ok(loader(ctx).load_one(id.to_owned()).await?.map(ChannelNode)),
+            None => Ok(None)
+        }
+    }
+}
@@ -1,34 +1,83 @@
 use std::collections::HashMap;
-use axum::{Extension, Json};
+use axum::Extension;
 use axum::extract::Query;
 use axum::http::StatusCode;
+use axum::response::Response;
 use sqlx::PgPool;
 use crate::models::Liver;
-use crate::database::LiverObject;
-use super::{ApiError, ErrorResponse};
+use crate::database::{with_names, LiverObject, Table};
+use super::{ApiError, ErrorResponse, Locales, Precondition, Representation, ResponseCache};
 
+#[utoipa::path(
+    get,
+    path = "/livers",
+    tag = "liver",
+    params(
+        ("lang" = Option<String>, Query, description = "Comma separated language tags to pick `display_name` in, overriding `Accept-Language`."),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages of `display_name`, falling back to `ja` and `en`."),
+    ),
+    responses(
+        (status = 200, description = "All livers.", body = [Liver]),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
 pub async fn get_livers(
-    Extension(pool): Extension<PgPool>
-) -> Result<Json<Vec<Liver>>, ErrorResponse> {
-    let liver_all = LiverObject::fetch_all(&pool).await
-        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?
-        .into_iter()
-        .map(Liver::from)
-        .collect();
-    Ok(Json(liver_all))
+    precondition: Precondition,
+    locales: Locales,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let repr = cache.get_or_fetch(Table::Livers, format!("all;{}", locales.key()), || async {
+        let liver_all = LiverObject::fetch_all(&pool).await
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        let last_modified = liver_all.iter().filter_map(LiverObject::updated_at).max();
+        let liver_all = liver_all.into_iter()
+            .map(|liver| Liver::localized(liver, locales.as_slice()))
+            .collect::<Vec<_>>();
+        Representation::json(&liver_all, last_modified)
+    }).await?;
+    Ok(Locales::vary(precondition.respond(repr)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/livers/filtered",
+    tag = "liver",
+    params(
+        ("affiliated" = Option<u64>, Query, description = "Affiliation id to filter livers by."),
+        ("lang" = Option<String>, Query, description = "Comma separated language tags to pick `display_name` in, overriding `Accept-Language`."),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages of `display_name`, falling back to `ja` and `en`."),
+    ),
+    responses(
+        (status = 200, description = "Livers belonging to the affiliation.", body = [Liver]),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
 pub async fn get_livers_filtered(
-    Query(affiliation_id): Query<HashMap<String, u64>>,
-    Extension(pool): Extension<PgPool>
-) -> Result<Json<Vec<Liver>>, ErrorResponse> {
-    let id = affiliation_id.into_iter().find(|(param_key, _)| param_key == "affiliated")
-        .map(|(_, id)| id)
-        .unwrap_or(0);
-    let livers = LiverObject::fetch_filtered_affiliation(id as i64, &pool).await
-        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?
-        .into_iter()
-        .map(Liver::from)
-        .collect();
-    Ok(Json(livers))
+    Query(params): Query<HashMap<String, String>>,
+    precondition: Precondition,
+    locales: Locales,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    // Other parameters such as `lang` are read by their own extractors.
+    let id = match params.get("affiliated") {
+        Some(id) => id.parse::<u64>()
+            .map_err(|e| ApiError::new(e).report(StatusCode::BAD_REQUEST))?,
+        None => 0
+    };
+    let repr = cache.get_or_fetch(Table::Livers, format!("affiliated:{};{}", id, locales.key()), || async {
+        let livers = LiverObject::fetch_filtered_affiliation(id as i64, &pool).await
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        let livers = with_names(livers, &pool).await
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        let last_modified = livers.iter().filter_map(LiverObject::updated_at).max();
+        let livers = livers.into_iter()
+            .map(|liver| Liver::localized(liver, locales.as_slice()))
+            .collect::<Vec<_>>();
+        Representation::json(&livers, last_modified)
+    }).await?;
+    Ok(Locales::vary(precondition.respond(repr)))
 }
\ No newline at end of file
@@ -0,0 +1,139 @@
+use std::convert::Infallible;
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::{header, HeaderValue};
+use axum::http::request::Parts;
+use axum::response::Response;
+use serde::Deserialize;
+
+use crate::database::DEFAULT_LOCALES;
+
+/// Locales to pick names in, most preferred first.
+///
+/// `?lang=` (comma separated) takes precedence over `Accept-Language`.
+/// Each tag is followed by its shorter forms, e.g. `en-us` by `en`, and the chain ends with `ja` and `en`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locales(Vec<String>);
+
+#[derive(Debug, Deserialize)]
+struct LangQuery {
+    lang: Option<String>
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Locales {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let lang = Query::<LangQuery>::try_from_uri(&parts.uri)
+            .ok()
+            .and_then(|Query(query)| query.lang);
+        let accept_language = parts.headers.get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok());
+        Ok(Self::negotiate(lang.as_deref(), accept_language))
+    }
+}
+
+impl Default for Locales {
+    fn default() -> Self {
+        Self::negotiate(None, None)
+    }
+}
+
+impl Locales {
+    pub fn negotiate(lang: Option<&str>, accept_language: Option<&str>) -> Self {
+        let requested = match lang.filter(|lang| !lang.trim().is_empty()) {
+            Some(lang) => lang.split(',').map(str::to_owned).collect(),
+            None => accept_language.map(by_quality).unwrap_or_default()
+        };
+        let mut chain = Vec::<String>::new();
+        let tags = requested.iter()
+            .map(|tag| tag.trim().replace('_', "-").to_lowercase())
+            .filter(|tag| !tag.is_empty() && tag != "*");
+        for tag in tags {
+            // RFC 4647 lookup: `zh-hant-tw`, `zh-hant`, `zh`.
+            let subtags = tag.split('-').collect::<Vec<_>>();
+            for len in (1..=subtags.len()).rev() {
+                chain.push(subtags[..len].join("-"));
+            }
+        }
+        chain.extend(DEFAULT_LOCALES.iter().map(|locale| locale.to_string()));
+
+        let mut seen = std::collections::HashSet::new();
+        chain.retain(|locale| seen.insert(locale.clone()));
+        Self(chain)
+    }
+
+    pub fn as_slice(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Identifies the chain in cache keys.
+    pub fn key(&self) -> String {
+        self.0.join(",")
+    }
+
+    /// Mark a response whose names depend on `Accept-Language`.
+    pub fn vary(mut response: Response) -> Response {
+        response.headers_mut().append(header::VARY, HeaderValue::from_static("Accept-Language"));
+        response
+    }
+}
+
+/// Language ranges of `Accept-Language`, highest quality first, without those of `q=0`.
+fn by_quality(accept_language: &str) -> Vec<String> {
+    let mut ranges = accept_language.split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let tag = params.next()?.trim();
+            let quality = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (quality > 0.0).then(|| (tag.to_owned(), quality))
+        })
+        .collect::<Vec<_>>();
+    // Stable, so that ranges of the same quality keep their order.
+    ranges.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    ranges.into_iter().map(|(tag, _)| tag).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{by_quality, Locales};
+
+    fn chain(lang: Option<&str>, accept_language: Option<&str>) -> Vec<String> {
+        Locales::negotiate(lang, accept_language).as_slice().to_vec()
+    }
+
+    #[test]
+    fn ranges_are_ordered_by_quality() {
+        assert_eq!(by_quality("en-US,ja;q=0.5"), ["en-US", "ja"]);
+        assert_eq!(by_quality("ja;q=0.5, fr;q=0.8, en"), ["en", "fr", "ja"]);
+        assert_eq!(by_quality("ko;q=0.5, zh;q=0.5"), ["ko", "zh"], "equal qualities keep their order");
+    }
+
+    #[test]
+    fn ranges_of_zero_or_malformed_quality_are_dropped() {
+        assert_eq!(by_quality("fr;q=0, en"), ["en"]);
+        assert_eq!(by_quality("fr;q=0.0, de;q=high, en;q=0.1"), ["en"]);
+    }
+
+    #[test]
+    fn tags_are_followed_by_their_shorter_forms_and_the_defaults() {
+        assert_eq!(chain(None, Some("en-US,ja;q=0.5")), ["en-us", "en", "ja"]);
+        assert_eq!(chain(None, Some("zh-Hant-TW")), ["zh-hant-tw", "zh-hant", "zh", "ja", "en"]);
+        assert_eq!(chain(None, Some("zh_TW, zh;q=0.9, *;q=0.1")), ["zh-tw", "zh", "ja", "en"]);
+    }
+
+    #[test]
+    fn lang_takes_precedence_over_accept_language() {
+        assert_eq!(chain(Some("ko,en"), Some("fr")), ["ko", "en", "ja"]);
+        assert_eq!(chain(Some(" "), Some("fr")), ["fr", "ja", "en"]);
+    }
+
+    #[test]
+    fn nothing_requested_is_the_defaults() {
+        assert_eq!(chain(None, None), ["ja", "en"]);
+        assert_eq!(Locales::default().key(), "ja,en");
+    }
+}
@@ -2,31 +2,58 @@ mod affiliation;
 mod liver;
 mod channel;
 mod upcoming;
+mod openapi;
+mod conditional;
+mod cache;
+mod events;
+mod socket;
+mod calendar;
+mod feed;
+mod graphql;
+mod search;
+mod locale;
+mod suggest;
+mod zone;
+mod schedule;
+mod version;
+mod admin;
+mod console;
 
 pub use self::{
     affiliation::*,
     liver::*,
     channel::*,
     upcoming::*,
+    openapi::*,
+    conditional::{Precondition, Representation},
+    cache::ResponseCache,
+    events::{get_events, Changed, EventHub, Notify},
+    socket::get_socket,
+    calendar::*,
+    feed::get_feed,
+    graphql::{get_graphiql, graphql_schema, post_graphql},
+    search::get_search,
+    suggest::{get_suggest, Suggest, SuggestIndex},
+    locale::Locales,
+    zone::OutputZone,
+    schedule::get_schedule,
+    version::{deprecation, version, ApiVersion},
+    admin::{get_admin_account, post_record, patch_record, delete_record, Editable, Editor},
+    console::{
+        console_session, get_console, get_console_login, post_console_login, post_console_logout,
+        get_console_list, get_console_edit, post_console_edit, get_console_delete, post_console_delete,
+        Console, Session, CONSOLE_ROOT
+    },
 };
 
 use axum::http::StatusCode;
 use axum::Json;
 use serde::{Serialize, Deserialize};
-use serde_json::Value;
+use utoipa::ToSchema;
 
 type ErrorResponse = (StatusCode, Json<ApiError>);
 
-pub async fn version() -> Json<Value> {
-    Json(serde_json::json!({
-        "api_name": "matatabi",
-        "major_version": dotenv::var("MAJOR_API_VERSION").ok(),
-        "minor_version": dotenv::var("MINOR_API_VERSION").ok(),
-        "repository": "https://github.com/ReiRokusanami0010/matatabi",
-    }))
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ApiError {
     reason: String
 }
@@ -0,0 +1,167 @@
+use axum::Json;
+use utoipa::{Modify, OpenApi};
+use utoipa::openapi::{
+    Content, ObjectBuilder, PathItem, PathItemType, Ref, Required, ResponseBuilder, SchemaType, Server
+};
+use utoipa::openapi::path::{OperationBuilder, ParameterBuilder, ParameterIn};
+use utoipa::openapi::request_body::RequestBodyBuilder;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+
+use crate::models::{
+    Affiliation, AffiliationFacet, Channel, Liver, SearchHit, SearchHits, SearchResult, Video,
+    Suggestions, AffiliationSuggestion, LiverSuggestion, ChannelSuggestion,
+    Schedule, ScheduleDay, ScheduleHour, ScheduleEntry,
+    ApiInfo, ApiVersionInfo, BuildInfo, MigrationInfo,
+    AffiliationRecord, LiverRecord, ChannelRecord, VideoRecord
+};
+
+use super::ApiError;
+
+/// OpenAPI document of the web api.
+///
+/// Every handler registered to the router must be listed in `paths`,
+/// its schema is generated from the `#[utoipa::path]` attribute on the handler.
+/// `server::axum` tests that the routed paths and the documented ones are the same.
+/// The admin routes are generic over the table, so [AdminPaths] writes them out instead.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        super::version::version,
+        super::get_affiliations,
+        super::get_affiliation_from_id,
+        super::get_livers,
+        super::get_livers_filtered,
+        super::get_channels,
+        super::get_upcomings,
+        super::schedule::get_schedule,
+        super::events::get_events,
+        super::socket::get_socket,
+        super::calendar::get_upcomings_calendar,
+        super::calendar::get_affiliation_calendar,
+        super::calendar::get_liver_calendar,
+        super::feed::get_feed,
+        super::graphql::post_graphql,
+        super::graphql::get_graphiql,
+        super::search::get_search,
+        super::suggest::get_suggest,
+        openapi,
+    ),
+    components(
+        schemas(Affiliation, Liver, Channel, Video, SearchResult, SearchHits, SearchHit, AffiliationFacet,
+                Suggestions, AffiliationSuggestion, LiverSuggestion, ChannelSuggestion,
+                Schedule, ScheduleDay, ScheduleHour, ScheduleEntry,
+                ApiInfo, ApiVersionInfo, BuildInfo, MigrationInfo, ApiError,
+                AffiliationRecord, LiverRecord, ChannelRecord, VideoRecord)
+    ),
+    modifiers(&AdminPaths),
+    servers(
+        (url = "/v1", description = "Current version. Unprefixed paths are aliases of it, answering with `Deprecation` once it is configured."),
+    ),
+    tags(
+        (name = "meta", description = "Information about this api."),
+        (name = "affiliation", description = "Affiliations the livers belong to."),
+        (name = "liver", description = "Livers."),
+        (name = "channel", description = "Channels owned by livers."),
+        (name = "video", description = "Upcoming, live and archived videos."),
+        (name = "event", description = "Changes committed by the collector."),
+        (name = "calendar", description = "iCalendar feeds of scheduled streams."),
+        (name = "feed", description = "Atom and RSS feeds of videos."),
+        (name = "graphql", description = "GraphQL endpoint over the same models."),
+        (name = "search", description = "Full-text search and autocompletion over livers, channels and videos."),
+        (name = "admin", description = "Writes by curators, with a Cage token. Served unversioned under `/admin`."),
+    )
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "meta",
+    responses(
+        (status = 200, description = "OpenAPI document of this api.", body = Object)
+    )
+)]
+pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Tables written through `/admin`, with the schema of their record and of their id.
+const ADMIN_TABLES: [(&str, &str, SchemaType); 4] = [
+    ("affiliations", "AffiliationRecord", SchemaType::Integer),
+    ("livers", "LiverRecord", SchemaType::Integer),
+    ("channels", "ChannelRecord", SchemaType::String),
+    ("videos", "VideoRecord", SchemaType::String)
+];
+
+/// Documents the `/admin` routes, which are served outside of the versioned prefix and need a bearer token.
+pub struct AdminPaths;
+
+impl Modify for AdminPaths {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("cage", SecurityScheme::Http(
+            HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()));
+
+        let operation = |summary: &str| OperationBuilder::new()
+            .tag("admin")
+            .summary(Some(summary))
+            .security(SecurityRequirement::new("cage", Vec::<String>::new()))
+            .response("401", ResponseBuilder::new().description("No valid token was given."))
+            .response("403", error_response("The account may not write this."));
+        let item = |path_item_type: PathItemType, operation: OperationBuilder| {
+            let mut item = PathItem::new(path_item_type, operation);
+            item.servers = Some(vec![Server::new("/")]);
+            item
+        };
+        let json = |schema: &str| Content::new(Ref::from_schema_name(schema));
+
+        let account = operation("Account the token belongs to.")
+            .response("200", ResponseBuilder::new().description("Account of the token.")
+                .content("application/json", Content::new(ObjectBuilder::new())));
+        openapi.paths.paths.insert("/admin/account".to_string(), item(PathItemType::Get, account));
+
+        for (table, record, id_type) in ADMIN_TABLES {
+            let insert = operation("Insert a record, answering with it as stored.")
+                .request_body(Some(RequestBodyBuilder::new()
+                    .content("application/json", json(record))
+                    .required(Some(Required::True))
+                    .build()))
+                .response("201", ResponseBuilder::new().description("Inserted record.")
+                    .content("application/json", json(record)))
+                .response("409", error_response("A record with the id already exists."));
+            openapi.paths.paths.insert(format!("/admin/{}", table), item(PathItemType::Post, insert));
+
+            let id = ParameterBuilder::new()
+                .name("id")
+                .parameter_in(ParameterIn::Path)
+                .required(Required::True)
+                .schema(Some(ObjectBuilder::new().schema_type(id_type)))
+                .build();
+            let patch = operation("Apply a JSON Merge Patch, answering with the old and the new record.")
+                .parameter(id.clone())
+                .request_body(Some(RequestBodyBuilder::new()
+                    .content("application/merge-patch+json", Content::new(ObjectBuilder::new()))
+                    .required(Some(Required::True))
+                    .build()))
+                .response("200", ResponseBuilder::new().description("Record as it was (`old`) and as it is (`new`).")
+                    .content("application/json", Content::new(ObjectBuilder::new()
+                        .property("old", Ref::from_schema_name(record))
+                        .property("new", Ref::from_schema_name(record)))))
+                .response("404", error_response("No record has the id."));
+            let delete = operation("Delete a record, answering with it as it was.")
+                .parameter(id)
+                .response("200", ResponseBuilder::new().description("Deleted record.")
+                    .content("application/json", json(record)))
+                .response("404", error_response("No record has the id."));
+            let mut by_id = item(PathItemType::Patch, patch);
+            by_id.operations.insert(PathItemType::Delete, delete.build());
+            openapi.paths.paths.insert(format!("/admin/{}/{{id}}", table), by_id);
+        }
+    }
+}
+
+fn error_response(description: &str) -> ResponseBuilder {
+    ResponseBuilder::new()
+        .description(description)
+        .content("application/json", Content::new(Ref::from_schema_name("ApiError")))
+}
@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use axum::Extension;
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::Response;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::database::{with_names, ChannelObject, LiverObject, ScheduleFilter, ScheduledVideoObject, Table, VideoObject};
+use crate::models::{Channel, Liver, Schedule, ScheduleDay, ScheduleEntry, ScheduleHour, Video};
+
+use super::{ApiError, ErrorResponse, Locales, OutputZone, Precondition, Representation, ResponseCache};
+
+const MAX_DAYS: usize = 7;
+/// Streams started within this many hours before midnight are carried over into the day,
+/// unless they ended before it. Collectors that do not send `ended_at` leave them running.
+const CARRY_OVER_HOURS: i64 = 12;
+
+/// Where a video is listed in a day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placement {
+    /// Starts at the local hour of the day.
+    Hour(u32),
+    /// Started before the day and is still running into it.
+    Continued
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleQuery {
+    /// `YYYY-MM-DD` in the requested time zone.
+    date: Option<String>,
+    days: Option<usize>,
+    affiliation: Option<i64>
+}
+
+#[utoipa::path(
+    get,
+    path = "/schedule",
+    tag = "video",
+    params(
+        ("date" = Option<String>, Query, description = "First day as `YYYY-MM-DD` in the time zone. Defaults to today."),
+        ("days" = Option<usize>, Query, description = "Number of days from `date`, 7 for a week view. Defaults to 1."),
+        ("affiliation" = Option<i64>, Query, description = "Only videos of livers in the affiliation."),
+        ("tz" = Option<String>, Query, description = "IANA time zone to cut days in and give timestamps in, such as `Asia/Tokyo`, overriding `Time-Zone`."),
+        ("Time-Zone" = Option<String>, Header, description = "IANA time zone to cut days in and give timestamps in. Defaults to UTC."),
+        ("lang" = Option<String>, Query, description = "Comma separated language tags to pick `display_name` in, overriding `Accept-Language`."),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages of `display_name`, falling back to `ja` and `en`."),
+    ),
+    responses(
+        (status = 200, description = "Videos bucketed by the local day and hour they start at.", body = Schedule),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 400, description = "Date, number of days or time zone is malformed.", body = ApiError),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
+pub async fn get_schedule(
+    Query(query): Query<ScheduleQuery>,
+    precondition: Precondition,
+    locales: Locales,
+    zone: OutputZone,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let tz = *zone.tz();
+    let date = match query.date {
+        Some(date) => NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .map_err(|e| ApiError::new(e).report(StatusCode::BAD_REQUEST))?,
+        None => Utc::now().with_timezone(&tz).date_naive()
+    };
+    let days = query.days.unwrap_or(1);
+    if !(1..=MAX_DAYS).contains(&days) {
+        return Err(ApiError::reason(format!("days must be from 1 to {}.", MAX_DAYS)).report(StatusCode::BAD_REQUEST));
+    }
+
+    let key = format!("schedule:{}:{}:{:?};{};{}", date, days, query.affiliation, locales.key(), zone.key());
+    let filter = ScheduleFilter { affiliation_id: query.affiliation, ..Default::default() };
+    let repr = cache.get_or_fetch(Table::Videos, key, || schedule(date, days, tz, &locales, filter, &pool)).await?;
+    Ok(OutputZone::vary(Locales::vary(precondition.respond(repr))))
+}
+
+async fn schedule(
+    date: NaiveDate,
+    days: usize,
+    tz: Tz,
+    locales: &Locales,
+    filter: ScheduleFilter,
+    pool: &PgPool
+) -> Result<Representation, ErrorResponse> {
+    // Days are not always 24 hours long, so each one is cut at its own midnight.
+    let bounds = date.iter_days()
+        .take(days + 1)
+        .map(|date| start_of_day(date, &tz))
+        .collect::<Vec<_>>();
+    let (from, to) = (bounds[0] - Duration::hours(CARRY_OVER_HOURS), bounds[bounds.len() - 1]);
+
+    let videos = ScheduledVideoObject::fetch_starting_between(from, to, &filter, pool).await
+        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let (livers, channels) = related(&videos, pool).await
+        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let last_modified = videos.iter()
+        .filter_map(|video| video.video().updated_at())
+        .chain(livers.values().filter_map(LiverObject::updated_at))
+        .chain(channels.values().filter_map(ChannelObject::updated_at))
+        .max();
+
+    let entry = |video: &ScheduledVideoObject, starts_at: DateTime<Utc>| ScheduleEntry {
+        starts_at: starts_at.with_timezone(&tz).fixed_offset(),
+        video: Video::from(video.video().clone()).in_zone(&tz),
+        channel: video.video().channel_id()
+            .and_then(|id| channels.get(&String::from(id.to_owned())))
+            .map(|channel| Channel::localized(channel.clone(), locales.as_slice()).in_zone(&tz)),
+        liver: video.liver_id()
+            .and_then(|id| livers.get(&i64::from(id)))
+            .map(|liver| Liver::localized(liver.clone(), locales.as_slice()))
+    };
+
+    let days = date.iter_days()
+        .zip(bounds.windows(2))
+        .map(|(date, bound)| {
+            let (start, end) = (bound[0], bound[1]);
+            let mut hours = BTreeMap::<u32, Vec<ScheduleEntry>>::new();
+            let mut continued = Vec::new();
+            for video in &videos {
+                match place(video.video(), start, end, &tz) {
+                    Some((starts_at, Placement::Hour(hour))) => hours.entry(hour).or_default().push(entry(video, starts_at)),
+                    Some((starts_at, Placement::Continued)) => continued.push(entry(video, starts_at)),
+                    None => {}
+                }
+            }
+            let hours = hours.into_iter()
+                .map(|(hour, entries)| ScheduleHour { hour, entries })
+                .collect();
+            ScheduleDay { date, hours, continued }
+        })
+        .collect();
+
+    let schedule = Schedule { time_zone: tz.name().to_owned(), days };
+    Representation::json(&schedule, last_modified)
+}
+
+/// When `video` starts and where it is listed in the day from `start` to `end`, if it is.
+fn place(video: &VideoObject, start: DateTime<Utc>, end: DateTime<Utc>, tz: &Tz) -> Option<(DateTime<Utc>, Placement)> {
+    let started_at = video.started_at();
+    let starts_at = started_at.or_else(|| video.will_start_at())?;
+    if start <= starts_at && starts_at < end {
+        return Some((starts_at, Placement::Hour(starts_at.with_timezone(tz).hour())));
+    }
+    let running = started_at.is_some()
+        && start - Duration::hours(CARRY_OVER_HOURS) <= starts_at && starts_at < start
+        && video.ended_at().map_or(true, |ended_at| start < ended_at);
+    running.then_some((starts_at, Placement::Continued))
+}
+
+/// First instant of `date` in `tz`, later than 00:00 where daylight saving time starts at midnight.
+fn start_of_day(date: NaiveDate, tz: &Tz) -> DateTime<Utc> {
+    (0..24)
+        .filter_map(|hour| date.and_hms_opt(hour, 0, 0))
+        .find_map(|at| tz.from_local_datetime(&at).earliest())
+        .map(|at| at.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN)))
+}
+
+/// Livers and channels of the videos, with their localized names.
+async fn related(
+    videos: &[ScheduledVideoObject],
+    pool: &PgPool
+) -> Result<(HashMap<i64, LiverObject>, HashMap<String, ChannelObject>), sqlx::Error> {
+    let liver_ids = videos.iter()
+        .filter_map(|video| video.liver_id().map(i64::from))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let channel_ids = videos.iter()
+        .filter_map(|video| video.video().channel_id().map(|id| String::from(id.to_owned())))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let livers = with_names(LiverObject::fetch_from_ids(&liver_ids, pool).await?, pool).await?
+        .into_iter()
+        .map(|liver| (i64::from(liver.liver_id()), liver))
+        .collect();
+    let channels = with_names(ChannelObject::fetch_from_ids(&channel_ids, pool).await?, pool).await?
+        .into_iter()
+        .map(|channel| (String::from(channel.channel_id().to_owned()), channel))
+        .collect();
+    Ok((livers, channels))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+    use chrono_tz::{America::Sao_Paulo, Asia::Tokyo, Europe::London, Tz};
+
+    use crate::database::{InitVideoObject, VideoObject};
+    use super::{place, start_of_day, Placement};
+
+    fn utc(at: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(at).unwrap().with_timezone(&Utc)
+    }
+
+    fn day(date: &str, tz: &Tz) -> (DateTime<Utc>, DateTime<Utc>) {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        (start_of_day(date, tz), start_of_day(date.succ_opt().unwrap(), tz))
+    }
+
+    fn video(will_start_at: Option<&str>, started_at: Option<&str>, ended_at: Option<&str>) -> VideoObject {
+        InitVideoObject {
+            will_start_at: will_start_at.map(utc),
+            started_at: started_at.map(utc),
+            ended_at: ended_at.map(utc),
+            ..Default::default()
+        }.build()
+    }
+
+    #[test]
+    fn days_start_at_local_midnight() {
+        let date = NaiveDate::from_ymd_opt(2022, 1, 31).unwrap();
+        assert_eq!(start_of_day(date, &Tokyo), utc("2022-01-30T15:00:00Z"));
+        assert_eq!(start_of_day(date, &London), utc("2022-01-31T00:00:00Z"));
+    }
+
+    #[test]
+    fn days_start_after_the_gap_where_daylight_saving_time_starts_at_midnight() {
+        // Clocks in São Paulo went from 00:00 -03:00 to 01:00 -02:00 on 2018-11-04.
+        let (start, end) = day("2018-11-04", &Sao_Paulo);
+        assert_eq!(start, utc("2018-11-04T03:00:00Z"));
+        assert_eq!(Sao_Paulo.from_utc_datetime(&start.naive_utc()).to_rfc3339(), "2018-11-04T01:00:00-02:00");
+        assert_eq!(end - start, Duration::hours(23));
+    }
+
+    #[test]
+    fn videos_are_bucketed_by_the_local_hour_they_start_at() {
+        let (start, end) = day("2022-01-31", &Tokyo);
+        let upcoming = video(Some("2022-01-31T12:00:00Z"), None, None);
+        assert_eq!(place(&upcoming, start, end, &Tokyo), Some((utc("2022-01-31T12:00:00Z"), Placement::Hour(21))));
+        // Once started, a stream is listed when it started rather than when it was scheduled.
+        let started = video(Some("2022-01-30T14:00:00Z"), Some("2022-01-30T15:30:00Z"), None);
+        assert_eq!(place(&started, start, end, &Tokyo), Some((utc("2022-01-30T15:30:00Z"), Placement::Hour(0))));
+        let tomorrow = video(Some("2022-01-31T15:00:00Z"), None, None);
+        assert_eq!(place(&tomorrow, start, end, &Tokyo), None);
+    }
+
+    #[test]
+    fn only_streams_running_into_the_day_are_carried_over() {
+        let (start, end) = day("2022-01-31", &Tokyo);
+        let live = video(None, Some("2022-01-30T14:00:00Z"), None);
+        assert_eq!(place(&live, start, end, &Tokyo), Some((utc("2022-01-30T14:00:00Z"), Placement::Continued)));
+        let ended_after_midnight = video(None, Some("2022-01-30T14:00:00Z"), Some("2022-01-30T16:00:00Z"));
+        assert_eq!(place(&ended_after_midnight, start, end, &Tokyo).map(|(_, placement)| placement), Some(Placement::Continued));
+        let ended_before_midnight = video(None, Some("2022-01-30T14:00:00Z"), Some("2022-01-30T14:50:00Z"));
+        assert_eq!(place(&ended_before_midnight, start, end, &Tokyo), None);
+        let not_started = video(Some("2022-01-30T14:00:00Z"), None, None);
+        assert_eq!(place(&not_started, start, end, &Tokyo), None);
+        let too_long_ago = video(None, Some("2022-01-30T02:00:00Z"), None);
+        assert_eq!(place(&too_long_ago, start, end, &Tokyo), None);
+    }
+}
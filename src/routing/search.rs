@@ -0,0 +1,64 @@
+use axum::{Extension, Json};
+use axum::extract::Query;
+use axum::http::StatusCode;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::database::AffiliationObject;
+use crate::models::SearchResult;
+use crate::server::search::SearchEngine;
+
+use super::{ApiError, ErrorResponse};
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    q: String,
+    /// Id or name of the affiliation.
+    affiliation: Option<String>,
+    limit: Option<usize>
+}
+
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "search",
+    params(
+        ("q" = String, Query, description = "Search terms. Small typos are tolerated."),
+        ("affiliation" = Option<String>, Query, description = "Only matches within the affiliation, given by its id or a similar name."),
+        ("limit" = Option<usize>, Query, description = "Maximum hits per index, up to 100. Defaults to 20.")
+    ),
+    responses(
+        (status = 200, description = "Matched livers, channels and videos, with match counts per affiliation.", body = SearchResult),
+        (status = 400, description = "Query is empty.", body = ApiError),
+        (status = 404, description = "Affiliation is not found.", body = ApiError),
+        (status = 503, description = "Search engine is unavailable.", body = ApiError)
+    )
+)]
+pub async fn get_search(
+    Query(query): Query<SearchQuery>,
+    Extension(pool): Extension<PgPool>,
+    Extension(search): Extension<SearchEngine>
+) -> Result<Json<SearchResult>, ErrorResponse> {
+    if query.q.trim().is_empty() {
+        return Err(ApiError::reason("q must not be empty.").report(StatusCode::BAD_REQUEST));
+    }
+    let affiliation = match query.affiliation.as_deref().map(str::trim).filter(|given| !given.is_empty()) {
+        None => None,
+        Some(given) => match given.parse::<i64>() {
+            Ok(id) => Some(id),
+            Err(_) => {
+                let id = AffiliationObject::fetch_id_from_name(given, &pool).await
+                    .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?
+                    .ok_or_else(|| ApiError::reason(format!("{} is not found.", given)).report(StatusCode::NOT_FOUND))?;
+                Some(i64::from(id))
+            }
+        }
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let result = search.search(query.q.trim(), affiliation, limit, &pool).await
+        .map_err(|e| ApiError::new(e).report(StatusCode::SERVICE_UNAVAILABLE))?;
+    Ok(Json(result))
+}
@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use axum::Extension;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::response::Response;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+
+use super::OutputZone;
+use super::events::{ChangeEvent, ChangeKind, ChangePayload, EventHub};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// Connection is closed when nothing is received from the client within this duration.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(90);
+const MAX_TOPICS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Topic {
+    Liver(i64),
+    Affiliation(i64),
+    AllLive
+}
+
+impl Topic {
+    fn matches(&self, event: &ChangeEvent) -> bool {
+        match self {
+            Topic::Liver(id) => event.scope.liver_id == Some(*id),
+            Topic::Affiliation(id) => event.scope.affiliation_id == Some(*id),
+            Topic::AllLive => event.kind == ChangeKind::Live
+        }
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_id = |id: &str| id.parse::<i64>().map_err(|_| format!("{} is not a valid id.", id));
+        match s.split_once(':') {
+            _ if s == "all-live" => Ok(Topic::AllLive),
+            Some(("liver", id)) => parse_id(id).map(Topic::Liver),
+            Some(("affiliation", id)) => parse_id(id).map(Topic::Affiliation),
+            _ => Err(format!("{} is not a valid topic.", s))
+        }
+    }
+}
+
+impl std::fmt::Display for Topic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Topic::Liver(id) => write!(f, "liver:{}", id),
+            Topic::Affiliation(id) => write!(f, "affiliation:{}", id),
+            Topic::AllLive => write!(f, "all-live")
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe { topics: Vec<String> },
+    Unsubscribe { topics: Vec<String> },
+    Ping
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Subscribed { topics: Vec<String> },
+    Event { topics: Vec<String>, event: String, data: &'a ChangeEvent },
+    /// Sent when events were dropped because the client could not keep up.
+    Resync,
+    Pong,
+    Error { reason: String }
+}
+
+impl ServerMessage<'_> {
+    fn to_message(&self) -> Option<Message> {
+        serde_json::to_string(self)
+            .map(Message::Text)
+            .ok()
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "event",
+    params(
+        ("tz" = Option<String>, Query, description = "IANA time zone to give timestamps in, such as `Asia/Tokyo`, overriding `Time-Zone`."),
+        ("Time-Zone" = Option<String>, Header, description = "IANA time zone to give timestamps in. Defaults to UTC."),
+    ),
+    responses(
+        (status = 101, description = "Upgrade to a WebSocket. \
+            Send `{\"op\": \"subscribe\", \"topics\": [\"liver:{id}\", \"affiliation:{id}\", \"all-live\"]}` \
+            to receive video changes under the topics."),
+        (status = 400, description = "Unknown time zone.", body = ApiError)
+    )
+)]
+pub async fn get_socket(
+    upgrade: WebSocketUpgrade,
+    zone: OutputZone,
+    Extension(hub): Extension<EventHub>
+) -> Response {
+    upgrade.on_upgrade(move |socket| serve_socket(socket, hub, zone))
+}
+
+async fn serve_socket(mut socket: WebSocket, hub: EventHub, zone: OutputZone) {
+    let (_, mut receiver) = hub.subscribe(None);
+    let mut topics = HashSet::<Topic>::new();
+    let mut heartbeat = interval(HEARTBEAT_INTERVAL);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_seen = Instant::now();
+
+    loop {
+        let reply = tokio::select! {
+            received = socket.recv() => {
+                let message = match received {
+                    Some(Ok(message)) => message,
+                    _ => break
+                };
+                last_seen = Instant::now();
+                match message {
+                    Message::Text(text) => handle_client_message(&text, &mut topics),
+                    Message::Close(_) => break,
+                    _ => None
+                }
+            }
+            received = receiver.recv() => match received {
+                Ok(event) => event_message(&event, &topics, zone.tz()),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::debug!("websocket client lagged behind {} events", skipped);
+                    ServerMessage::Resync.to_message()
+                }
+                Err(RecvError::Closed) => break
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > CLIENT_TIMEOUT {
+                    tracing::debug!("websocket client timed out");
+                    break;
+                }
+                Some(Message::Ping(Vec::new()))
+            }
+        };
+
+        if let Some(reply) = reply {
+            if socket.send(reply).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+fn handle_client_message(text: &str, topics: &mut HashSet<Topic>) -> Option<Message> {
+    let message = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(message) => message,
+        Err(e) => return ServerMessage::Error { reason: e.to_string() }.to_message()
+    };
+    let parse = |requested: Vec<String>| requested.iter()
+        .map(|topic| topic.parse::<Topic>())
+        .collect::<Result<Vec<_>, _>>();
+
+    match message {
+        ClientMessage::Subscribe { topics: requested } => match parse(requested) {
+            Ok(requested) if topics.len() + requested.iter().filter(|topic| !topics.contains(topic)).count() > MAX_TOPICS => ServerMessage::Error {
+                reason: format!("Cannot subscribe more than {} topics.", MAX_TOPICS)
+            },
+            Ok(requested) => {
+                topics.extend(requested);
+                ServerMessage::Subscribed { topics: topics.iter().map(Topic::to_string).collect() }
+            }
+            Err(reason) => ServerMessage::Error { reason }
+        },
+        ClientMessage::Unsubscribe { topics: requested } => match parse(requested) {
+            Ok(requested) => {
+                requested.iter().for_each(|topic| { topics.remove(topic); });
+                ServerMessage::Subscribed { topics: topics.iter().map(Topic::to_string).collect() }
+            }
+            Err(reason) => ServerMessage::Error { reason }
+        },
+        ClientMessage::Ping => ServerMessage::Pong
+    }.to_message()
+}
+
+fn event_message(event: &Arc<ChangeEvent>, topics: &HashSet<Topic>, zone: &Tz) -> Option<Message> {
+    if !matches!(event.payload, ChangePayload::Video { .. }) {
+        return None;
+    }
+    let matched = topics.iter()
+        .filter(|topic| topic.matches(event))
+        .map(Topic::to_string)
+        .collect::<Vec<_>>();
+    if matched.is_empty() {
+        return None;
+    }
+    ServerMessage::Event { topics: matched, event: event.name(), data: &event.in_zone(zone) }.to_message()
+}
@@ -0,0 +1,390 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+use axum::{Extension, Json};
+use axum::extract::Query;
+use axum::http::StatusCode;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::database::{Fetch, AffiliationObject, LiverObject, ChannelObject, VideoObject};
+use crate::models::{AffiliationSuggestion, ChannelSuggestion, LiverSuggestion, Suggestions};
+use crate::server::normalize::normalize;
+
+use super::{ApiError, Changed, ErrorResponse, Locales};
+
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 20;
+
+/// How well a name matched, smaller is better.
+///
+/// Matches at the first word come first, then names matched as a whole, then shorter names.
+type Rank = (usize, bool, usize);
+
+/// Normalised names split into words, so that any word of a name can start a prefix.
+fn words(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| c.is_whitespace() || c == '・')
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Sorted keys of names, each mapped to the ids having it and the position of its first word in the name.
+#[derive(Debug)]
+struct PrefixIndex<K> {
+    keys: BTreeMap<String, HashMap<K, usize>>,
+    ids: HashMap<K, Vec<String>>
+}
+
+impl<K> Default for PrefixIndex<K> {
+    fn default() -> Self {
+        Self { keys: BTreeMap::new(), ids: HashMap::new() }
+    }
+}
+
+impl<K: Clone + Eq + Hash + Ord> PrefixIndex<K> {
+    fn insert<'a>(&mut self, id: K, names: impl IntoIterator<Item = &'a str>) {
+        self.remove(&id);
+        let mut keys = Vec::new();
+        for name in names {
+            let words = words(name);
+            for position in 0..words.len() {
+                let key = words[position..].join(" ");
+                let first = self.keys.entry(key.clone()).or_default().entry(id.clone()).or_insert(position);
+                *first = (*first).min(position);
+                keys.push(key);
+            }
+        }
+        self.ids.insert(id, keys);
+    }
+
+    fn remove(&mut self, id: &K) {
+        for key in self.ids.remove(id).into_iter().flatten() {
+            if let Some(ids) = self.keys.get_mut(&key) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.keys.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Ids with a name starting with `prefix`, best first.
+    fn lookup(&self, prefix: &str, limit: usize) -> Vec<K> {
+        if prefix.is_empty() {
+            return Vec::new();
+        }
+        let mut ranks = HashMap::<&K, Rank>::new();
+        let keys = self.keys.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(key, _)| key.starts_with(prefix));
+        for (key, ids) in keys {
+            for (id, &position) in ids {
+                let rank = (position, key != prefix, key.len());
+                let best = ranks.entry(id).or_insert(rank);
+                *best = (*best).min(rank);
+            }
+        }
+        let mut ranked = ranks.into_iter().collect::<Vec<_>>();
+        ranked.sort_by(|(a_id, a_rank), (b_id, b_rank)| a_rank.cmp(b_rank).then(a_id.cmp(b_id)));
+        ranked.into_iter()
+            .take(limit)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+/// Names and prefixes held by [SuggestIndex].
+#[derive(Debug, Default)]
+pub struct SuggestEntries {
+    affiliations: HashMap<i64, AffiliationObject>,
+    livers: HashMap<i64, LiverObject>,
+    channels: HashMap<String, ChannelObject>,
+    affiliation_prefixes: PrefixIndex<i64>,
+    liver_prefixes: PrefixIndex<i64>,
+    channel_prefixes: PrefixIndex<String>
+}
+
+impl SuggestEntries {
+    fn upsert_affiliation(&mut self, affiliation: &AffiliationObject) {
+        let id = i64::from(affiliation.affiliation_id());
+        let names = std::iter::once(affiliation.name()).chain(affiliation.names().iter().map(|(_, name)| name));
+        self.affiliation_prefixes.insert(id, names);
+        self.affiliations.insert(id, affiliation.clone());
+    }
+
+    fn remove_affiliation(&mut self, affiliation: &AffiliationObject) {
+        let id = i64::from(affiliation.affiliation_id());
+        self.affiliation_prefixes.remove(&id);
+        self.affiliations.remove(&id);
+    }
+
+    fn upsert_liver(&mut self, liver: &LiverObject) {
+        let id = i64::from(liver.liver_id());
+        self.liver_prefixes.insert(id, liver_names(liver));
+        self.livers.insert(id, liver.clone());
+        self.rekey_channels_of(id);
+    }
+
+    fn remove_liver(&mut self, liver: &LiverObject) {
+        let id = i64::from(liver.liver_id());
+        self.liver_prefixes.remove(&id);
+        self.livers.remove(&id);
+        self.rekey_channels_of(id);
+    }
+
+    fn upsert_channel(&mut self, channel: &ChannelObject) {
+        let channel_id = String::from(channel.channel_id().to_owned());
+        let liver = channel.liver_id().and_then(|id| self.livers.get(&i64::from(id)));
+        let names = channel.names().iter()
+            .map(|(_, name)| name)
+            .chain(liver.into_iter().flat_map(liver_names));
+        self.channel_prefixes.insert(channel_id.clone(), names);
+        self.channels.insert(channel_id, channel.clone());
+    }
+
+    fn remove_channel(&mut self, channel_id: &String) {
+        self.channel_prefixes.remove(channel_id);
+        self.channels.remove(channel_id);
+    }
+
+    /// Channels are also found by the names of their liver.
+    fn rekey_channels_of(&mut self, liver_id: i64) {
+        let channels = self.channels.values()
+            .filter(|channel| channel.liver_id().map(i64::from) == Some(liver_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for channel in &channels {
+            self.upsert_channel(channel);
+        }
+    }
+}
+
+fn liver_names(liver: &LiverObject) -> impl Iterator<Item = &str> {
+    [liver.name(), liver.localized_name()].into_iter()
+        .chain(liver.names().iter().map(|(_, name)| name))
+}
+
+/// Reflect a change committed by Salmon into the suggestions.
+pub trait Suggest: Sized {
+    fn suggest(changed: &Changed<Self>, entries: &mut SuggestEntries);
+}
+
+impl Suggest for AffiliationObject {
+    fn suggest(changed: &Changed<Self>, entries: &mut SuggestEntries) {
+        match changed {
+            Changed::Inserted(new) | Changed::Updated(_, new) => entries.upsert_affiliation(new),
+            Changed::Deleted(old) => entries.remove_affiliation(old)
+        }
+    }
+}
+
+impl Suggest for LiverObject {
+    fn suggest(changed: &Changed<Self>, entries: &mut SuggestEntries) {
+        match changed {
+            Changed::Inserted(new) | Changed::Updated(_, new) => entries.upsert_liver(new),
+            Changed::Deleted(old) => entries.remove_liver(old)
+        }
+    }
+}
+
+impl Suggest for ChannelObject {
+    fn suggest(changed: &Changed<Self>, entries: &mut SuggestEntries) {
+        match changed {
+            Changed::Inserted(new) | Changed::Updated(_, new) => entries.upsert_channel(new),
+            Changed::Deleted(old) => entries.remove_channel(&old.channel_id().to_owned().into())
+        }
+    }
+}
+
+impl Suggest for VideoObject {
+    /// Videos are not suggested.
+    fn suggest(_: &Changed<Self>, _: &mut SuggestEntries) {}
+}
+
+/// In-memory prefix index of affiliation, liver and channel names, for autocompletion.
+///
+/// Loaded from the database at startup and kept up to date by Salmon through [SuggestIndex::apply],
+/// so that suggesting never waits for the database.
+#[derive(Debug, Clone, Default)]
+pub struct SuggestIndex {
+    entries: Arc<RwLock<SuggestEntries>>
+}
+
+impl SuggestIndex {
+    /// Replace every entry with the rows of the database.
+    pub async fn load(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let affiliations = AffiliationObject::fetch_all(pool).await?;
+        let livers = <LiverObject as Fetch>::fetch_all(pool).await?;
+        let channels = ChannelObject::fetch_all(pool).await?;
+
+        let mut entries = SuggestEntries::default();
+        affiliations.iter().for_each(|affiliation| entries.upsert_affiliation(affiliation));
+        livers.iter().for_each(|liver| entries.upsert_liver(liver));
+        channels.iter().for_each(|channel| entries.upsert_channel(channel));
+        tracing::info!("{:<10} {} affiliations, {} livers, {} channels",
+            yansi::Paint::green("suggest"), affiliations.len(), livers.len(), channels.len());
+
+        *self.entries.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = entries;
+        Ok(())
+    }
+
+    pub fn apply<T: Suggest>(&self, changes: &[Changed<T>]) {
+        let mut entries = self.entries.write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for changed in changes {
+            T::suggest(changed, &mut entries);
+        }
+    }
+
+    /// Names starting with `query` in any language, at most `limit` of each type,
+    /// given in the first of `locales` that has one.
+    pub fn suggest(&self, query: &str, locales: &Locales, limit: usize) -> Suggestions {
+        let prefix = words(query).join(" ");
+        let locales = locales.as_slice();
+        let entries = self.entries.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let affiliations = entries.affiliation_prefixes.lookup(&prefix, limit).into_iter()
+            .filter_map(|id| entries.affiliations.get(&id))
+            .map(|affiliation| AffiliationSuggestion {
+                affiliation_id: affiliation.affiliation_id().into(),
+                name: affiliation.display_name(locales).to_owned()
+            })
+            .collect();
+        let livers = entries.liver_prefixes.lookup(&prefix, limit).into_iter()
+            .filter_map(|id| entries.livers.get(&id))
+            .map(|liver| LiverSuggestion {
+                liver_id: liver.liver_id().into(),
+                affiliation_id: liver.affiliation_id().map(Into::into),
+                name: liver.display_name(locales).to_owned()
+            })
+            .collect();
+        let channels = entries.channel_prefixes.lookup(&prefix, limit).into_iter()
+            .filter_map(|id| entries.channels.get(&id))
+            .map(|channel| {
+                let liver = channel.liver_id().and_then(|id| entries.livers.get(&i64::from(id)));
+                ChannelSuggestion {
+                    channel_id: channel.channel_id().to_owned().into(),
+                    liver_id: channel.liver_id().map(Into::into),
+                    name: channel.display_name(locales)
+                        .or_else(|| liver.map(|liver| liver.display_name(locales)))
+                        .map(str::to_owned)
+                }
+            })
+            .collect();
+
+        Suggestions { query: query.to_owned(), affiliations, livers, channels }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestQuery {
+    q: String,
+    limit: Option<usize>
+}
+
+#[utoipa::path(
+    get,
+    path = "/suggest",
+    tag = "search",
+    params(
+        ("q" = String, Query, description = "Beginning of a name, or of any word in it, in kana, romaji or any width."),
+        ("lang" = Option<String>, Query, description = "Comma separated language tags to give names in, overriding `Accept-Language`."),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred languages of names, falling back to `ja` and `en`."),
+        ("limit" = Option<usize>, Query, description = "Maximum suggestions per type, up to 20. Defaults to 5.")
+    ),
+    responses(
+        (status = 200, description = "Affiliations, livers and channels with a name starting with the query.", body = Suggestions),
+        (status = 400, description = "Query is empty.", body = ApiError)
+    )
+)]
+pub async fn get_suggest(
+    Query(query): Query<SuggestQuery>,
+    locales: Locales,
+    Extension(suggest): Extension<SuggestIndex>
+) -> Result<Json<Suggestions>, ErrorResponse> {
+    if query.q.trim().is_empty() {
+        return Err(ApiError::reason("q must not be empty.").report(StatusCode::BAD_REQUEST));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    Ok(Json(suggest.suggest(query.q.trim(), &locales, limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::{ChannelId, InitChannelObject, LiverId, LiverObject};
+    use super::{Changed, Locales, PrefixIndex, SuggestIndex};
+
+    fn index(names: &[(i64, &str)]) -> PrefixIndex<i64> {
+        let mut index = PrefixIndex::default();
+        for (id, name) in names {
+            index.insert(*id, [*name]);
+        }
+        index
+    }
+
+    #[test]
+    fn names_starting_with_the_prefix_come_first_then_whole_then_shorter() {
+        let index = index(&[(1, "usada pekora"), (2, "pekorin"), (3, "pekora"), (4, "peko"), (5, "marine")]);
+        assert_eq!(index.lookup("peko", 10), [4, 3, 2, 1]);
+        assert_eq!(index.lookup("peko", 2), [4, 3]);
+        assert_eq!(index.lookup("", 10), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn a_prefix_matches_later_words() {
+        let index = index(&[(1, "houshou marine"), (2, "usada pekora")]);
+        assert_eq!(index.lookup("mari", 10), [1]);
+        assert_eq!(index.lookup("usada peko", 10), [2]);
+        assert_eq!(index.lookup("pekora usada", 10), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn the_best_of_several_names_ranks_an_id() {
+        let mut index = PrefixIndex::default();
+        index.insert(1, ["usada pekora", "pekora"]);
+        index.insert(2, ["pekorama"]);
+        assert_eq!(index.lookup("pekora", 10), [1, 2]);
+    }
+
+    #[test]
+    fn removed_and_renamed_ids_leave_no_keys() {
+        let mut index = index(&[(1, "usada pekora"), (2, "pekora")]);
+        index.remove(&1);
+        assert_eq!(index.lookup("usada", 10), Vec::<i64>::new());
+        assert!(!index.ids.contains_key(&1));
+        assert!(index.keys.values().all(|ids| !ids.contains_key(&1)));
+
+        index.insert(2, ["marine"]);
+        assert_eq!(index.lookup("peko", 10), Vec::<i64>::new());
+        assert_eq!(index.keys.keys().collect::<Vec<_>>(), ["marine"]);
+    }
+
+    #[test]
+    fn channels_are_rekeyed_when_their_liver_is_renamed() {
+        let suggest = SuggestIndex::default();
+        let liver = LiverObject::new(1, None, "Usada Pekora", "兎田ぺこら");
+        let channel = InitChannelObject {
+            channel_id: ChannelId::new("UC1DCedRgGHBdm81E1llLhOQ"),
+            liver_id: Some(LiverId::new(1)),
+            ..Default::default()
+        }.build();
+        suggest.apply(&[Changed::Inserted(liver.clone())]);
+        suggest.apply(&[Changed::Inserted(channel)]);
+        let locales = Locales::default();
+        let channel_names = |query: &str| suggest.suggest(query, &locales, 5).channels.into_iter()
+            .map(|channel| channel.name)
+            .collect::<Vec<_>>();
+        assert_eq!(channel_names("peko"), [Some("Usada Pekora".to_owned())]);
+
+        let renamed = LiverObject::new(1, None, "Houshou Marine", "宝鐘マリン");
+        suggest.apply(&[Changed::Updated(liver, renamed.clone())]);
+        assert!(channel_names("peko").is_empty());
+        assert_eq!(channel_names("marine"), [Some("Houshou Marine".to_owned())]);
+
+        suggest.apply(&[Changed::Deleted(renamed)]);
+        assert!(channel_names("marine").is_empty());
+    }
+}
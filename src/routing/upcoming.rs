@@ -1,19 +1,43 @@
-use axum::{Extension, Json};
+use axum::Extension;
 use axum::http::StatusCode;
+use axum::response::Response;
 use sqlx::PgPool;
 use crate::models::Video;
-use crate::database::{Fetch, VideoObject};
+use crate::database::{Fetch, Table, VideoObject};
 
 use super::ApiError;
 use super::ErrorResponse;
+use super::{OutputZone, Precondition, Representation, ResponseCache};
 
+#[utoipa::path(
+    get,
+    path = "/upcomings",
+    tag = "video",
+    params(
+        ("tz" = Option<String>, Query, description = "IANA time zone to give timestamps in, such as `Asia/Tokyo`, overriding `Time-Zone`."),
+        ("Time-Zone" = Option<String>, Header, description = "IANA time zone to give timestamps in. Defaults to UTC."),
+    ),
+    responses(
+        (status = 200, description = "All videos.", body = [Video]),
+        (status = 304, description = "Not modified since the validator sent by the client."),
+        (status = 400, description = "Unknown time zone.", body = ApiError),
+        (status = 500, description = "Database error.", body = ApiError)
+    )
+)]
 pub async fn get_upcomings(
-    Extension(pool): Extension<PgPool>
-) -> Result<Json<Vec<Video>>, ErrorResponse> {
-    let live_all = VideoObject::fetch_all(&pool).await
-        .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?
-        .into_iter()
-        .map(Video::from)
-        .collect::<Vec<_>>();
-    Ok(Json(live_all))
+    precondition: Precondition,
+    zone: OutputZone,
+    Extension(pool): Extension<PgPool>,
+    Extension(cache): Extension<ResponseCache>
+) -> Result<Response, ErrorResponse> {
+    let repr = cache.get_or_fetch(Table::Videos, format!("all;{}", zone.key()), || async {
+        let live_all = VideoObject::fetch_all(&pool).await
+            .map_err(|e| ApiError::new(e).report(StatusCode::INTERNAL_SERVER_ERROR))?;
+        let last_modified = live_all.iter().filter_map(VideoObject::updated_at).max();
+        let live_all = live_all.into_iter()
+            .map(|live| Video::from(live).in_zone(zone.tz()))
+            .collect::<Vec<_>>();
+        Representation::json(&live_all, last_modified)
+    }).await?;
+    Ok(OutputZone::vary(precondition.respond(repr)))
 }
\ No newline at end of file
@@ -0,0 +1,152 @@
+use axum::{Extension, Json};
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::PgPool;
+
+use crate::database::postgres_database;
+use crate::models::{ApiInfo, ApiVersionInfo, BuildInfo, MigrationInfo};
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Versions of the REST api served side by side.
+///
+/// A new version gets its own variant and prefix, and is mounted next to the older ones,
+/// so that models can change without breaking clients of an older version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiVersion {
+    /// Paths without a prefix, served before `/v1` existed. Same as `v1`.
+    Unversioned,
+    V1
+}
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::Unversioned, ApiVersion::V1];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApiVersion::Unversioned => "unversioned",
+            ApiVersion::V1 => "v1"
+        }
+    }
+
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            ApiVersion::Unversioned => "",
+            ApiVersion::V1 => "/v1"
+        }
+    }
+
+    /// From `API_UNVERSIONED_DEPRECATED_AT` for unversioned paths, which are not deprecated without it.
+    pub fn deprecated_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            ApiVersion::Unversioned => configured_at("API_UNVERSIONED_DEPRECATED_AT").ok().flatten(),
+            ApiVersion::V1 => None
+        }
+    }
+
+    /// From `API_UNVERSIONED_SUNSET_AT` for unversioned paths, sent only along with the deprecation.
+    pub fn sunset_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            ApiVersion::Unversioned => configured_at("API_UNVERSIONED_SUNSET_AT").ok().flatten(),
+            ApiVersion::V1 => None
+        }
+    }
+
+    /// Fail on malformed deprecation or sunset times, which would otherwise be ignored.
+    pub fn check_env() -> Result<(), String> {
+        configured_at("API_UNVERSIONED_DEPRECATED_AT")?;
+        configured_at("API_UNVERSIONED_SUNSET_AT")?;
+        Ok(())
+    }
+
+    /// Version clients of this one should move to.
+    pub fn successor(&self) -> Option<ApiVersion> {
+        match self {
+            ApiVersion::Unversioned => Some(ApiVersion::V1),
+            ApiVersion::V1 => None
+        }
+    }
+}
+
+fn configured_at(key: &str) -> Result<Option<DateTime<Utc>>, String> {
+    match dotenv::var(key) {
+        Ok(at) => DateTime::parse_from_rfc3339(&at)
+            .map(|at| Some(at.with_timezone(&Utc)))
+            .map_err(|_| format!("{} {} is not a time like 2027-04-18T00:00:00Z.", key, at)),
+        Err(_) => Ok(None)
+    }
+}
+
+impl From<ApiVersion> for ApiVersionInfo {
+    fn from(version: ApiVersion) -> Self {
+        Self {
+            name: version.name().to_owned(),
+            prefix: version.prefix().to_owned(),
+            deprecated_at: version.deprecated_at(),
+            sunset_at: version.sunset_at()
+        }
+    }
+}
+
+/// Add `Deprecation` (RFC 9745), `Sunset` (RFC 8594) and a link to the successor to responses of a deprecated version.
+pub async fn deprecation<B>(State(version): State<ApiVersion>, request: Request<B>, next: Next<B>) -> Response {
+    let successor = version.successor()
+        .map(|successor| format!("<{}{}>; rel=\"successor-version\"", successor.prefix(), request.uri().path()));
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    if let Some(Ok(deprecation)) = version.deprecated_at()
+        .map(|at| HeaderValue::from_str(&format!("@{}", at.timestamp()))) {
+        headers.insert("deprecation", deprecation);
+    }
+    if let Some(Ok(sunset)) = version.sunset_at()
+        .map(|at| HeaderValue::from_str(&at.format(HTTP_DATE_FORMAT).to_string())) {
+        headers.insert("sunset", sunset);
+    }
+    if let Some(Ok(link)) = successor.map(|link| HeaderValue::from_str(&link)) {
+        headers.append(header::LINK, link);
+    }
+    response
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "meta",
+    responses(
+        (status = 200, description = "Api versions, and the build and database migration serving them.", body = ApiInfo)
+    )
+)]
+pub async fn version(
+    Extension(version): Extension<ApiVersion>,
+    Extension(pool): Extension<PgPool>
+) -> Json<ApiInfo> {
+    let migration = postgres_database::latest_migration(&pool).await
+        .map_err(|e| tracing::warn!("Failed to read migrations: {:?}", e))
+        .ok()
+        .flatten()
+        .map(|migration| MigrationInfo {
+            version: migration.version,
+            description: migration.description,
+            installed_on: migration.installed_on,
+            applied: migration.applied
+        });
+    let build = BuildInfo {
+        git_commit: Some(env!("MATATABI_GIT_COMMIT").to_owned()).filter(|commit| !commit.is_empty()),
+        git_dirty: env!("MATATABI_GIT_DIRTY") == "true",
+        rustc: env!("MATATABI_RUSTC_VERSION").to_owned(),
+        built_at: env!("MATATABI_BUILT_AT").parse().ok()
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+    };
+    Json(ApiInfo {
+        api_name: env!("CARGO_PKG_NAME").to_owned(),
+        api_version: version.name().to_owned(),
+        versions: ApiVersion::ALL.into_iter().map(ApiVersionInfo::from).collect(),
+        package_version: env!("CARGO_PKG_VERSION").to_owned(),
+        build,
+        migration,
+        repository: "https://github.com/ReiRokusanami0010/matatabi".to_owned()
+    })
+}
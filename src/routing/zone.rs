@@ -0,0 +1,66 @@
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::http::request::Parts;
+use axum::response::Response;
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use super::{ApiError, ErrorResponse};
+
+const TIME_ZONE: &str = "time-zone";
+
+/// IANA time zone to render timestamps of a response in, such as `Asia/Tokyo`.
+///
+/// `?tz=` takes precedence over the `Time-Zone` header, and timestamps stay in UTC without either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputZone(Tz);
+
+#[derive(Debug, Deserialize)]
+struct TzQuery {
+    tz: Option<String>
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for OutputZone {
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let tz = Query::<TzQuery>::try_from_uri(&parts.uri)
+            .ok()
+            .and_then(|Query(query)| query.tz);
+        let header = parts.headers.get(TIME_ZONE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        match tz.or(header).filter(|name| !name.trim().is_empty()) {
+            Some(name) => name.trim().parse::<Tz>()
+                .map(Self)
+                .map_err(|e| ApiError::reason(e).report(StatusCode::BAD_REQUEST)),
+            None => Ok(Self::default())
+        }
+    }
+}
+
+impl Default for OutputZone {
+    fn default() -> Self {
+        Self(Tz::UTC)
+    }
+}
+
+impl OutputZone {
+    pub fn tz(&self) -> &Tz {
+        &self.0
+    }
+
+    /// Identifies the zone in cache keys.
+    pub fn key(&self) -> &'static str {
+        self.0.name()
+    }
+
+    /// Mark a response whose timestamps depend on `Time-Zone`.
+    pub fn vary(mut response: Response) -> Response {
+        response.headers_mut().append(header::VARY, HeaderValue::from_static("Time-Zone"));
+        response
+    }
+}
+
@@ -1,30 +1,177 @@
 use std::net::SocketAddr;
 use axum::Router;
-use axum::routing::get;
+use axum::middleware;
+use axum::routing::{get, patch, post, MethodRouter};
 use sqlx::{Pool, Postgres};
+use crate::database::{AffiliationObject, ChannelObject, LiverObject, VideoObject};
 use crate::routing;
+use crate::routing::{ApiVersion, Console, EventHub, ResponseCache, Session, SuggestIndex};
+use crate::server::cage::CageService;
+use crate::server::search::SearchEngine;
+use crate::server::layer::{ApiKeyLayer, Authenticator, CageLayer};
+use crate::server::tls::{self, TlsFiles};
 
-pub async fn run_webapi_server(connection_instance: Pool<Postgres>) {
-    let app = Router::new()
-        .route("/", get(routing::version))
-        .route("/affiliations", get(routing::get_affiliations))
-        .route("/affiliations/:id", get(routing::get_affiliation_from_id))
-        .route("/livers", get(routing::get_livers))
-        .route("/livers/filtered", get(routing::get_livers_filtered))
-        .route("/channels", get(routing::get_channels))
-        .route("/upcomings", get(routing::get_upcomings))
-        .layer(axum::Extension(connection_instance));
-
-    let bind_address = SocketAddr::from(([127, 0, 0, 1], 4500));
-    tracing::debug!("listening on {}", bind_address);
-    axum::Server::bind(&bind_address)
-        .serve(app.into_make_service())
-        .with_graceful_shutdown(exit())
-        .await
-        .unwrap_or_else(|_| panic!("Cannot startup webapi server!"))
-}
-
-async fn exit() {
+pub async fn run_webapi_server(connection_instance: Pool<Postgres>, cache: ResponseCache, events: EventHub, search: SearchEngine, suggest: SuggestIndex, authenticator: Authenticator) {
+    ApiVersion::check_env()
+        .unwrap_or_else(|reason| panic!("{}", reason));
+    let schema = routing::graphql_schema(connection_instance.clone());
+    let tls = TlsFiles::from_env()
+        .unwrap_or_else(|reason| panic!("{}", reason));
+    let session = Session::new(CageService::new(connection_instance.clone(), authenticator.clone()), tls.is_some());
+
+    let app = ApiVersion::ALL.into_iter()
+        .fold(Router::new(), |app, version| match version.prefix() {
+            "" => app.merge(routes(version)),
+            prefix => app.nest(prefix, routes(version))
+        })
+        .nest("/admin", admin(authenticator.clone()))
+        .nest(routing::CONSOLE_ROOT, console(authenticator, session));
+
+    let app = app
+        .layer(ApiKeyLayer::from_env(connection_instance.clone()))
+        .layer(axum::Extension(connection_instance))
+        .layer(axum::Extension(cache))
+        .layer(axum::Extension(events))
+        .layer(axum::Extension(schema))
+        .layer(axum::Extension(search))
+        .layer(axum::Extension(suggest));
+
+    let bind_address = bind_address()
+        .unwrap_or_else(|reason| panic!("{}", reason));
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls {
+        Some(files) => {
+            let config = files.load()
+                .await
+                .unwrap_or_else(|e| panic!("Cannot load tls certificate: {}", e));
+            files.watch(config.clone());
+            let redirect = tls::redirect_address(bind_address)
+                .unwrap_or_else(|reason| panic!("{}", reason));
+            if let Some(redirect) = redirect {
+                tokio::spawn(tls::redirect_to_https(redirect, bind_address.port()));
+            }
+            let handle = axum_server::Handle::new();
+            let shutdown = handle.clone();
+            tokio::spawn(async move {
+                exit().await;
+                shutdown.graceful_shutdown(None);
+            });
+            tracing::debug!("listening on {} with tls", bind_address);
+            axum_server::bind_rustls(bind_address, config)
+                .handle(handle)
+                .serve(service)
+                .await
+                .unwrap_or_else(|_| panic!("Cannot startup webapi server!"))
+        }
+        None => {
+            tracing::debug!("listening on {}", bind_address);
+            axum::Server::bind(&bind_address)
+                .serve(service)
+                .with_graceful_shutdown(exit())
+                .await
+                .unwrap_or_else(|_| panic!("Cannot startup webapi server!"))
+        }
+    }
+}
+
+/// Address of the api from `WEBAPI_BIND`, only reachable from the same host by default.
+fn bind_address() -> Result<SocketAddr, String> {
+    match dotenv::var("WEBAPI_BIND") {
+        Ok(address) => address.parse()
+            .map_err(|_| format!("WEBAPI_BIND {} is not an address like 0.0.0.0:4500.", address)),
+        Err(_) => Ok(SocketAddr::from(([127, 0, 0, 1], 4500)))
+    }
+}
+
+/// Paths and handlers of every api version, each path documented in [routing::ApiDoc].
+fn api_routes() -> Vec<(&'static str, MethodRouter)> {
+    vec![
+        ("/", get(routing::version)),
+        ("/affiliations", get(routing::get_affiliations)),
+        ("/affiliations/:id", get(routing::get_affiliation_from_id)),
+        ("/livers", get(routing::get_livers)),
+        ("/livers/filtered", get(routing::get_livers_filtered)),
+        ("/channels", get(routing::get_channels)),
+        ("/upcomings", get(routing::get_upcomings)),
+        ("/schedule", get(routing::get_schedule)),
+        ("/upcomings.ics", get(routing::get_upcomings_calendar)),
+        ("/affiliations/:id/upcomings.ics", get(routing::get_affiliation_calendar)),
+        ("/livers/:id/upcomings.ics", get(routing::get_liver_calendar)),
+        ("/feeds/:feed", get(routing::get_feed)),
+        ("/events", get(routing::get_events)),
+        ("/ws", get(routing::get_socket)),
+        ("/search", get(routing::get_search)),
+        ("/suggest", get(routing::get_suggest)),
+        ("/graphql", get(routing::get_graphiql).post(routing::post_graphql)),
+        ("/openapi.json", get(routing::openapi))
+    ]
+}
+
+/// Paths and handlers under `/admin`, documented by [routing::AdminPaths].
+fn admin_routes() -> Vec<(&'static str, MethodRouter)> {
+    vec![
+        ("/account", get(routing::get_admin_account)),
+        ("/affiliations", post(routing::post_record::<AffiliationObject>)),
+        ("/affiliations/:id", patch(routing::patch_record::<AffiliationObject>).delete(routing::delete_record::<AffiliationObject>)),
+        ("/livers", post(routing::post_record::<LiverObject>)),
+        ("/livers/:id", patch(routing::patch_record::<LiverObject>).delete(routing::delete_record::<LiverObject>)),
+        ("/channels", post(routing::post_record::<ChannelObject>)),
+        ("/channels/:id", patch(routing::patch_record::<ChannelObject>).delete(routing::delete_record::<ChannelObject>)),
+        ("/videos", post(routing::post_record::<VideoObject>)),
+        ("/videos/:id", patch(routing::patch_record::<VideoObject>).delete(routing::delete_record::<VideoObject>))
+    ]
+}
+
+/// Routes of `version`, which carry its deprecation headers once it is deprecated.
+fn routes(version: ApiVersion) -> Router {
+    let router = api_routes().into_iter()
+        .fold(Router::new(), |router, (path, route)| router.route(path, route));
+
+    #[cfg(feature = "redoc")]
+    let router = {
+        use utoipa::OpenApi;
+        use utoipa_redoc::Servable;
+        router.merge(utoipa_redoc::Redoc::with_url("/redoc", routing::ApiDoc::openapi()))
+    };
+
+    let router = router.layer(axum::Extension(version));
+    match version.deprecated_at() {
+        Some(_) => router.layer(middleware::from_fn_with_state(version, routing::deprecation)),
+        None => router
+    }
+}
+
+/// Routes that need a Cage token of an active account.
+fn admin(authenticator: Authenticator) -> Router {
+    admin_routes().into_iter()
+        .fold(Router::new(), |router, (path, route)| router.route(path, route))
+        .layer(CageLayer::new(authenticator))
+}
+
+/// Server-rendered console for curators, signed in with a session cookie instead of a bearer token.
+fn console(authenticator: Authenticator, session: Session) -> Router {
+    let router = Router::new()
+        .route("/", get(routing::get_console));
+    let router = console_table::<AffiliationObject>(router);
+    let router = console_table::<LiverObject>(router);
+    let router = console_table::<ChannelObject>(router);
+    let router = console_table::<VideoObject>(router);
+    router
+        .route_layer(middleware::from_fn_with_state(authenticator, routing::console_session))
+        .route("/login", get(routing::get_console_login).post(routing::post_console_login))
+        .route("/logout", post(routing::post_console_logout))
+        .layer(axum::Extension(session))
+}
+
+fn console_table<T: Console>(router: Router) -> Router {
+    let path = format!("/{}", T::TABLE.as_str());
+    router
+        .route(&path, get(routing::get_console_list::<T>))
+        .route(&format!("{}/:id", path), get(routing::get_console_edit::<T>).post(routing::post_console_edit::<T>))
+        .route(&format!("{}/:id/delete", path), get(routing::get_console_delete::<T>).post(routing::post_console_delete::<T>))
+}
+
+pub(super) async fn exit() {
     let user_interrupt = async {
         tokio::signal::ctrl_c()
             .await
@@ -36,4 +183,33 @@ async fn exit() {
     }
 
     tracing::info!("interrupt signal received. shutdown.")
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use utoipa::OpenApi;
+
+    use crate::routing::ApiDoc;
+    use super::{admin_routes, api_routes};
+
+    /// Path as OpenAPI writes it, `{id}` for `:id`.
+    fn documented(path: &str) -> String {
+        path.split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("{{{}}}", name),
+                None => segment.to_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let routed = api_routes().into_iter()
+            .map(|(path, _)| documented(path))
+            .chain(admin_routes().into_iter().map(|(path, _)| documented(&format!("/admin{}", path))))
+            .collect::<BTreeSet<_>>();
+        let paths = ApiDoc::openapi().paths.paths.into_keys().collect::<BTreeSet<_>>();
+        assert_eq!(routed, paths);
+    }
+}
@@ -0,0 +1,680 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::errors::ErrorKind;
+use serde::{Deserialize, Serialize};
+use sqlx::Postgres;
+use tokio::sync::OnceCell;
+use tonic::{Request, Response, Status};
+use proto::cage_api_server::CageApi;
+use proto::{Account, AccountConfig, PasswordChange, StatusChange, Token, Void};
+
+use crate::database::{
+    AccountObject, AccountRole, AccountStatus,
+    RefreshTokenObject, RevokedTokenObject, REFRESH_TOKEN_PREFIX
+};
+use crate::server::env_or;
+use crate::server::layer::{limited_by, Authenticator, Buckets, Principal, Quota, Rejection};
+
+pub use proto::cage_api_server::CageApiServer;
+
+#[allow(clippy::all, rustdoc::all)]
+mod proto { tonic::include_proto!("cage"); }
+
+const ISSUER: &str = "matatabi";
+const MAX_USER_NAME_LENGTH: usize = 64;
+const MIN_PASSWORD_LENGTH: usize = 8;
+const WRONG_CREDENTIALS: &str = "user_name or user_pass is wrong.";
+/// Addresses trying passwords and refresh tokens, kept apart from those of the web api.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Hash checked for unknown users, so that they take as long to reject as a wrong password.
+static UNKNOWN_USER_HASH: OnceCell<String> = OnceCell::const_new();
+
+/// Claims of the access tokens issued by Cage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// Id of the account.
+    pub sub: String,
+    pub name: String,
+    pub iss: String,
+    pub iat: i64,
+    pub exp: i64,
+    /// Id of the token, listed in `revoked_tokens` once revoked.
+    pub jti: String,
+    /// `token_version` of the account when issued.
+    pub ver: i32
+}
+
+impl Claims {
+    pub fn account_id(&self) -> Option<i64> {
+        self.sub.parse().ok()
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.exp, 0).single().unwrap_or_else(Utc::now)
+    }
+}
+
+/// Signs and checks HS256 access tokens with `CAGE_JWT_SECRET`.
+///
+/// Without a secret a random one is used, and tokens stop being valid when the server restarts.
+/// Access tokens are valid for `CAGE_ACCESS_TOKEN_TTL_SECONDS` and refresh tokens for `CAGE_REFRESH_TOKEN_TTL_SECONDS`.
+#[derive(Clone)]
+pub struct TokenKeys {
+    encoding: Arc<EncodingKey>,
+    decoding: Arc<DecodingKey>,
+    access_ttl_seconds: i64,
+    refresh_ttl_seconds: i64
+}
+
+impl std::fmt::Debug for TokenKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenKeys")
+            .field("access_ttl_seconds", &self.access_ttl_seconds)
+            .field("refresh_ttl_seconds", &self.refresh_ttl_seconds)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenKeys {
+    pub fn from_env() -> Self {
+        let secret = match dotenv::var("CAGE_JWT_SECRET").ok().filter(|secret| !secret.is_empty()) {
+            Some(secret) => secret.into_bytes(),
+            None => {
+                tracing::warn!("CAGE_JWT_SECRET is not set, tokens are signed with a random secret.");
+                let mut random = vec![0u8; 32];
+                openssl::rand::rand_bytes(&mut random)
+                    .expect("Failed to generate a secret for Cage tokens.");
+                random
+            }
+        };
+        Self {
+            encoding: Arc::new(EncodingKey::from_secret(&secret)),
+            decoding: Arc::new(DecodingKey::from_secret(&secret)),
+            access_ttl_seconds: env_or("CAGE_ACCESS_TOKEN_TTL_SECONDS", 15 * 60),
+            refresh_ttl_seconds: env_or("CAGE_REFRESH_TOKEN_TTL_SECONDS", 30 * 24 * 60 * 60)
+        }
+    }
+
+    pub fn issue(&self, account: &AccountObject) -> Result<String, jsonwebtoken::errors::Error> {
+        let now = Utc::now().timestamp();
+        let mut jti = [0u8; 16];
+        openssl::rand::rand_bytes(&mut jti)
+            .expect("Failed to generate an id of a Cage token.");
+        let claims = Claims {
+            sub: account.account_id().to_string(),
+            name: account.user_name().to_owned(),
+            iss: ISSUER.to_owned(),
+            iat: now,
+            exp: now + self.access_ttl_seconds,
+            jti: jti.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            ver: account.token_version()
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding)
+    }
+
+    /// Claims of a token signed by these keys, which is neither expired nor issued by someone else.
+    ///
+    /// Whether it was revoked is checked by [Authenticator::verify].
+    pub fn verify(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[ISSUER]);
+        jsonwebtoken::decode::<Claims>(token, &self.decoding, &validation)
+            .map(|data| data.claims)
+    }
+
+    pub fn access_ttl_seconds(&self) -> i64 {
+        self.access_ttl_seconds
+    }
+
+    pub fn refresh_expires_at(&self) -> DateTime<Utc> {
+        Utc::now() + Duration::seconds(self.refresh_ttl_seconds)
+    }
+}
+
+/// Accounts and their tokens.
+///
+/// `login`, `refresh` and `changePassword` are limited per ip to `CAGE_RATE_LIMIT_PER_MINUTE` and `CAGE_RATE_LIMIT_BURST`,
+/// as the gRPC listener is not behind the rate limit of the web api.
+#[derive(Debug, Clone)]
+pub struct CageService {
+    pool: sqlx::Pool<Postgres>,
+    authenticator: Authenticator,
+    attempts: Arc<Buckets<IpAddr>>,
+    quota: Quota
+}
+
+impl CageService {
+    pub fn new(pool: sqlx::Pool<Postgres>, authenticator: Authenticator) -> Self {
+        let quota = Quota {
+            rate_per_minute: env_or("CAGE_RATE_LIMIT_PER_MINUTE", 10),
+            burst: env_or("CAGE_RATE_LIMIT_BURST", 5)
+        };
+        Self { pool, authenticator, attempts: Arc::new(Buckets::new(MAX_BUCKETS)), quota }
+    }
+
+    /// Take a token of the address a password or refresh token came from. Requests without one, as in tests, are not limited.
+    // `Status` is large, but it is what the rpc answers with anyway, right after this returns.
+    #[allow(clippy::result_large_err)]
+    fn throttle(&self, ip: Option<IpAddr>) -> Result<(), Status> {
+        let ip = match ip {
+            Some(ip) => ip,
+            None => return Ok(())
+        };
+        let decision = self.attempts.take(limited_by(ip), self.quota);
+        if decision.allowed {
+            Ok(())
+        } else {
+            tracing::warn!("{:<10} {}", yansi::Paint::red("throttle"), ip);
+            Err(Status::resource_exhausted(format!("Too many attempts, retry after {} seconds.", decision.retry_after)))
+        }
+    }
+
+    /// The admin whose access token is in the `authorization` metadata.
+    async fn admin<T>(&self, req: &Request<T>) -> Result<Principal, Status> {
+        let admin = self.authenticator.authenticate(&req.metadata().clone().into_headers()).await
+            .map_err(|rejection| rejection.status())?;
+        if admin.role < AccountRole::Admin {
+            return Err(Status::permission_denied(format!("{} is not an admin.", admin)));
+        }
+        Ok(admin)
+    }
+
+    /// An access token and a refresh token of the account.
+    async fn issue_pair(&self, account: &AccountObject, transaction: &mut sqlx::Transaction<'_, Postgres>) -> Result<Token, Status> {
+        let keys = self.authenticator.keys();
+        let access = keys.issue(account)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let (_, refresh) = RefreshTokenObject::issue(account.account_id(), keys.refresh_expires_at(), &mut *transaction).await
+            .map_err(internal)?;
+        Ok(Token { id: access, refresh_token: refresh, expires_in: keys.access_ttl_seconds() })
+    }
+
+    /// An access token of the active account of the name and password, without a refresh token,
+    /// for the session cookie of the admin console. Attempts are limited per ip as `login` is.
+    pub async fn sign_in(&self, ip: IpAddr, user_name: &str, user_pass: String) -> Result<(AccountObject, String), Status> {
+        self.throttle(Some(ip))?;
+        let account = self.credentials(user_name, user_pass).await?;
+        active(&account)?;
+        let token = self.authenticator.keys().issue(&account)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        tracing::debug!("{:<10} {}", yansi::Paint::green("sign in"), account);
+        Ok((account, token))
+    }
+
+    /// Revoke an access token before it expires.
+    pub async fn sign_out(&self, token: &str) -> Result<(), Status> {
+        let token = Token { id: token.to_owned(), ..Token::default() };
+        self.revoke(Request::new(token)).await?;
+        Ok(())
+    }
+
+    pub fn access_ttl_seconds(&self) -> i64 {
+        self.authenticator.keys().access_ttl_seconds()
+    }
+
+    /// The account of the name and password, whatever its status is.
+    ///
+    /// A password is checked for unknown users too, so that the time taken does not tell which names exist.
+    async fn credentials(&self, user_name: &str, user_pass: String) -> Result<AccountObject, Status> {
+        let account = AccountObject::fetch_from_name(user_name.trim(), &self.pool).await
+            .map_err(internal)?;
+        let password_hash = match &account {
+            Some(account) => account.password_hash().to_owned(),
+            None => UNKNOWN_USER_HASH.get_or_try_init(|| hash_password(String::new())).await?.clone()
+        };
+        let verified = verify_password(user_pass, password_hash).await?;
+        match account {
+            Some(account) if verified => Ok(account),
+            _ => Err(Status::unauthenticated(WRONG_CREDENTIALS))
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl CageApi for CageService {
+    async fn create_account(&self, req: Request<AccountConfig>) -> Result<Response<Token>, Status> {
+        let admin = self.admin(&req).await?;
+        let AccountConfig { user_name, user_pass } = req.into_inner();
+        let user_name = check_user_name(&user_name)?;
+        check_password("user_pass", &user_pass)?;
+
+        let password_hash = hash_password(user_pass).await?;
+        let mut transaction = self.pool.begin().await.map_err(internal)?;
+        let account = AccountObject::create(&user_name, &password_hash, &mut transaction).await
+            .map_err(internal)?
+            .ok_or_else(|| Status::already_exists(format!("{} is already taken.", user_name)))?;
+        let token = self.issue_pair(&account, &mut transaction).await?;
+        transaction.commit().await.map_err(internal)?;
+        tracing::info!("{:<10} {} by {}", yansi::Paint::green("create"), account, admin);
+        Ok(Response::new(token))
+    }
+
+    async fn verification(&self, req: Request<Token>) -> Result<Response<Account>, Status> {
+        let (_, account) = self.authenticator.verify(&req.into_inner().id).await
+            .map_err(|rejection| rejection.status())?;
+        Ok(Response::new(Account::from(account)))
+    }
+
+    async fn login(&self, req: Request<AccountConfig>) -> Result<Response<Token>, Status> {
+        self.throttle(req.remote_addr().map(|addr| addr.ip()))?;
+        let AccountConfig { user_name, user_pass } = req.into_inner();
+        let account = self.credentials(&user_name, user_pass).await?;
+        active(&account)?;
+        let mut transaction = self.pool.begin().await.map_err(internal)?;
+        let token = self.issue_pair(&account, &mut transaction).await?;
+        transaction.commit().await.map_err(internal)?;
+        tracing::debug!("{:<10} {}", yansi::Paint::green("login"), account);
+        Ok(Response::new(token))
+    }
+
+    async fn refresh(&self, req: Request<Token>) -> Result<Response<Token>, Status> {
+        self.throttle(req.remote_addr().map(|addr| addr.ip()))?;
+        let refresh_token = req.into_inner().refresh_token;
+        let mut transaction = self.pool.begin().await.map_err(internal)?;
+        let consumed = match RefreshTokenObject::consume(&refresh_token, &mut transaction).await.map_err(internal)? {
+            Some(consumed) => consumed,
+            None => {
+                drop(transaction);
+                let reused = RefreshTokenObject::fetch_from_token(&refresh_token, &self.pool).await
+                    .map_err(internal)?
+                    .filter(|stored| stored.revoked_at().is_some());
+                if let Some(reused) = reused {
+                    // Someone else may have exchanged it first, so the whole family is revoked.
+                    RefreshTokenObject::revoke_all(reused.account_id(), &self.pool).await
+                        .map_err(internal)?;
+                    tracing::warn!("{:<10} {} was used again, revoked every refresh token of the account",
+                        yansi::Paint::red("reuse"), reused);
+                }
+                return Err(Status::unauthenticated("Refresh token is invalid, expired or revoked."));
+            }
+        };
+        let account = AccountObject::fetch_from_id(consumed.account_id(), &mut transaction).await
+            .map_err(internal)?
+            .ok_or_else(|| Rejection::Unknown.status())?;
+        active(&account)?;
+        let token = self.issue_pair(&account, &mut transaction).await?;
+        transaction.commit().await.map_err(internal)?;
+        Ok(Response::new(token))
+    }
+
+    async fn revoke(&self, req: Request<Token>) -> Result<Response<Void>, Status> {
+        let Token { id, refresh_token, .. } = req.into_inner();
+        if refresh_token.starts_with(REFRESH_TOKEN_PREFIX) {
+            if let Some(revoked) = RefreshTokenObject::consume(&refresh_token, &self.pool).await.map_err(internal)? {
+                tracing::debug!("{:<10} {}", yansi::Paint::magenta("revoke"), revoked);
+            }
+        }
+        if !id.is_empty() {
+            let claims = match self.authenticator.keys().verify(&id) {
+                Ok(claims) => claims,
+                // Nothing to revoke, it can not be used any more.
+                Err(e) if matches!(e.kind(), ErrorKind::ExpiredSignature) => return Ok(Response::new(Void {})),
+                Err(e) => return Err(Rejection::Invalid(e.to_string()).status())
+            };
+            let account_id = claims.account_id()
+                .ok_or_else(|| Rejection::Invalid("malformed subject".to_owned()).status())?;
+            let mut transaction = self.pool.begin().await.map_err(internal)?;
+            let revoked = RevokedTokenObject::revoke(&claims.jti, account_id, claims.expires_at(), &mut transaction).await
+                .map_err(internal)?;
+            transaction.commit().await.map_err(internal)?;
+            tracing::debug!("{:<10} {}", yansi::Paint::magenta("revoke"), revoked);
+        }
+        Ok(Response::new(Void {}))
+    }
+
+    async fn change_password(&self, req: Request<PasswordChange>) -> Result<Response<Token>, Status> {
+        self.throttle(req.remote_addr().map(|addr| addr.ip()))?;
+        let PasswordChange { user_name, user_pass, new_pass } = req.into_inner();
+        check_password("new_pass", &new_pass)?;
+        let account = self.credentials(&user_name, user_pass).await?;
+        active(&account)?;
+
+        let password_hash = hash_password(new_pass).await?;
+        let mut transaction = self.pool.begin().await.map_err(internal)?;
+        let account = AccountObject::update_password(account.account_id(), &password_hash, &mut transaction).await
+            .map_err(internal)?
+            .ok_or_else(|| Rejection::Unknown.status())?;
+        RefreshTokenObject::revoke_all(account.account_id(), &mut transaction).await
+            .map_err(internal)?;
+        let token = self.issue_pair(&account, &mut transaction).await?;
+        transaction.commit().await.map_err(internal)?;
+        self.authenticator.forget(account.account_id());
+        tracing::debug!("{:<10} {}", yansi::Paint::yellow("password"), account);
+        Ok(Response::new(token))
+    }
+
+    async fn change_status(&self, req: Request<StatusChange>) -> Result<Response<Account>, Status> {
+        let admin = self.admin(&req).await?;
+        let StatusChange { user_id, status } = req.into_inner();
+        let account_id = user_id.parse::<i64>()
+            .map_err(|_| Status::invalid_argument(format!("{} is not a valid user_id.", user_id)))?;
+        let status = proto::account::AccountStatus::from_i32(status)
+            .and_then(|status| AccountStatus::try_from(status).ok())
+            .ok_or_else(|| Status::invalid_argument(format!("{} is not an AccountStatus, status is required.", status)))?;
+        if account_id == admin.account_id {
+            return Err(Status::failed_precondition("Admins can not change the status of their own account."));
+        }
+
+        let mut transaction = self.pool.begin().await.map_err(internal)?;
+        let account = AccountObject::update_status(account_id, status, &mut transaction).await
+            .map_err(internal)?
+            .ok_or_else(|| Status::not_found(format!("account {} is not found.", account_id)))?;
+        if status != AccountStatus::Active {
+            RefreshTokenObject::revoke_all(account_id, &mut transaction).await
+                .map_err(internal)?;
+        }
+        transaction.commit().await.map_err(internal)?;
+        self.authenticator.forget(account_id);
+        tracing::info!("{:<10} {} by {}", yansi::Paint::yellow("status"), account, admin);
+        Ok(Response::new(Account::from(account)))
+    }
+}
+
+fn internal(e: sqlx::Error) -> Status {
+    tracing::error!("{:<10} {:?}", yansi::Paint::red("cage"), e);
+    Status::internal("Failed to access accounts.")
+}
+
+#[allow(clippy::result_large_err)]
+fn active(account: &AccountObject) -> Result<(), Status> {
+    match account.status() {
+        AccountStatus::Active => Ok(()),
+        AccountStatus::Inactive => Err(Rejection::Inactive.status()),
+        AccountStatus::Blocked => Err(Rejection::Blocked.status())
+    }
+}
+
+/// The trimmed name, when it fits.
+#[allow(clippy::result_large_err)]
+pub(crate) fn check_user_name(user_name: &str) -> Result<String, Status> {
+    let user_name = user_name.trim();
+    if user_name.is_empty() || user_name.chars().count() > MAX_USER_NAME_LENGTH
+        || user_name.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        return Err(Status::invalid_argument(format!(
+            "user_name must be 1 to {} characters without spaces.", MAX_USER_NAME_LENGTH)));
+    }
+    Ok(user_name.to_owned())
+}
+
+#[allow(clippy::result_large_err)]
+pub(crate) fn check_password(field: &str, password: &str) -> Result<(), Status> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(Status::invalid_argument(format!(
+            "{} must be at least {} characters.", field, MIN_PASSWORD_LENGTH)));
+    }
+    Ok(())
+}
+
+/// Argon2id with a random salt, run off the async workers as it is slow on purpose.
+pub(crate) async fn hash_password(password: String) -> Result<String, Status> {
+    tokio::task::spawn_blocking(move || {
+        let mut random = [0u8; 16];
+        openssl::rand::rand_bytes(&mut random)
+            .map_err(|e| e.to_string())?;
+        let salt = SaltString::b64_encode(&random)
+            .map_err(|e| e.to_string())?;
+        Argon2::default().hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| e.to_string())
+    }).await
+      .map_err(|e| Status::internal(e.to_string()))?
+      .map_err(Status::internal)
+}
+
+async fn verify_password(password: String, password_hash: String) -> Result<bool, Status> {
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&password_hash)
+            .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+            .map_err(|e| e.to_string())
+    }).await
+      .map_err(|e| Status::internal(e.to_string()))?
+      .map_err(Status::internal)
+}
+
+impl From<AccountStatus> for proto::account::AccountStatus {
+    fn from(status: AccountStatus) -> Self {
+        match status {
+            AccountStatus::Active => Self::Active,
+            AccountStatus::Inactive => Self::Inactive,
+            AccountStatus::Blocked => Self::Blocked
+        }
+    }
+}
+
+impl TryFrom<proto::account::AccountStatus> for AccountStatus {
+    type Error = proto::account::AccountStatus;
+
+    /// `Unspecified` is the default of an omitted field, so it is not taken as any status.
+    fn try_from(status: proto::account::AccountStatus) -> Result<Self, Self::Error> {
+        match status {
+            proto::account::AccountStatus::Unspecified => Err(status),
+            proto::account::AccountStatus::Active => Ok(Self::Active),
+            proto::account::AccountStatus::Inactive => Ok(Self::Inactive),
+            proto::account::AccountStatus::Blocked => Ok(Self::Blocked)
+        }
+    }
+}
+
+impl From<AccountObject> for Account {
+    fn from(obj: AccountObject) -> Self {
+        Self {
+            user_id: obj.account_id().to_string(),
+            user_name: obj.user_name().to_owned(),
+            status: proto::account::AccountStatus::from(obj.status()) as i32
+        }
+    }
+}
+
+/// Runs against the database of `DATABASE_URL`, migrating it first, with `cargo test -- --ignored`.
+/// Accounts get unique names, so runs do not collide.
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue};
+    use tokio::sync::OnceCell;
+    use tonic::Code;
+
+    use super::*;
+    use crate::database::postgres_database;
+
+    static MIGRATED: OnceCell<()> = OnceCell::const_new();
+    const PASSWORD: &str = "nyan-nyan-0010";
+
+    async fn service() -> CageService {
+        dotenv::dotenv().ok();
+        MIGRATED.get_or_init(|| async {
+            postgres_database::migration().await
+                .expect("Tests need a local Postgres at DATABASE_URL.");
+        }).await;
+        let pool = sqlx::PgPool::connect(&dotenv::var("DATABASE_URL").unwrap()).await.unwrap();
+        CageService::new(pool.clone(), Authenticator::new(pool, TokenKeys::from_env()))
+    }
+
+    fn unique_name() -> String {
+        let mut random = [0u8; 6];
+        openssl::rand::rand_bytes(&mut random).unwrap();
+        format!("test_{}", random.iter().map(|byte| format!("{:02x}", byte)).collect::<String>())
+    }
+
+    fn config(user_name: &str, user_pass: &str) -> Request<AccountConfig> {
+        Request::new(AccountConfig { user_name: user_name.to_owned(), user_pass: user_pass.to_owned() })
+    }
+
+    fn bearer(token: &Token) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token.id)).unwrap());
+        headers
+    }
+
+    fn refresh_token(token: &Token) -> Request<Token> {
+        Request::new(Token { refresh_token: token.refresh_token.clone(), ..Token::default() })
+    }
+
+    fn authorized<T>(mut request: Request<T>, by: &Token) -> Request<T> {
+        request.metadata_mut().insert("authorization", format!("Bearer {}", by.id).parse().unwrap());
+        request
+    }
+
+    /// A token of a new admin, made without `createAccount` as the cli does.
+    async fn admin(cage: &CageService) -> Token {
+        let password_hash = hash_password(PASSWORD.to_owned()).await.unwrap();
+        let mut transaction = cage.pool.begin().await.unwrap();
+        let account = AccountObject::create(&unique_name(), &password_hash, &mut transaction).await.unwrap().unwrap();
+        let account = AccountObject::update_role(account.account_id(), AccountRole::Admin, None, &mut transaction).await.unwrap().unwrap();
+        let token = cage.issue_pair(&account, &mut transaction).await.unwrap();
+        transaction.commit().await.unwrap();
+        token
+    }
+
+    async fn create(cage: &CageService) -> (String, Token) {
+        let name = unique_name();
+        let token = cage.create_account(authorized(config(&name, PASSWORD), &admin(cage).await)).await.unwrap().into_inner();
+        (name, token)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn login_checks_the_password() {
+        let cage = service().await;
+        let (name, created) = create(&cage).await;
+        assert!(created.id.split('.').count() == 3 && created.refresh_token.starts_with(REFRESH_TOKEN_PREFIX));
+
+        let token = cage.login(config(&name, PASSWORD)).await.unwrap().into_inner();
+        let account = cage.verification(Request::new(token)).await.unwrap().into_inner();
+        assert_eq!(account.user_name, name);
+        assert_eq!(account.status(), proto::account::AccountStatus::Active);
+
+        let wrong = cage.login(config(&name, "wrong-password")).await.unwrap_err();
+        assert_eq!(wrong.code(), Code::Unauthenticated);
+        let unknown = cage.login(config(&unique_name(), PASSWORD)).await.unwrap_err();
+        assert_eq!(unknown.code(), Code::Unauthenticated);
+        assert_eq!(wrong.message(), unknown.message());
+
+        let admin = admin(&cage).await;
+        let taken = cage.create_account(authorized(config(&name, PASSWORD), &admin)).await.unwrap_err();
+        assert_eq!(taken.code(), Code::AlreadyExists);
+        let short = cage.create_account(authorized(config(&unique_name(), "short"), &admin)).await.unwrap_err();
+        assert_eq!(short.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn only_admins_create_accounts() {
+        let cage = service().await;
+        let (_, user) = create(&cage).await;
+
+        let anonymous = cage.create_account(config(&unique_name(), PASSWORD)).await.unwrap_err();
+        assert_eq!(anonymous.code(), Code::Unauthenticated);
+        let not_admin = cage.create_account(authorized(config(&unique_name(), PASSWORD), &user)).await.unwrap_err();
+        assert_eq!(not_admin.code(), Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn refresh_tokens_are_used_once() {
+        let cage = service().await;
+        let (_, created) = create(&cage).await;
+
+        let refreshed = cage.refresh(refresh_token(&created)).await.unwrap().into_inner();
+        assert_ne!(refreshed.refresh_token, created.refresh_token);
+        assert!(cage.authenticator.authenticate(&bearer(&refreshed)).await.is_ok());
+
+        // Reusing an exchanged token revokes the whole family, including the one it was exchanged for.
+        let reused = cage.refresh(refresh_token(&created)).await.unwrap_err();
+        assert_eq!(reused.code(), Code::Unauthenticated);
+        let family = cage.refresh(refresh_token(&refreshed)).await.unwrap_err();
+        assert_eq!(family.code(), Code::Unauthenticated);
+
+        let unknown = cage.refresh(Request::new(Token { refresh_token: "mtr_unknown".to_owned(), ..Token::default() })).await.unwrap_err();
+        assert_eq!(unknown.code(), Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn revoked_tokens_are_rejected() {
+        let cage = service().await;
+        let (_, created) = create(&cage).await;
+        assert!(cage.authenticator.authenticate(&bearer(&created)).await.is_ok());
+
+        cage.revoke(Request::new(created.clone())).await.unwrap();
+        assert_eq!(cage.authenticator.authenticate(&bearer(&created)).await.unwrap_err(), Rejection::Revoked);
+        assert_eq!(cage.verification(Request::new(created.clone())).await.unwrap_err().code(), Code::Unauthenticated);
+        assert_eq!(cage.refresh(refresh_token(&created)).await.unwrap_err().code(), Code::Unauthenticated);
+
+        // Revoking again is fine.
+        cage.revoke(Request::new(created)).await.unwrap();
+        let forged = cage.revoke(Request::new(Token { id: "not.a.token".to_owned(), ..Token::default() })).await.unwrap_err();
+        assert_eq!(forged.code(), Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn changing_the_password_revokes_every_token() {
+        let cage = service().await;
+        let (name, created) = create(&cage).await;
+        let new_pass = "meow-meow-0010";
+        let change = |user_pass: &str, new_pass: &str| Request::new(PasswordChange {
+            user_name: name.clone(), user_pass: user_pass.to_owned(), new_pass: new_pass.to_owned()
+        });
+
+        assert_eq!(cage.change_password(change("wrong-password", new_pass)).await.unwrap_err().code(), Code::Unauthenticated);
+        assert_eq!(cage.change_password(change(PASSWORD, "short")).await.unwrap_err().code(), Code::InvalidArgument);
+
+        let changed = cage.change_password(change(PASSWORD, new_pass)).await.unwrap().into_inner();
+        assert_eq!(cage.authenticator.authenticate(&bearer(&created)).await.unwrap_err(), Rejection::Revoked);
+        assert_eq!(cage.refresh(refresh_token(&created)).await.unwrap_err().code(), Code::Unauthenticated);
+        assert!(cage.authenticator.authenticate(&bearer(&changed)).await.is_ok());
+
+        assert_eq!(cage.login(config(&name, PASSWORD)).await.unwrap_err().code(), Code::Unauthenticated);
+        assert!(cage.login(config(&name, new_pass)).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn admins_change_the_status_of_accounts() {
+        let cage = service().await;
+        let (_, admin) = create(&cage).await;
+        let (name, user) = create(&cage).await;
+        let admin_id = cage.verification(Request::new(admin.clone())).await.unwrap().into_inner().user_id;
+        let user_id = cage.verification(Request::new(user.clone())).await.unwrap().into_inner().user_id;
+        let change = |by: &Token, user_id: &str, status: proto::account::AccountStatus| {
+            authorized(Request::new(StatusChange { user_id: user_id.to_owned(), status: status as i32 }), by)
+        };
+
+        let not_admin = cage.change_status(change(&admin, &user_id, proto::account::AccountStatus::Blocked)).await.unwrap_err();
+        assert_eq!(not_admin.code(), Code::PermissionDenied);
+        AccountObject::update_role(admin_id.parse().unwrap(), AccountRole::Admin, None, &cage.pool).await.unwrap();
+        cage.authenticator.forget(admin_id.parse().unwrap());
+        let own = cage.change_status(change(&admin, &admin_id, proto::account::AccountStatus::Inactive)).await.unwrap_err();
+        assert_eq!(own.code(), Code::FailedPrecondition);
+
+        let blocked = cage.change_status(change(&admin, &user_id, proto::account::AccountStatus::Blocked)).await.unwrap().into_inner();
+        assert_eq!(blocked.status(), proto::account::AccountStatus::Blocked);
+        assert_eq!(cage.verification(Request::new(user.clone())).await.unwrap_err().code(), Code::Unauthenticated);
+        assert_eq!(cage.login(config(&name, PASSWORD)).await.unwrap_err().code(), Code::PermissionDenied);
+        assert_eq!(cage.refresh(refresh_token(&user)).await.unwrap_err().code(), Code::Unauthenticated);
+
+        let inactive = cage.change_status(change(&admin, &user_id, proto::account::AccountStatus::Inactive)).await.unwrap().into_inner();
+        assert_eq!(inactive.status(), proto::account::AccountStatus::Inactive);
+        assert_eq!(cage.login(config(&name, PASSWORD)).await.unwrap_err().code(), Code::PermissionDenied);
+
+        cage.change_status(change(&admin, &user_id, proto::account::AccountStatus::Active)).await.unwrap();
+        let token = cage.login(config(&name, PASSWORD)).await.unwrap().into_inner();
+        assert!(cage.authenticator.authenticate(&bearer(&token)).await.is_ok());
+        // Tokens from before the account was blocked stay revoked.
+        assert_eq!(cage.authenticator.authenticate(&bearer(&user)).await.unwrap_err(), Rejection::Revoked);
+
+        let missing = cage.change_status(change(&admin, "0", proto::account::AccountStatus::Active)).await.unwrap_err();
+        assert_eq!(missing.code(), Code::NotFound);
+
+        // An omitted status does not reactivate a blocked account.
+        cage.change_status(change(&admin, &user_id, proto::account::AccountStatus::Blocked)).await.unwrap();
+        let omitted = cage.change_status(authorized(Request::new(StatusChange { user_id: user_id.clone(), ..StatusChange::default() }), &admin)).await.unwrap_err();
+        assert_eq!(omitted.code(), Code::InvalidArgument);
+        assert_eq!(cage.login(config(&name, PASSWORD)).await.unwrap_err().code(), Code::PermissionDenied);
+    }
+}
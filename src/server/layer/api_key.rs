@@ -0,0 +1,267 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use futures::future::BoxFuture;
+use lru::LruCache;
+use sqlx::PgPool;
+use tokio::time::Instant;
+use tower::{Layer, Service};
+
+use crate::database::ApiKeyObject;
+use crate::routing::ApiError;
+use crate::server::env_or;
+use super::rate_limit::{limited_by, Buckets, Decision, Quota};
+
+const API_KEY_HEADER: &str = "x-api-key";
+const API_KEY_QUERY: &str = "api_key";
+/// How long a key is trusted without the database, which bounds how late a revocation or a new quota applies.
+const KEY_CACHE_TTL: Duration = Duration::from_secs(60);
+/// Clients and keys are sent by anyone, so both maps are capped, dropping the least recently used entry when full.
+const MAX_BUCKETS: usize = 100_000;
+const MAX_KEYS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Client {
+    Key(i64),
+    Ip(IpAddr)
+}
+
+#[derive(Debug)]
+struct Limiter {
+    pool: PgPool,
+    anonymous: Quota,
+    trusted_proxies: usize,
+    keys: Mutex<LruCache<String, (Instant, Option<ApiKeyObject>)>>,
+    buckets: Buckets<Client>
+}
+
+impl Limiter {
+    /// The key stored for `key` when it was looked up within [KEY_CACHE_TTL].
+    fn cached(&self, key: &str) -> Option<Option<ApiKeyObject>> {
+        let now = Instant::now();
+        self.keys.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(key)
+            .filter(|(fetched, _)| now.duration_since(*fetched) < KEY_CACHE_TTL)
+            .map(|(_, found)| found.clone())
+    }
+
+    /// The key stored for `key`, cached for [KEY_CACHE_TTL] so that most requests do not reach the database.
+    async fn lookup(&self, key: &str) -> Result<Option<ApiKeyObject>, sqlx::Error> {
+        if let Some(found) = self.cached(key) {
+            return Ok(found);
+        }
+
+        let found = ApiKeyObject::fetch_from_key(key, &self.pool).await?;
+        let now = Instant::now();
+        self.keys.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .put(key.to_owned(), (now, found.clone()));
+        Ok(found)
+    }
+}
+
+/// Address of the client, taken from `X-Forwarded-For` behind `trusted_proxies` proxies, and limited by [limited_by].
+///
+/// Each proxy appends the address it was connected from, so the entries left of those appended by
+/// the trusted proxies are written by the client and are skipped.
+fn client_ip<B>(request: &Request<B>, trusted_proxies: usize) -> Option<IpAddr> {
+    let forwarded = (trusted_proxies > 0)
+        .then(|| request.headers().get_all("x-forwarded-for"))
+        .map(|values| values.iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>())
+        .and_then(|entries| entries.into_iter().rev().nth(trusted_proxies - 1))
+        .and_then(|ip| ip.parse().ok());
+    forwarded
+        .or_else(|| request.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip()))
+        .map(limited_by)
+}
+
+
+/// Checks api keys and limits requests with a token bucket per key, or per ip for requests without a key.
+///
+/// A key is sent in `X-API-Key` or `?api_key=`. Quotas of keys are stored with them,
+/// and `RATE_LIMIT_ANONYMOUS_PER_MINUTE` and `RATE_LIMIT_ANONYMOUS_BURST` apply to the others.
+/// Behind reverse proxies, `RATE_LIMIT_TRUSTED_PROXIES` is how many of them append to `X-Forwarded-For`,
+/// and the ip is the entry appended by the outermost one. `RATE_LIMIT_TRUST_FORWARDED_FOR=true` is the same as one proxy.
+/// A key that is not cached costs the ip a token before it is looked up.
+#[derive(Debug, Clone)]
+pub struct ApiKeyLayer {
+    limiter: Arc<Limiter>
+}
+
+impl ApiKeyLayer {
+    pub fn from_env(pool: PgPool) -> Self {
+        let anonymous = Quota {
+            rate_per_minute: env_or("RATE_LIMIT_ANONYMOUS_PER_MINUTE", 60),
+            burst: env_or("RATE_LIMIT_ANONYMOUS_BURST", 30)
+        };
+        let trusted_proxies = dotenv::var("RATE_LIMIT_TRUSTED_PROXIES")
+            .ok()
+            .and_then(|f| f.parse().ok())
+            .unwrap_or_else(|| dotenv::var("RATE_LIMIT_TRUST_FORWARDED_FOR").map_or(0, |f| usize::from(f == "true")));
+        let limiter = Limiter {
+            pool,
+            anonymous,
+            trusted_proxies,
+            keys: Mutex::new(LruCache::new(MAX_KEYS)),
+            buckets: Buckets::new(MAX_BUCKETS)
+        };
+        Self { limiter: Arc::new(limiter) }
+    }
+}
+
+impl<S> Layer<S> for ApiKeyLayer {
+    type Service = ApiKeyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ApiKeyService { inner, limiter: Arc::clone(&self.limiter) }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKeyService<S> {
+    inner: S,
+    limiter: Arc<Limiter>
+}
+
+impl<S, B> Service<Request<B>> for ApiKeyService<S>
+    where S: Service<Request<B>, Response = Response> + Clone + Send + 'static,
+          S::Future: Send + 'static,
+          B: Send + 'static
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        // The clone may not be ready, so the one that was polled is used.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = Arc::clone(&self.limiter);
+        let key = api_key(&request);
+        let ip = client_ip(&request, limiter.trusted_proxies);
+        Box::pin(async move {
+            // Unknown keys would otherwise reach the database as fast as they are sent.
+            if let (Some(key), Some(ip)) = (&key, ip) {
+                if limiter.cached(key).is_none() {
+                    let decision = limiter.buckets.take(Client::Ip(ip), limiter.anonymous);
+                    if !decision.allowed {
+                        return Ok(too_many_requests(&decision));
+                    }
+                }
+            }
+            let (client, quota) = match key {
+                Some(key) => match limiter.lookup(&key).await {
+                    Ok(Some(found)) => (Client::Key(found.key_id()), Quota::from(&found)),
+                    Ok(None) => return Ok(ApiError::reason("Api key is unknown or revoked.")
+                        .report(StatusCode::UNAUTHORIZED)
+                        .into_response()),
+                    Err(e) => return Ok(ApiError::new(e)
+                        .report(StatusCode::SERVICE_UNAVAILABLE)
+                        .into_response())
+                },
+                None => match ip {
+                    Some(ip) => (Client::Ip(ip), limiter.anonymous),
+                    // Nothing to limit by without a socket address.
+                    None => return inner.call(request).await
+                }
+            };
+
+            let decision = limiter.buckets.take(client, quota);
+            if !decision.allowed {
+                return Ok(too_many_requests(&decision));
+            }
+            let mut response = inner.call(request).await?;
+            rate_limit_headers(response.headers_mut(), &decision);
+            Ok(response)
+        })
+    }
+}
+
+fn too_many_requests(decision: &Decision) -> Response {
+    let mut response = ApiError::reason("Too many requests.")
+        .report(StatusCode::TOO_MANY_REQUESTS)
+        .into_response();
+    response.headers_mut().insert("retry-after", HeaderValue::from(decision.retry_after));
+    rate_limit_headers(response.headers_mut(), decision);
+    response
+}
+
+fn api_key<B>(request: &Request<B>) -> Option<String> {
+    let header = request.headers().get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let query = || request.uri().query()
+        .and_then(|query| query.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(name, _)| *name == API_KEY_QUERY)
+            .map(|(_, value)| value.to_owned()));
+    header.or_else(query).filter(|key| !key.is_empty())
+}
+
+/// Headers of draft-ietf-httpapi-ratelimit-headers.
+fn rate_limit_headers(headers: &mut HeaderMap, decision: &Decision) {
+    let policy = format!("{};w=60;burst={}", decision.quota.rate_per_minute, decision.quota.burst);
+    headers.insert("ratelimit-limit", HeaderValue::from(decision.quota.burst));
+    headers.insert("ratelimit-remaining", HeaderValue::from(decision.remaining));
+    headers.insert("ratelimit-reset", HeaderValue::from(decision.reset));
+    if let Ok(policy) = HeaderValue::from_str(&policy) {
+        headers.insert("ratelimit-policy", policy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(forwarded_for: &[&str]) -> Request<()> {
+        let mut request = Request::new(());
+        for value in forwarded_for {
+            request.headers_mut().append("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        }
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 443))));
+        request
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_without_trusted_proxies() {
+        assert_eq!(client_ip(&request(&["192.0.2.1"]), 0), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn the_entry_appended_by_the_outermost_trusted_proxy_is_the_client() {
+        let spoofed = request(&["198.51.100.7, 192.0.2.1, 10.0.0.2"]);
+        assert_eq!(client_ip(&spoofed, 1), ip("10.0.0.2"));
+        assert_eq!(client_ip(&spoofed, 2), ip("192.0.2.1"));
+        assert_eq!(client_ip(&request(&["198.51.100.7", "192.0.2.1"]), 1), ip("192.0.2.1"), "headers are read in order");
+    }
+
+    #[test]
+    fn fewer_entries_than_trusted_proxies_fall_back_to_the_socket() {
+        assert_eq!(client_ip(&request(&["192.0.2.1"]), 2), ip("10.0.0.1"));
+        assert_eq!(client_ip(&request(&["not an address"]), 1), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn forwarded_ipv6_clients_are_limited_by_their_prefix() {
+        assert_eq!(client_ip(&request(&["2001:db8::1:2:3:4"]), 1), ip("2001:db8::"));
+    }
+}
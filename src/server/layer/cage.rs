@@ -1 +1,364 @@
-// todo: authentication request
\ No newline at end of file
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use axum::http::{header, HeaderMap, HeaderValue, Request, StatusCode};
+use axum::response::IntoResponse;
+use futures::future::BoxFuture;
+use openssl::nid::Nid;
+use openssl::x509::X509;
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::time::Instant;
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use tower::{Layer, Service};
+
+use crate::database::{Accessor, AccountObject, AccountRole, AccountStatus, AffiliationId, RevokedTokenObject, Scoped, Table};
+use crate::routing::ApiError;
+use crate::server::cage::{Claims, TokenKeys};
+
+/// Age of a cached account past which its status and token version are read again.
+const ACCOUNT_CACHE_TTL: Duration = Duration::from_secs(60);
+const MAX_ACCOUNTS: usize = 10_000;
+
+/// Account a request was authenticated as, found in the request extensions behind [CageLayer].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Principal {
+    pub account_id: i64,
+    pub user_name: String,
+    pub role: AccountRole,
+    /// `None` when the account is not limited to some affiliations.
+    pub scope: Option<Vec<AffiliationId>>,
+    /// Common name of the client certificate, when authenticated over mutual TLS instead of with a token.
+    pub certificate: Option<String>
+}
+
+/// Kind of a write, which roles are checked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Write {
+    Upsert,
+    Delete
+}
+
+impl Principal {
+    /// Whether the role allows `write` to `table`.
+    pub fn may(&self, table: Table, write: Write) -> bool {
+        match (table, write) {
+            (Table::Affiliations, _) => self.role >= AccountRole::Admin,
+            (_, Write::Upsert) => self.role >= AccountRole::Collector,
+            (_, Write::Delete) => self.role >= AccountRole::Curator
+        }
+    }
+
+    /// Whether all of `affiliations` are in the scope. Values of no affiliation are only covered without a scope.
+    pub fn covers(&self, affiliations: &[Option<AffiliationId>]) -> bool {
+        match &self.scope {
+            Some(scope) => affiliations.iter()
+                .all(|affiliation| affiliation.map_or(false, |affiliation| scope.contains(&affiliation))),
+            None => true
+        }
+    }
+
+    /// Whether `item` may be written, both by the role and by the affiliations it belongs to before and after.
+    pub async fn permits<T>(&self, item: &T, write: Write, transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Result<bool, sqlx::Error>
+        where T: Accessor + Scoped + Sync {
+        if !self.may(T::TABLE, write) {
+            return Ok(false);
+        }
+        Ok(self.covers(&item.affiliations(transaction).await?))
+    }
+}
+
+impl Display for Principal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "account >> {}: {} ({})", self.account_id, self.user_name, self.role.name())?;
+        match &self.certificate {
+            Some(certificate) => write!(f, " by certificate {}", certificate),
+            None => Ok(())
+        }
+    }
+}
+
+impl From<&AccountObject> for Principal {
+    fn from(account: &AccountObject) -> Self {
+        Self {
+            account_id: account.account_id(),
+            user_name: account.user_name().to_owned(),
+            role: account.role(),
+            scope: account.affiliation_scope(),
+            certificate: None
+        }
+    }
+}
+
+/// Why a request was not authenticated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    Missing,
+    Invalid(String),
+    /// Revoked on its own, or along with every token of the account.
+    Revoked,
+    Unknown,
+    /// Client certificate whose common name is not mapped to an account.
+    UnknownCertificate(String),
+    Inactive,
+    Blocked,
+    Unavailable(String)
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::Missing => write!(f, "Authorization with a Cage token is required."),
+            Rejection::Invalid(reason) => write!(f, "Token is invalid: {}", reason),
+            Rejection::Revoked => write!(f, "Token is revoked."),
+            Rejection::Unknown => write!(f, "Account of the token is not found."),
+            Rejection::UnknownCertificate(subject) => write!(f, "Client certificate {} is not mapped to an account.", subject),
+            Rejection::Inactive => write!(f, "Account is inactive."),
+            Rejection::Blocked => write!(f, "Account is blocked."),
+            Rejection::Unavailable(reason) => write!(f, "Accounts are unavailable: {}", reason)
+        }
+    }
+}
+
+impl Rejection {
+    /// The rejection as a gRPC status.
+    pub fn status(&self) -> tonic::Status {
+        let message = self.to_string();
+        match self {
+            Rejection::Inactive | Rejection::Blocked => tonic::Status::permission_denied(message),
+            Rejection::Unavailable(_) => tonic::Status::unavailable(message),
+            _ => tonic::Status::unauthenticated(message)
+        }
+    }
+}
+
+/// Responses of a protocol that a rejected request is answered with.
+pub trait Reject {
+    fn reject(rejection: &Rejection) -> Self;
+}
+
+impl Reject for axum::response::Response {
+    fn reject(rejection: &Rejection) -> Self {
+        let status = match rejection {
+            Rejection::Inactive | Rejection::Blocked => StatusCode::FORBIDDEN,
+            Rejection::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::UNAUTHORIZED
+        };
+        let mut response = ApiError::reason(rejection.to_string())
+            .report(status)
+            .into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+impl Reject for axum::http::Response<tonic::body::BoxBody> {
+    fn reject(rejection: &Rejection) -> Self {
+        rejection.status().to_http()
+    }
+}
+
+/// Checks Cage tokens against the accounts they were issued for.
+#[derive(Debug, Clone)]
+pub struct Authenticator {
+    inner: Arc<Accounts>
+}
+
+#[derive(Debug)]
+struct Accounts {
+    pool: PgPool,
+    keys: TokenKeys,
+    cache: Mutex<HashMap<i64, (Instant, Option<AccountObject>)>>
+}
+
+impl Authenticator {
+    pub fn new(pool: PgPool, keys: TokenKeys) -> Self {
+        let inner = Accounts { pool, keys, cache: Mutex::new(HashMap::new()) };
+        Self { inner: Arc::new(inner) }
+    }
+
+    pub fn keys(&self) -> &TokenKeys {
+        &self.inner.keys
+    }
+
+    /// The active account of the bearer token in `authorization`, which is both the HTTP header and the gRPC metadata.
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, Rejection> {
+        let token = bearer(headers).ok_or(Rejection::Missing)?;
+        self.authenticate_token(&token).await
+    }
+
+    /// The active account of an access token, such as the one kept in the session cookie of the admin console.
+    pub async fn authenticate_token(&self, token: &str) -> Result<Principal, Rejection> {
+        let (_, account) = self.verify(token).await?;
+        admit(&account)?;
+        Ok(Principal::from(&account))
+    }
+
+    /// The active account mapped to the common name of a client certificate, which was verified by the TLS handshake.
+    pub async fn authenticate_certificate(&self, common_name: &str) -> Result<Principal, Rejection> {
+        let account = AccountObject::fetch_from_certificate_subject(common_name, &self.inner.pool).await
+            .map_err(|e| Rejection::Unavailable(e.to_string()))?
+            .ok_or_else(|| Rejection::UnknownCertificate(common_name.to_owned()))?;
+        admit(&account)?;
+        Ok(Principal { certificate: Some(common_name.to_owned()), ..Principal::from(&account) })
+    }
+
+    /// Claims of an access token that is not revoked, and the account it was issued for whatever its status is.
+    pub async fn verify(&self, token: &str) -> Result<(Claims, AccountObject), Rejection> {
+        let claims = self.inner.keys.verify(token)
+            .map_err(|e| Rejection::Invalid(e.to_string()))?;
+        let account_id = claims.account_id()
+            .ok_or_else(|| Rejection::Invalid("malformed subject".to_owned()))?;
+        let account = self.account(account_id).await
+            .map_err(|e| Rejection::Unavailable(e.to_string()))?
+            .ok_or(Rejection::Unknown)?;
+        if claims.ver != account.token_version() {
+            return Err(Rejection::Revoked);
+        }
+        let revoked = RevokedTokenObject::is_revoked(&claims.jti, &self.inner.pool).await
+            .map_err(|e| Rejection::Unavailable(e.to_string()))?;
+        if revoked {
+            return Err(Rejection::Revoked);
+        }
+        Ok((claims, account))
+    }
+
+    /// Drop the cached account, after its status or tokens changed.
+    pub fn forget(&self, account_id: i64) {
+        self.inner.cache.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&account_id);
+    }
+
+    /// The account of `account_id`, cached for [ACCOUNT_CACHE_TTL].
+    async fn account(&self, account_id: i64) -> Result<Option<AccountObject>, sqlx::Error> {
+        let now = Instant::now();
+        let cached = self.inner.cache.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&account_id)
+            .filter(|(fetched, _)| now.duration_since(*fetched) < ACCOUNT_CACHE_TTL)
+            .map(|(_, found)| found.clone());
+        if let Some(found) = cached {
+            return Ok(found);
+        }
+
+        let found = AccountObject::fetch_from_id(account_id, &self.inner.pool).await?;
+        let mut cache = self.inner.cache.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if cache.len() >= MAX_ACCOUNTS {
+            cache.retain(|_, (fetched, _)| now.duration_since(*fetched) < ACCOUNT_CACHE_TTL);
+        }
+        cache.insert(account_id, (now, found.clone()));
+        Ok(found)
+    }
+}
+
+fn admit(account: &AccountObject) -> Result<(), Rejection> {
+    match account.status() {
+        AccountStatus::Active => Ok(()),
+        AccountStatus::Inactive => Err(Rejection::Inactive),
+        AccountStatus::Blocked => Err(Rejection::Blocked)
+    }
+}
+
+/// Rejects requests without a token of an active account, and attaches the [Principal] to the others.
+///
+/// Requests without a token over mutual TLS are authenticated by the client certificate instead.
+/// Wraps a tonic service as well as an axum router, answering in the protocol of the wrapped one.
+#[derive(Debug, Clone)]
+pub struct CageLayer {
+    authenticator: Authenticator
+}
+
+impl CageLayer {
+    pub fn new(authenticator: Authenticator) -> Self {
+        Self { authenticator }
+    }
+}
+
+impl<S> Layer<S> for CageLayer {
+    type Service = CageAuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CageAuthService { inner, authenticator: self.authenticator.clone() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CageAuthService<S> {
+    inner: S,
+    authenticator: Authenticator
+}
+
+impl<S, B> Service<Request<B>> for CageAuthService<S>
+    where S: Service<Request<B>> + Clone + Send + 'static,
+          S::Response: Reject + Send,
+          S::Future: Send + 'static,
+          B: Send + 'static
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        // The clone may not be ready, so the one that was polled is used.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let authenticator = self.authenticator.clone();
+        let path = request.uri().path().to_owned();
+        let headers = request.headers().clone();
+        let certificate = client_certificate(&request);
+        Box::pin(async move {
+            let authenticated = match certificate {
+                Some(common_name) if bearer(&headers).is_none() => authenticator.authenticate_certificate(&common_name).await,
+                _ => authenticator.authenticate(&headers).await
+            };
+            match authenticated {
+                Ok(principal) => {
+                    request.extensions_mut().insert(principal);
+                    inner.call(request).await
+                }
+                Err(rejection) => {
+                    tracing::debug!("{:<10} {} {}", yansi::Paint::red("reject"), path, rejection);
+                    Ok(S::Response::reject(&rejection))
+                }
+            }
+        })
+    }
+}
+
+impl<S: tonic::transport::NamedService> tonic::transport::NamedService for CageAuthService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+/// Common name of the subject of the client certificate, on a Salmon connection over mutual TLS.
+fn client_certificate<B>(request: &Request<B>) -> Option<String> {
+    let certificates = request.extensions()
+        .get::<TlsConnectInfo<TcpConnectInfo>>()?
+        .peer_certs()?;
+    let certificate = X509::from_der(certificates.first()?.get_ref()).ok()?;
+    let common_name = certificate.subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .next()?
+        .data()
+        .as_utf8()
+        .ok()?
+        .to_string();
+    Some(common_name)
+}
+
+fn bearer(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.trim().split_once(' ')?;
+    Some(token.trim())
+        .filter(|token| scheme.eq_ignore_ascii_case("bearer") && !token.is_empty())
+        .map(str::to_owned)
+}
@@ -1 +1,7 @@
-mod cage;
\ No newline at end of file
+mod cage;
+mod api_key;
+mod rate_limit;
+
+pub use self::api_key::ApiKeyLayer;
+pub use self::rate_limit::{limited_by, Buckets, Quota};
+pub use self::cage::{Authenticator, CageLayer, Principal, Rejection, Write};
@@ -0,0 +1,173 @@
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Mutex;
+use lru::LruCache;
+use tokio::time::Instant;
+
+use crate::database::ApiKeyObject;
+
+/// Token bucket refilled by `rate_per_minute` tokens a minute, holding up to `burst` tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub rate_per_minute: u32,
+    pub burst: u32
+}
+
+impl From<&ApiKeyObject> for Quota {
+    fn from(key: &ApiKeyObject) -> Self {
+        Self { rate_per_minute: key.rate_per_minute(), burst: key.burst() }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant
+}
+
+/// Outcome of taking a token.
+#[derive(Debug)]
+pub struct Decision {
+    pub allowed: bool,
+    pub quota: Quota,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next token, when denied.
+    pub retry_after: u64
+}
+
+impl Bucket {
+    fn take(&mut self, quota: Quota, now: Instant) -> Decision {
+        let per_second = quota.rate_per_minute as f64 / 60.0;
+        let burst = quota.burst as f64;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        // A quota lowered by the cli also caps tokens saved under the old one.
+        self.tokens = (self.tokens + elapsed * per_second).min(burst);
+        self.updated = now;
+
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+        Decision {
+            allowed,
+            quota,
+            remaining: self.tokens.floor() as u32,
+            reset: ((burst - self.tokens) / per_second).ceil() as u64,
+            retry_after: if allowed { 0 } else { ((1.0 - self.tokens) / per_second).ceil() as u64 }
+        }
+    }
+}
+
+/// Token buckets per client, at most `capacity` of them.
+///
+/// Clients are whoever sends a request, so once full, the least recently used bucket is dropped.
+#[derive(Debug)]
+pub struct Buckets<K: Hash + Eq> {
+    buckets: Mutex<LruCache<K, Bucket>>
+}
+
+impl<K: Hash + Eq> Buckets<K> {
+    pub fn new(capacity: usize) -> Self {
+        Self { buckets: Mutex::new(LruCache::new(capacity)) }
+    }
+
+    pub fn take(&self, client: K, quota: Quota) -> Decision {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match buckets.get_mut(&client) {
+            Some(bucket) => bucket.take(quota, now),
+            None => {
+                let mut bucket = Bucket { tokens: quota.burst as f64, updated: now };
+                let decision = bucket.take(quota, now);
+                buckets.put(client, bucket);
+                decision
+            }
+        }
+    }
+}
+
+/// The address requests from `ip` are limited by.
+///
+/// An IPv6 host is usually given a whole /64, so it is limited by that prefix rather than by each of its addresses.
+pub fn limited_by(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(v6) & (u128::MAX << 64)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    const QUOTA: Quota = Quota { rate_per_minute: 60, burst: 3 };
+
+    fn full(now: Instant) -> Bucket {
+        Bucket { tokens: QUOTA.burst as f64, updated: now }
+    }
+
+    #[test]
+    fn a_burst_is_allowed_and_then_denied_until_the_next_token() {
+        let now = Instant::now();
+        let mut bucket = full(now);
+        for remaining in (0..3).rev() {
+            let decision = bucket.take(QUOTA, now);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+        let denied = bucket.take(QUOTA, now);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, 1);
+        assert_eq!(denied.reset, 3);
+    }
+
+    #[test]
+    fn tokens_refill_at_the_rate_up_to_the_burst() {
+        let now = Instant::now();
+        let mut bucket = Bucket { tokens: 0.0, updated: now };
+        let decision = bucket.take(QUOTA, now + Duration::from_secs(2));
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+
+        let decision = bucket.take(QUOTA, now + Duration::from_secs(60 * 60));
+        assert_eq!(decision.remaining, 2, "refilled past the burst");
+    }
+
+    #[test]
+    fn a_lowered_quota_caps_saved_tokens() {
+        let now = Instant::now();
+        let mut bucket = full(now);
+        let decision = bucket.take(Quota { rate_per_minute: 60, burst: 1 }, now);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+    }
+
+    #[test]
+    fn the_least_recently_used_bucket_is_dropped_when_full() {
+        let buckets = Buckets::new(2);
+        let quota = Quota { rate_per_minute: 1, burst: 1 };
+        assert!(buckets.take(1, quota).allowed);
+        assert!(buckets.take(2, quota).allowed);
+        assert!(!buckets.take(1, quota).allowed);
+        assert!(buckets.take(3, quota).allowed);
+        assert!(!buckets.take(1, quota).allowed, "1 was used after 2, so 2 is dropped");
+        assert!(buckets.take(2, quota).allowed);
+    }
+
+    #[test]
+    fn ipv6_hosts_are_limited_by_their_64_prefix() {
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+        assert_eq!(limited_by(ip("2001:db8:1:2:3:4:5:6")), ip("2001:db8:1:2::"));
+        assert_eq!(limited_by(ip("2001:db8:1:2:ffff::1")), limited_by(ip("2001:db8:1:2::1")));
+        assert_ne!(limited_by(ip("2001:db8:1:3::1")), limited_by(ip("2001:db8:1:2::1")));
+        assert_eq!(limited_by(ip("::ffff:192.0.2.1")), ip("192.0.2.1"));
+        assert_eq!(limited_by(ip("192.0.2.1")), ip("192.0.2.1"));
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use meilisearch_sdk::client::{Client, SwapIndexes};
+use meilisearch_sdk::search::{SearchResults, Selectors};
+use meilisearch_sdk::settings::Settings;
+use meilisearch_sdk::task_info::TaskInfo;
+use meilisearch_sdk::tasks::Task;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+
+use crate::database::{
+    Fetch, Table,
+    LiverObject,
+    ChannelObject,
+    ScheduleFilter, ScheduledVideoObject
+};
+use crate::models::{SearchHit, SearchHits, SearchResult};
+
+use super::normalize::normalize;
+use super::search::{affiliation_facets, liver_reading, ChannelDocument, LiverDocument, SearchError, VideoDocument};
+
+/// Number of documents sent to Meilisearch in one request while reindexing.
+const BATCH_SIZE: i64 = 1000;
+const TASK_TIMEOUT: Duration = Duration::from_secs(300);
+const FACETS: &[&str] = &["affiliation_id"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchIndex {
+    Livers,
+    Channels,
+    Videos
+}
+
+impl SearchIndex {
+    const ALL: [SearchIndex; 3] = [SearchIndex::Livers, SearchIndex::Channels, SearchIndex::Videos];
+
+    fn uid(&self) -> &'static str {
+        match self {
+            SearchIndex::Livers => "livers",
+            SearchIndex::Channels => "channels",
+            SearchIndex::Videos => "videos"
+        }
+    }
+
+    fn primary_key(&self) -> &'static str {
+        match self {
+            SearchIndex::Livers => "liver_id",
+            SearchIndex::Channels => "channel_id",
+            SearchIndex::Videos => "video_id"
+        }
+    }
+
+    /// Attributes in order of importance for ranking.
+    fn searchable(&self) -> &'static [&'static str] {
+        match self {
+            SearchIndex::Livers => &["name", "localized_name", "reading"],
+            SearchIndex::Channels => &["liver_name", "liver_reading", "description"],
+            SearchIndex::Videos => &["title", "liver_name", "description"]
+        }
+    }
+
+    fn settings(&self) -> Settings {
+        let settings = Settings::new()
+            .with_searchable_attributes(self.searchable());
+        match self {
+            SearchIndex::Livers => settings
+                .with_filterable_attributes(["affiliation_id"]),
+            SearchIndex::Channels => settings
+                .with_filterable_attributes(["affiliation_id", "liver_id"]),
+            SearchIndex::Videos => settings
+                .with_filterable_attributes(["affiliation_id", "liver_id", "channel_id", "status"])
+                .with_sortable_attributes(["will_start_at", "published_at"])
+        }
+    }
+}
+
+/// Keeps the Meilisearch indexes of livers, channels and videos in sync with the database.
+#[derive(Debug, Clone)]
+pub struct SearchIndexer {
+    client: Client
+}
+
+impl SearchIndexer {
+    /// Connects to `MEILISEARCH_URL` with `MEILISEARCH_API_KEY`.
+    pub fn from_env() -> Self {
+        let url = dotenv::var("MEILISEARCH_URL").unwrap_or_else(|_| "http://localhost:7700".to_string());
+        let api_key = dotenv::var("MEILISEARCH_API_KEY").unwrap_or_default();
+        Self { client: Client::new(url, api_key) }
+    }
+
+    /// Creates the indexes if missing and applies their searchable, filterable and sortable attributes.
+    pub async fn configure(&self) -> Result<(), SearchError> {
+        for index in SearchIndex::ALL {
+            self.configure_index(index.uid(), index).await?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds every index from the database.
+    ///
+    /// Documents are written to a staging index which is swapped with the live one,
+    /// so searches keep working while reindexing.
+    pub async fn reindex(&self, pool: &PgPool) -> Result<(), SearchError> {
+        for index in SearchIndex::ALL {
+            let staging = format!("{}_reindex", index.uid());
+            // Leftover of an interrupted reindex.
+            if let Ok(task) = self.client.delete_index(&staging).await {
+                self.client.wait_for_task(task, None, Some(TASK_TIMEOUT)).await?;
+            }
+            self.configure_index(&staging, index).await?;
+
+            match index {
+                SearchIndex::Livers => {
+                    let livers = <LiverObject as Fetch>::fetch_all(pool).await?;
+                    self.add_livers(&staging, &livers).await?;
+                }
+                SearchIndex::Channels => {
+                    let channels = ChannelObject::fetch_all(pool).await?;
+                    self.add_channels(&staging, &channels, pool).await?;
+                }
+                SearchIndex::Videos => {
+                    self.add_videos(&staging, &ScheduleFilter::default(), None, pool).await?;
+                }
+            }
+
+            self.configure_index(index.uid(), index).await?;
+            let task = self.client.swap_indexes([&SwapIndexes { indexes: (index.uid().to_string(), staging.clone()) }]).await?;
+            self.settle(task).await?;
+            let task = self.client.delete_index(&staging).await?;
+            self.settle(task).await?;
+            tracing::info!("{:<10} {}", yansi::Paint::green("reindexed"), index.uid());
+        }
+        Ok(())
+    }
+
+    /// Reflects rows committed to `table` into the indexes.
+    pub async fn sync(&self, table: Table, upserted: Vec<String>, deleted: Vec<String>, pool: &PgPool) -> Result<(), SearchError> {
+        match table {
+            // Affiliation names are resolved when searching.
+            Table::Affiliations => {}
+            Table::Livers => {
+                let ids = upserted.iter()
+                    .filter_map(|id| id.parse::<i64>().ok())
+                    .collect::<Vec<_>>();
+                let livers = LiverObject::fetch_from_ids(&ids, pool).await?;
+                self.add_livers(SearchIndex::Livers.uid(), &livers).await?;
+                self.delete(SearchIndex::Livers, &deleted).await?;
+                // Channels and videos carry the name and the affiliation of the liver.
+                if !ids.is_empty() {
+                    let channels = ChannelObject::fetch_filtered_livers(&ids, pool).await?;
+                    self.add_channels(SearchIndex::Channels.uid(), &channels, pool).await?;
+                    let filter = ScheduleFilter { liver_ids: Some(ids), ..Default::default() };
+                    self.add_videos(SearchIndex::Videos.uid(), &filter, None, pool).await?;
+                }
+            }
+            Table::Channels => {
+                let channels = ChannelObject::fetch_from_ids(&upserted, pool).await?;
+                self.add_channels(SearchIndex::Channels.uid(), &channels, pool).await?;
+                self.delete(SearchIndex::Channels, &deleted).await?;
+                // A channel may have moved to another liver.
+                if !upserted.is_empty() {
+                    self.add_videos(SearchIndex::Videos.uid(), &ScheduleFilter::default(), Some(&upserted), pool).await?;
+                }
+            }
+            Table::Videos => {
+                let videos = ScheduledVideoObject::fetch_from_ids(&upserted, pool).await?;
+                self.add(SearchIndex::Videos.uid(), SearchIndex::Videos, &videos.iter().map(VideoDocument::from).collect::<Vec<_>>()).await?;
+                self.delete(SearchIndex::Videos, &deleted).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Searches livers, channels and videos at once, with typo tolerance.
+    pub async fn search(&self, query: &str, affiliation_id: Option<i64>, limit: usize, pool: &PgPool) -> Result<SearchResult, SearchError> {
+        let filter = affiliation_id.map(|id| format!("affiliation_id = {}", id));
+        // Readings are matched by the normalised query, names and descriptions by the query as typed.
+        let reading = normalize(query);
+        let with_reading = if reading == query.to_lowercase() { reading } else { format!("{} {}", query, reading) };
+        let (livers, channels, videos) = futures::try_join!(
+            self.search_index(SearchIndex::Livers, &with_reading, filter.as_deref(), limit),
+            self.search_index(SearchIndex::Channels, &with_reading, filter.as_deref(), limit),
+            self.search_index(SearchIndex::Videos, query, filter.as_deref(), limit)
+        )?;
+
+        let mut counts = HashMap::<i64, usize>::new();
+        for distribution in [&livers, &channels, &videos].into_iter().filter_map(|results| results.facet_distribution.as_ref()) {
+            for (id, count) in distribution.get("affiliation_id").into_iter().flatten() {
+                if let Ok(id) = id.parse::<i64>() {
+                    *counts.entry(id).or_default() += count;
+                }
+            }
+        }
+        let affiliations = affiliation_facets(counts, pool).await?;
+
+        Ok(SearchResult {
+            query: query.to_owned(),
+            livers: hits(livers),
+            channels: hits(channels),
+            videos: hits(videos),
+            affiliations
+        })
+    }
+
+    async fn search_index(&self, index: SearchIndex, query: &str, filter: Option<&str>, limit: usize) -> Result<SearchResults<Map<String, Value>>, SearchError> {
+        let meili = self.client.index(index.uid());
+        let mut search = meili.search();
+        search.with_query(query)
+            .with_limit(limit)
+            .with_facets(Selectors::Some(FACETS))
+            .with_attributes_to_highlight(Selectors::Some(index.searchable()));
+        if let Some(filter) = filter {
+            search.with_filter(filter);
+        }
+        Ok(search.execute::<Map<String, Value>>().await?)
+    }
+
+    async fn configure_index(&self, uid: &str, index: SearchIndex) -> Result<(), SearchError> {
+        let task = self.client.create_index(uid, Some(index.primary_key())).await?;
+        // Fails when the index already exists, which is fine.
+        self.client.wait_for_task(task, None, Some(TASK_TIMEOUT)).await?;
+        let task = self.client.index(uid).set_settings(&index.settings()).await?;
+        self.settle(task).await
+    }
+
+    async fn add_livers(&self, uid: &str, livers: &[LiverObject]) -> Result<(), SearchError> {
+        self.add(uid, SearchIndex::Livers, &livers.iter().map(LiverDocument::from).collect::<Vec<_>>()).await
+    }
+
+    async fn add_channels(&self, uid: &str, channels: &[ChannelObject], pool: &PgPool) -> Result<(), SearchError> {
+        let liver_ids = channels.iter()
+            .filter_map(|channel| channel.liver_id().map(i64::from))
+            .collect::<Vec<_>>();
+        let livers = LiverObject::fetch_from_ids(&liver_ids, pool).await?
+            .into_iter()
+            .map(|liver| (liver.liver_id(), liver))
+            .collect::<HashMap<_, _>>();
+        let documents = channels.iter()
+            .map(|channel| {
+                let liver = channel.liver_id().and_then(|id| livers.get(&id));
+                ChannelDocument {
+                    channel_id: channel.channel_id().to_owned().into(),
+                    liver_id: channel.liver_id().map(Into::into),
+                    affiliation_id: liver.and_then(LiverObject::affiliation_id).map(Into::into),
+                    liver_name: liver.map(LiverObject::name),
+                    liver_reading: liver.map(liver_reading),
+                    description: channel.description(),
+                    logo_url: channel.logo_url()
+                }
+            })
+            .collect::<Vec<_>>();
+        self.add(uid, SearchIndex::Channels, &documents).await
+    }
+
+    async fn add_videos(&self, uid: &str, filter: &ScheduleFilter, channel_ids: Option<&[String]>, pool: &PgPool) -> Result<(), SearchError> {
+        let mut offset = 0;
+        loop {
+            let videos = ScheduledVideoObject::fetch_filtered(filter, channel_ids, None, BATCH_SIZE, offset, pool).await?;
+            self.add(uid, SearchIndex::Videos, &videos.iter().map(VideoDocument::from).collect::<Vec<_>>()).await?;
+            if (videos.len() as i64) < BATCH_SIZE {
+                return Ok(());
+            }
+            offset += BATCH_SIZE;
+        }
+    }
+
+    async fn add<T: Serialize>(&self, uid: &str, index: SearchIndex, documents: &[T]) -> Result<(), SearchError> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+        let task = self.client.index(uid).add_or_replace(documents, Some(index.primary_key())).await?;
+        self.settle(task).await
+    }
+
+    async fn delete(&self, index: SearchIndex, ids: &[String]) -> Result<(), SearchError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let task = self.client.index(index.uid()).delete_documents(ids).await?;
+        self.settle(task).await
+    }
+
+    /// Waits for the task, and turns a failed one into an error.
+    async fn settle(&self, task: TaskInfo) -> Result<(), SearchError> {
+        match self.client.wait_for_task(task, None, Some(TASK_TIMEOUT)).await? {
+            Task::Failed { content } => Err(meilisearch_sdk::errors::Error::from(content.error).into()),
+            _ => Ok(())
+        }
+    }
+}
+
+fn hits(results: SearchResults<Map<String, Value>>) -> SearchHits {
+    SearchHits {
+        estimated_total_hits: results.estimated_total_hits.unwrap_or(results.hits.len()),
+        hits: results.hits.into_iter()
+            .map(|hit| SearchHit {
+                document: Value::Object(hit.result),
+                highlighted: hit.formatted_result.map(Value::Object).unwrap_or(Value::Null)
+            })
+            .collect()
+    }
+}
@@ -1,10 +1,43 @@
 pub mod salmon;
+pub mod cage;
 pub mod meilisearch;
+pub mod search;
+pub mod normalize;
+mod pg_search;
 mod axum;
+mod tls;
 mod layer;
 
+pub use self::layer::{Authenticator, Principal, Write};
+
+/// Number set in `key`, or `default` when it is unset, malformed or not positive.
+pub(crate) fn env_or<T: std::str::FromStr + PartialOrd + Default>(key: &str, default: T) -> T {
+    dotenv::var(key)
+        .ok()
+        .and_then(|f| f.parse().ok())
+        .filter(|value| *value > T::default())
+        .unwrap_or(default)
+}
+
 #[allow(unused_must_use)]
 pub async fn server_run(pool: sqlx::PgPool) {
-    salmon::run_salmon(pool.clone()).await;
-    axum::run_webapi_server(pool.clone()).await;
+    let cache = crate::routing::ResponseCache::from_env();
+    let events = crate::routing::EventHub::new();
+    let search = search::SearchEngine::from_env();
+    let indexer = search.clone();
+    let indexer_pool = pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = indexer.configure(&indexer_pool).await {
+            tracing::warn!("failed to configure search indexes: {}", e);
+        }
+    });
+    let suggest = crate::routing::SuggestIndex::default();
+    if let Err(e) = suggest.load(&pool).await {
+        tracing::warn!("failed to load suggestions: {}", e);
+    }
+    let authenticator = layer::Authenticator::new(pool.clone(), cage::TokenKeys::from_env());
+    salmon::run_salmon(pool.clone(), cache.clone(), events.clone(), search.clone(), suggest.clone(), authenticator.clone())
+        .await
+        .expect("Salmon Server failed to start...");
+    axum::run_webapi_server(pool.clone(), cache, events, search, suggest, authenticator).await;
 }
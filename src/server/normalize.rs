@@ -0,0 +1,189 @@
+//! Normalisation of liver names for search.
+//!
+//! Names are typed in hiragana, katakana, romaji and with mixed-width characters,
+//! so both indexed names and queries are folded into one reading:
+//!
+//! 1. Width folding: full-width ASCII and half-width katakana become their usual forms.
+//! 2. Kana unification: katakana becomes hiragana, and voiced sound marks are composed.
+//! 3. Romaji transliteration: hiragana becomes Hepburn romaji.
+//!
+//! Characters other than kana, such as kanji, are kept as they are,
+//! e.g. `ペコラ`, `ぺこら`, `ﾍﾟｺﾗ` and `Ｐｅｋｏｒａ` all become `pekora`.
+
+/// Half-width katakana from U+FF66 to U+FF9D, in the order of their code points.
+const HALF_WIDTH_KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+/// Folds width, unifies kana and transliterates kana into romaji, in lower case.
+pub fn normalize(text: &str) -> String {
+    romanize(&unify_kana(text))
+}
+
+/// Folds width and turns katakana into hiragana.
+fn unify_kana(text: &str) -> String {
+    let mut unified = String::with_capacity(text.len());
+    for c in text.chars() {
+        let c = fold_width(c);
+        match c {
+            // Voiced and semi-voiced sound marks, combining or spacing.
+            '\u{3099}' | '\u{309B}' | '\u{FF9E}' => compose(&mut unified, voiced),
+            '\u{309A}' | '\u{309C}' | '\u{FF9F}' => compose(&mut unified, semi_voiced),
+            // Katakana except for the few without a hiragana counterpart.
+            'ァ'..='ヶ' => unified.push(char::from_u32(c as u32 - 0x60).unwrap_or(c)),
+            c => unified.extend(c.to_lowercase())
+        }
+    }
+    unified
+}
+
+fn fold_width(c: char) -> char {
+    match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        '\u{FF65}' => '・',
+        '\u{FF66}'..='\u{FF9D}' => HALF_WIDTH_KATAKANA.chars()
+            .nth((c as u32 - 0xFF66) as usize)
+            .unwrap_or(c),
+        c => c
+    }
+}
+
+fn compose(unified: &mut String, mark: fn(char) -> Option<char>) {
+    if let Some(marked) = unified.chars().last().and_then(mark) {
+        unified.pop();
+        unified.push(marked);
+    }
+}
+
+fn voiced(c: char) -> Option<char> {
+    match c {
+        'う' => Some('ゔ'),
+        'か' | 'き' | 'く' | 'け' | 'こ' | 'さ' | 'し' | 'す' | 'せ' | 'そ' |
+        'た' | 'ち' | 'つ' | 'て' | 'と' | 'は' | 'ひ' | 'ふ' | 'へ' | 'ほ' => char::from_u32(c as u32 + 1),
+        _ => None
+    }
+}
+
+fn semi_voiced(c: char) -> Option<char> {
+    match c {
+        'は' | 'ひ' | 'ふ' | 'へ' | 'ほ' => char::from_u32(c as u32 + 2),
+        _ => None
+    }
+}
+
+/// Transliterates hiragana into Hepburn romaji.
+fn romanize(text: &str) -> String {
+    let mut romanized = String::with_capacity(text.len() * 2);
+    let mut chars = text.chars().peekable();
+    // Set by a small tsu, doubles the consonant of the next syllable.
+    let mut geminate = false;
+
+    while let Some(c) = chars.next() {
+        let syllable = match c {
+            'っ' => {
+                geminate = true;
+                continue;
+            }
+            // Long vowel mark is dropped, so that `ぺこーら` reads as `pekora`.
+            'ー' => continue,
+            c => match syllable(c) {
+                Some(syllable) => syllable,
+                None => {
+                    geminate = false;
+                    romanized.push(c);
+                    continue;
+                }
+            }
+        };
+
+        let mut syllable = syllable.to_string();
+        match chars.peek().copied() {
+            // Contracted sounds: `きゃ` is `kya`, `しゃ` is `sha`.
+            Some(small @ ('ゃ' | 'ゅ' | 'ょ')) if syllable.len() > 1 && syllable.ends_with('i') => {
+                chars.next();
+                syllable.pop();
+                let vowel = match small { 'ゃ' => 'a', 'ゅ' => 'u', _ => 'o' };
+                if !matches!(syllable.as_str(), "sh" | "ch" | "j") {
+                    syllable.push('y');
+                }
+                syllable.push(vowel);
+            }
+            // Small vowels replace the vowel: `ふぁ` is `fa`, `てぃ` is `ti`.
+            Some(small @ ('ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ')) if syllable.len() > 1 => {
+                chars.next();
+                syllable.pop();
+                syllable.push(small_vowel(small));
+            }
+            _ => {}
+        }
+
+        if std::mem::take(&mut geminate) {
+            match syllable.as_bytes().first() {
+                Some(b'c') => romanized.push('t'),
+                Some(&consonant) if !b"aiueon".contains(&consonant) => romanized.push(consonant as char),
+                _ => {}
+            }
+        }
+        romanized.push_str(&syllable);
+    }
+    romanized
+}
+
+fn small_vowel(c: char) -> char {
+    match c {
+        'ぁ' => 'a',
+        'ぃ' => 'i',
+        'ぅ' => 'u',
+        'ぇ' => 'e',
+        _ => 'o'
+    }
+}
+
+fn syllable(c: char) -> Option<&'static str> {
+    let syllable = match c {
+        'あ' | 'ぁ' => "a", 'い' | 'ぃ' => "i", 'う' | 'ぅ' => "u", 'え' | 'ぇ' => "e", 'お' | 'ぉ' => "o",
+        'か' | 'ゕ' => "ka", 'き' => "ki", 'く' => "ku", 'け' | 'ゖ' => "ke", 'こ' => "ko",
+        'が' => "ga", 'ぎ' => "gi", 'ぐ' => "gu", 'げ' => "ge", 'ご' => "go",
+        'さ' => "sa", 'し' => "shi", 'す' => "su", 'せ' => "se", 'そ' => "so",
+        'ざ' => "za", 'じ' => "ji", 'ず' => "zu", 'ぜ' => "ze", 'ぞ' => "zo",
+        'た' => "ta", 'ち' => "chi", 'つ' => "tsu", 'て' => "te", 'と' => "to",
+        'だ' => "da", 'ぢ' => "ji", 'づ' => "zu", 'で' => "de", 'ど' => "do",
+        'な' => "na", 'に' => "ni", 'ぬ' => "nu", 'ね' => "ne", 'の' => "no",
+        'は' => "ha", 'ひ' => "hi", 'ふ' => "fu", 'へ' => "he", 'ほ' => "ho",
+        'ば' => "ba", 'び' => "bi", 'ぶ' => "bu", 'べ' => "be", 'ぼ' => "bo",
+        'ぱ' => "pa", 'ぴ' => "pi", 'ぷ' => "pu", 'ぺ' => "pe", 'ぽ' => "po",
+        'ま' => "ma", 'み' => "mi", 'む' => "mu", 'め' => "me", 'も' => "mo",
+        'や' | 'ゃ' => "ya", 'ゆ' | 'ゅ' => "yu", 'よ' | 'ょ' => "yo",
+        'ら' => "ra", 'り' => "ri", 'る' => "ru", 'れ' => "re", 'ろ' => "ro",
+        'わ' | 'ゎ' => "wa", 'ゐ' => "i", 'ゑ' => "e", 'を' => "o", 'ん' => "n",
+        'ゔ' => "vu",
+        _ => return None
+    };
+    Some(syllable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize;
+
+    #[test]
+    fn spellings_of_a_name_fold_into_one_reading() {
+        for name in ["pekora", "ぺこら", "ペコラ", "ﾍﾟｺﾗ", "Ｐｅｋｏｒａ", "PEKORA", "ぺこーら"] {
+            assert_eq!(normalize(name), "pekora", "{}", name);
+        }
+    }
+
+    #[test]
+    fn contracted_and_geminate_sounds_are_transliterated() {
+        assert_eq!(normalize("きゃ"), "kya");
+        assert_eq!(normalize("しゅ"), "shu");
+        assert_eq!(normalize("まっちゃ"), "matcha");
+        assert_eq!(normalize("ファ"), "fa");
+        assert_eq!(normalize("ｶﾞｳﾙ"), "gauru");
+    }
+
+    #[test]
+    fn other_characters_are_kept() {
+        assert_eq!(normalize("兎田ぺこら"), "兎田pekora");
+        assert_eq!(normalize("ぺこら　ch"), "pekora ch");
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+
+use crate::database::{search_object, AffiliationCountObject};
+use crate::models::{SearchHit, SearchHits, SearchResult};
+
+use super::normalize::normalize;
+use super::search::{affiliation_facets, ChannelDocument, LiverDocument, SearchError, VideoDocument};
+
+/// Searches with the full-text and trigram indexes of the database.
+pub async fn search(query: &str, affiliation_id: Option<i64>, limit: usize, pool: &PgPool) -> Result<SearchResult, SearchError> {
+    let limit = limit as i64;
+    let reading = normalize(query);
+    let ((livers, liver_counts), (channels, channel_counts), (videos, video_counts)) = futures::try_join!(
+        search_object::search_livers(query, &reading, affiliation_id, limit, pool),
+        search_object::search_channels(query, &reading, affiliation_id, limit, pool),
+        search_object::search_videos(query, affiliation_id, limit, pool)
+    )?;
+
+    let liver_hits = livers.iter()
+        .map(|liver| {
+            let document = LiverDocument::from(liver);
+            let highlighted = LiverDocument {
+                name: &highlight(document.name, query),
+                localized_name: &highlight(document.localized_name, query),
+                reading: highlight(&document.reading, &reading),
+                ..LiverDocument::from(liver)
+            };
+            hit(&document, &highlighted)
+        })
+        .collect();
+
+    let channel_hits = channels.iter()
+        .map(|matched| {
+            let channel = matched.channel();
+            let document = |liver_name, liver_reading, description| ChannelDocument {
+                channel_id: channel.channel_id().to_owned().into(),
+                liver_id: channel.liver_id().map(Into::into),
+                affiliation_id: matched.affiliation_id().map(Into::into),
+                liver_name,
+                liver_reading,
+                description,
+                logo_url: channel.logo_url()
+            };
+            let liver_name = matched.liver_name().map(|name| highlight(name, query));
+            let liver_reading = matched.liver_reading().map(|name| highlight(name, &reading));
+            hit(
+                &document(matched.liver_name(), matched.liver_reading().map(str::to_owned), channel.description()),
+                &document(liver_name.as_deref(), liver_reading, matched.highlighted_description())
+            )
+        })
+        .collect();
+
+    let video_hits = videos.iter()
+        .map(|matched| {
+            let document = VideoDocument::from(matched.video());
+            let highlighted = VideoDocument {
+                title: matched.highlighted_title(),
+                description: matched.highlighted_description(),
+                ..VideoDocument::from(matched.video())
+            };
+            hit(&document, &highlighted)
+        })
+        .collect();
+
+    let mut counts = HashMap::<i64, usize>::new();
+    for count in liver_counts.iter().chain(&channel_counts).chain(&video_counts) {
+        if let Some(id) = count.affiliation_id() {
+            *counts.entry(id.into()).or_default() += count.count() as usize;
+        }
+    }
+    let affiliations = affiliation_facets(counts, pool).await?;
+
+    Ok(SearchResult {
+        query: query.to_owned(),
+        livers: SearchHits { estimated_total_hits: total(&liver_counts), hits: liver_hits },
+        channels: SearchHits { estimated_total_hits: total(&channel_counts), hits: channel_hits },
+        videos: SearchHits { estimated_total_hits: total(&video_counts), hits: video_hits },
+        affiliations
+    })
+}
+
+fn total(counts: &[AffiliationCountObject]) -> usize {
+    counts.iter().map(|count| count.count() as usize).sum()
+}
+
+fn hit<T: Serialize>(document: &T, highlighted: &T) -> SearchHit {
+    SearchHit {
+        document: serde_json::to_value(document).unwrap_or(Value::Null),
+        highlighted: serde_json::to_value(highlighted).unwrap_or(Value::Null)
+    }
+}
+
+/// Wraps occurrences of the query in `<em>`, ignoring ASCII case.
+///
+/// Names matched only by trigram similarity are left as is.
+fn highlight(text: &str, query: &str) -> String {
+    if query.is_empty() {
+        return text.to_owned();
+    }
+    let mut highlighted = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let found = rest.char_indices()
+            .map(|(at, _)| at)
+            .find(|&at| rest.get(at..at + query.len()).map_or(false, |part| part.eq_ignore_ascii_case(query)));
+        match found {
+            Some(at) => {
+                highlighted.push_str(&rest[..at]);
+                highlighted.push_str("<em>");
+                highlighted.push_str(&rest[at..at + query.len()]);
+                highlighted.push_str("</em>");
+                rest = &rest[at + query.len()..];
+            }
+            None => {
+                highlighted.push_str(rest);
+                break;
+            }
+        }
+    }
+    highlighted
+}
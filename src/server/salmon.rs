@@ -1,23 +1,30 @@
 use std::collections::vec_deque::VecDeque;
 use std::fmt::Display;
-use std::net::ToSocketAddrs;
+use std::net::SocketAddr;
 use std::pin::Pin;
-use chrono::{DateTime, Local, TimeZone};
+use chrono::{DateTime, TimeZone, Utc};
 
 use sqlx::Postgres;
 use tokio::sync::mpsc;
 use tokio::time::Instant;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::transport::Server;
-use tonic::{Request, Response, Status, Streaming};
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tower::Layer;
+use tonic::{Code, Request, Response, Status, Streaming};
 use proto::salmon_api_server::{SalmonApiServer, SalmonApi};
 use proto::{Affiliation, Channel, Liver, Video, TaskResult, Void};
 
+use crate::routing::{Changed, EventHub, Notify, ResponseCache, Suggest, SuggestIndex};
+use crate::server::cage::{CageApiServer, CageService};
+use crate::server::layer::{Authenticator, CageLayer, Principal, Write};
+use crate::server::search::{self, Indexed, SearchEngine};
 use crate::database::{
-    Accessor, Fetch,
+    Accessor, Fetch, Scoped,
+    BatchOutcome, InitIngestionBatchObject,
     AffiliationObject,
     LiverId, LiverObject,
     ChannelId, ChannelObject, InitChannelObject,
+    LocalizedNames,
     VideoId, VideoObject, InitVideoObject
 };
 
@@ -26,12 +33,16 @@ mod proto { tonic::include_proto!("salmon"); }
 
 #[derive(Debug)]
 pub struct SalmonAutoCollector {
-    pool: sqlx::Pool<Postgres>
+    pool: sqlx::Pool<Postgres>,
+    cache: ResponseCache,
+    events: EventHub,
+    search: SearchEngine,
+    suggest: SuggestIndex
 }
 
 impl SalmonAutoCollector {
-    fn new(connection_pool: sqlx::Pool<Postgres>) -> Self {
-        Self { pool: connection_pool }
+    fn new(connection_pool: sqlx::Pool<Postgres>, cache: ResponseCache, events: EventHub, search: SearchEngine, suggest: SuggestIndex) -> Self {
+        Self { pool: connection_pool, cache, events, search, suggest }
     }
 }
 
@@ -79,11 +90,13 @@ impl SalmonApi for SalmonAutoCollector {
 
 impl SalmonAutoCollector {
     pub async fn collect<R, T>(&self, receive: Request<Streaming<R>>) -> SalmonResult<TaskResult>
-        where T: From<R> + Display + Accessor,
+        where T: From<R> + Display + Accessor + Scoped + Notify + Indexed + Suggest + Sync + 'static,
               R: DeleteFlag
     {
         use futures::StreamExt;
         let dur_now = Instant::now();
+        let principal = receive.extensions().get::<Principal>().cloned()
+            .ok_or_else(|| Status::unauthenticated("Authorization with a Cage token is required."))?;
         let collector_item = receive.into_inner()
             .map(Result::unwrap)    
             .map(|rec| (rec.flagged(), T::from(rec)))
@@ -91,24 +104,89 @@ impl SalmonAutoCollector {
             .collect::<VecDeque<(bool, T)>>()
             .await;
         tracing::info!("received data: {}ms", dur_now.elapsed().as_millis());
+        tracing::info!("{:<10} {} items from {}", yansi::Paint::green("collect"), collector_item.len(), principal);
 
+        let started_at = Utc::now();
         let dur_now = Instant::now();
+        let received = collector_item.len();
+        let written = self.write(&principal, collector_item).await;
+        let elapsed_ms = dur_now.elapsed().as_millis();
+
+        let (outcome, message) = match &written {
+            Ok(_) => (BatchOutcome::Committed, String::new()),
+            Err(status) if status.code() == Code::PermissionDenied => (BatchOutcome::Denied, status.message().to_owned()),
+            Err(status) => (BatchOutcome::Failed, status.message().to_owned())
+        };
+        let (mut inserted, mut updated, mut deleted) = (0, 0, 0);
+        for changed in written.iter().flatten() {
+            match changed {
+                Changed::Inserted(_) => inserted += 1,
+                Changed::Updated(..) => updated += 1,
+                Changed::Deleted(_) => deleted += 1
+            }
+        }
+        let batch = InitIngestionBatchObject {
+            account_id: principal.account_id,
+            user_name: principal.user_name.clone(),
+            target: T::TABLE,
+            received,
+            inserted,
+            updated,
+            deleted,
+            outcome,
+            message,
+            started_at,
+            elapsed_ms
+        };
+        match batch.record(&self.pool).await {
+            Ok(batch) => tracing::debug!("{:<10} {}", yansi::Paint::green("record"), batch),
+            Err(e) => tracing::warn!("failed to record the batch: {}", e)
+        }
+
+        let changes = written?;
+        propagate(changes, &self.pool, &self.cache, &self.events, &self.search, &self.suggest);
+
+        tracing::info!("transaction elapsed {}ms", elapsed_ms);
+        Ok(Response::new(TaskResult { message: "".to_string() }))
+    }
+
+    /// Write a batch in a single transaction, nothing of it when some of it is denied.
+    async fn write<T>(&self, principal: &Principal, collector_item: VecDeque<(bool, T)>) -> Result<Vec<Changed<T>>, Status>
+        where T: Display + Accessor + Scoped + Sync
+    {
         let mut transaction = self.pool.begin().await
             .map_err(|e| Status::failed_precondition(format!("Failed to begin build transaction: {:?}", e)))?;
 
+        let mut denied = Vec::new();
+        for (delete_flag, item) in &collector_item {
+            let write = if *delete_flag { Write::Delete } else { Write::Upsert };
+            if !principal.permits(item, write, &mut transaction).await
+                .map_err(|e| Status::internal(format!("Failed func permits: {:?}", e)))? {
+                tracing::debug!("{:<10} {}", yansi::Paint::red("denied"), item);
+                denied.push(item.to_string());
+            }
+        }
+        if !denied.is_empty() {
+            return Err(Status::permission_denied(denied_message(principal, &denied, collector_item.len())));
+        }
+
+        let mut changes = Vec::new();
+
         for (delete_flag, item) in collector_item {
             if item.exists(&mut transaction).await
                 .map_err(|e| Status::internal(format!("Failed func exists: {:?}", e)))?{
                 if delete_flag {
                     let del = item.delete(&mut transaction).await
                         .map_err(|e| Status::internal(format!("Failed func delete: {:?}", e)))?;
-                        tracing::debug!("{:<10} {}", yansi::Paint::magenta("delete"), del)
+                        tracing::debug!("{:<10} {}", yansi::Paint::magenta("delete"), del);
+                        changes.push(Changed::Deleted(del));
                 } else if !item.compare(&mut transaction).await
                     .map_err(|e| Status::internal(format!("Failed func compare: {:?}", e)))? {
                     let upd = item.update(&mut transaction).await
                         .map_err(|e| Status::internal(format!("Failed func update: {:?}", e)))?;
                         tracing::debug!("{:<10} ┌ {}", yansi::Paint::yellow("update old"), upd.0);
                         tracing::debug!("{:<10} ┕ {}", yansi::Paint::yellow("update new"), upd.1);
+                        changes.push(Changed::Updated(upd.0, upd.1));
                 } else {
                     tracing::debug!("{:<10} {}", yansi::Paint::blue("unchanged"), item)
                 }
@@ -116,6 +194,7 @@ impl SalmonAutoCollector {
                 let ins = item.insert(&mut transaction).await
                     .map_err(|e| Status::internal(format!("Failed func insert: {:?}", e)))?;
                 tracing::debug!("{:<10} {}", yansi::Paint::cyan("insert"), ins);
+                changes.push(Changed::Inserted(ins));
             } else {
                 tracing::debug!("{:<10} {}", yansi::Paint::blue("not found"), item)
             }
@@ -124,8 +203,8 @@ impl SalmonAutoCollector {
         transaction.commit().await
             .map_err(|e| Status::internal(format!("Failed to commit: {:?}", e)))?;
 
-        tracing::info!("transaction elapsed {}ms", dur_now.elapsed().as_millis());
-        Ok(Response::new(TaskResult { message: "".to_string() }))
+        tracing::info!("{:<10} {} changes by {}", yansi::Paint::green("commit"), changes.len(), principal);
+        Ok(changes)
     }
 
     pub async fn fetch<D, G>(&self) -> SalmonResult<SalmonResponseStream<G>>
@@ -155,9 +234,41 @@ impl SalmonAutoCollector {
     }
 }
 
+/// Reflect committed changes into the response cache, the suggestions, the search indexes and the event subscribers.
+pub fn propagate<T>(changes: Vec<Changed<T>>, pool: &sqlx::Pool<Postgres>, cache: &ResponseCache, events: &EventHub, search: &SearchEngine, suggest: &SuggestIndex)
+    where T: Accessor + Notify + Indexed + Suggest + Sync + 'static
+{
+    if changes.is_empty() {
+        return;
+    }
+    cache.invalidate(T::TABLE);
+    suggest.apply(&changes);
+    let (upserted, deleted) = search::document_ids(&changes);
+    let search = search.clone();
+    let search_pool = pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = search.sync(T::TABLE, upserted, deleted, &search_pool).await {
+            tracing::warn!("failed to sync search indexes: {}", e);
+        }
+    });
+    events.publish(changes, pool);
+}
+
+/// Nothing of a batch is written when some of it is denied, and the denied records are listed.
+fn denied_message(principal: &Principal, denied: &[String], total: usize) -> String {
+    const LISTED: usize = 20;
+    let mut message = format!("Denied {} of {} records for {}: {}",
+        denied.len(), total, principal, denied.iter().take(LISTED).cloned().collect::<Vec<_>>().join(", "));
+    if denied.len() > LISTED {
+        message.push_str(&format!(" and {} more", denied.len() - LISTED));
+    }
+    message
+}
+
 impl From<Affiliation> for AffiliationObject {
     fn from(data: Affiliation) -> Self {
         AffiliationObject::new(data.affiliation_id, data.name)
+            .with_names(sent_names(data.localized_names))
     }
 }
 
@@ -166,6 +277,7 @@ impl From<AffiliationObject> for Affiliation {
         Self { 
             affiliation_id: obj.affiliation_id().into(),
             name: obj.name().to_owned(),
+            localized_names: localized_names(obj.names()),
             delete: false
         }
     }
@@ -174,6 +286,7 @@ impl From<AffiliationObject> for Affiliation {
 impl From<Liver> for LiverObject {
     fn from(data: Liver) -> Self {
         LiverObject::new(data.liver_id, data.affiliation_id, data.name, data.localized_name)
+            .with_names(sent_names(data.localized_names))
     }
 }
 
@@ -184,6 +297,7 @@ impl From<LiverObject> for Liver {
             name: obj.name().to_owned(),
             localized_name: obj.localized_name().to_owned(),
             affiliation_id: obj.affiliation_id().map(Into::into),
+            localized_names: localized_names(obj.names()),
             delete: false
         }
     }
@@ -191,14 +305,13 @@ impl From<LiverObject> for Liver {
 
 impl From<Channel> for ChannelObject {
     fn from(data: Channel) -> Self {
-        let timestamp = if let Some(stamp) = data.published_at { (stamp.seconds, stamp.nanos as u32) } else { (0, 0) };
-        let date: DateTime<Local> = Local.timestamp(timestamp.0, timestamp.1);
         InitChannelObject {
             channel_id: ChannelId::new(data.channel_id),
             liver_id: data.liver_id.map(LiverId::new),
             logo_url: data.logo_url,
-            published_at: date,
+            published_at: data.published_at.and_then(from_timestamp),
             description: data.description,
+            names: sent_names(data.localized_names),
             ..Default::default()
         }.build()
     }
@@ -210,13 +323,35 @@ impl From<ChannelObject> for Channel {
             channel_id: obj.channel_id().to_owned().into(),
             liver_id: obj.liver_id().map(Into::into),
             logo_url: obj.logo_url().to_owned(),
-            published_at: Some(::prost_types::Timestamp::from(std::time::SystemTime::from(obj.published_at()))),
+            published_at: obj.published_at().map(to_timestamp),
             description: obj.description().to_owned(),
+            localized_names: localized_names(obj.names()),
             delete: false
         }
     }
 }
 
+/// Collectors can not tell no names from none sent, so stored names are left as they are for an empty map.
+fn sent_names(names: std::collections::HashMap<String, String>) -> LocalizedNames {
+    if names.is_empty() { LocalizedNames::default() } else { names.into_iter().collect() }
+}
+
+fn localized_names(names: &LocalizedNames) -> std::collections::HashMap<String, String> {
+    names.iter()
+        .map(|(locale, name)| (locale.to_owned(), name.to_owned()))
+        .collect()
+}
+
+/// Timestamps are handled in UTC, and an out of range one is taken as missing.
+fn from_timestamp(stamp: ::prost_types::Timestamp) -> Option<DateTime<Utc>> {
+    u32::try_from(stamp.nanos).ok()
+        .and_then(|nanos| Utc.timestamp_opt(stamp.seconds, nanos).single())
+}
+
+fn to_timestamp(at: DateTime<Utc>) -> ::prost_types::Timestamp {
+    ::prost_types::Timestamp::from(std::time::SystemTime::from(at))
+}
+
 impl From<Video> for VideoObject {
     fn from(data: Video) -> Self {
         let cloned = data.video_id.clone();
@@ -225,10 +360,11 @@ impl From<Video> for VideoObject {
             channel_id: data.channel_id.map(ChannelId::new),
             title: data.title,
             description: data.description,
-            published_at: data.published_at.map(|stamp| Local.timestamp(stamp.seconds, stamp.nanos as u32)),
-            updated_at: data.updated_at.map(|stamp| Local.timestamp(stamp.seconds, stamp.nanos as u32)),
-            will_start_at: data.will_start_at.map(|stamp| Local.timestamp(stamp.seconds, stamp.nanos as u32)),
-            started_at: data.started_at.map(|stamp| Local.timestamp(stamp.seconds, stamp.nanos as u32)),
+            published_at: data.published_at.and_then(from_timestamp),
+            updated_at: data.updated_at.and_then(from_timestamp),
+            will_start_at: data.will_start_at.and_then(from_timestamp),
+            started_at: data.started_at.and_then(from_timestamp),
+            ended_at: data.ended_at.and_then(from_timestamp),
             thumbnail_url: format!("https://img.youtube.com/vi/{}/maxresdefault.jpg", cloned),
             ..Default::default()
         }.build()
@@ -242,10 +378,11 @@ impl From<VideoObject> for Video {
             channel_id: obj.channel_id().map(|id| id.to_owned().into()),
             title: obj.title().to_owned(),
             description: obj.description().to_owned(),
-            published_at: obj.published_at().map(|at| ::prost_types::Timestamp::from(std::time::SystemTime::from(at))),
-            updated_at: obj.updated_at().map(|at| ::prost_types::Timestamp::from(std::time::SystemTime::from(at))),
-            will_start_at: obj.will_start_at().map(|at| ::prost_types::Timestamp::from(std::time::SystemTime::from(at))),
-            started_at: obj.started_at().map(|at| ::prost_types::Timestamp::from(std::time::SystemTime::from(at))),
+            published_at: obj.published_at().map(to_timestamp),
+            updated_at: obj.updated_at().map(to_timestamp),
+            will_start_at: obj.will_start_at().map(to_timestamp),
+            started_at: obj.started_at().map(to_timestamp),
+            ended_at: obj.ended_at().map(to_timestamp),
             delete: false
         }
     }
@@ -279,19 +416,56 @@ impl DeleteFlag for Video {
     }
 }
 
-pub async fn run_salmon(pool: sqlx::Pool<Postgres>) -> Result<(), Box<dyn std::error::Error>> {
-    let bind_ip = "[::1]:50051".to_socket_addrs()
-        .unwrap().next()
-        .unwrap();
-    let server = SalmonAutoCollector::new(pool);
+pub async fn run_salmon(pool: sqlx::Pool<Postgres>, cache: ResponseCache, events: EventHub, search: SearchEngine, suggest: SuggestIndex, authenticator: Authenticator) -> Result<(), Box<dyn std::error::Error>> {
+    let bind_ip = bind_address()?;
+    let cage = CageService::new(pool.clone(), authenticator.clone());
+    let server = SalmonAutoCollector::new(pool, cache, events, search, suggest);
+    let mut builder = Server::builder();
+    if let Some(tls) = tls_config()? {
+        builder = builder.tls_config(tls)?;
+    }
     tokio::spawn(async move {
-        tracing::debug!("listening salmon autocollector from {}", bind_ip);
-        Server::builder()
-            .add_service(SalmonApiServer::new(server))
+        tracing::debug!("listening salmon autocollector and cage from {}", bind_ip);
+        builder
+            .add_service(CageLayer::new(authenticator).layer(SalmonApiServer::new(server)))
+            .add_service(CageApiServer::new(cage))
             .serve(bind_ip)
             .await
             .expect("Salmon Server failed to start...")
     });
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Address of Salmon and Cage from `SALMON_BIND`, only reachable from the same host by default.
+fn bind_address() -> Result<SocketAddr, String> {
+    match dotenv::var("SALMON_BIND") {
+        Ok(address) => address.parse()
+            .map_err(|_| format!("SALMON_BIND {} is not an address like [::]:50051.", address)),
+        Err(_) => Ok(SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, 50051)))
+    }
+}
+
+/// TLS of the Salmon listener, served when `SALMON_TLS_CERT` and `SALMON_TLS_KEY` are set to PEM files.
+///
+/// With `SALMON_TLS_CLIENT_CA` also set, clients must present a certificate signed by that CA,
+/// and those without a token are authenticated as the account mapped to the common name of the certificate.
+fn tls_config() -> Result<Option<ServerTlsConfig>, Box<dyn std::error::Error>> {
+    let (cert, key) = match (dotenv::var("SALMON_TLS_CERT"), dotenv::var("SALMON_TLS_KEY")) {
+        (Ok(cert), Ok(key)) => (cert, key),
+        (Err(_), Err(_)) => return Ok(None),
+        _ => return Err("SALMON_TLS_CERT and SALMON_TLS_KEY must be set together.".into())
+    };
+    let read = |path: &str| std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e));
+    let mut tls = ServerTlsConfig::new()
+        .identity(Identity::from_pem(read(&cert)?, read(&key)?));
+    match dotenv::var("SALMON_TLS_CLIENT_CA") {
+        Ok(ca) => {
+            tls = tls.client_ca_root(Certificate::from_pem(read(&ca)?));
+            tracing::info!("salmon requires client certificates signed by {}", ca);
+        }
+        Err(_) => tracing::info!("salmon serves tls with {}", cert)
+    }
+    Ok(Some(tls))
+}
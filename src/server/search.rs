@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::database::{
+    search_object,
+    Fetch, Table,
+    AffiliationObject,
+    LiverObject,
+    ChannelObject,
+    VideoObject,
+    ScheduledVideoObject
+};
+use crate::models::{AffiliationFacet, SearchResult};
+use crate::routing::Changed;
+
+use super::meilisearch::SearchIndexer;
+use super::normalize::normalize;
+use super::pg_search;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SearchError {
+    #[error(transparent)]
+    Meilisearch(#[from] meilisearch_sdk::errors::Error),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error)
+}
+
+/// Search backend selected by `SEARCH_BACKEND`.
+#[derive(Debug, Clone)]
+pub enum SearchEngine {
+    /// Separate Meilisearch instance, kept in sync on every Salmon commit.
+    Meilisearch(SearchIndexer),
+    /// Full-text and trigram indexes of the database itself.
+    Postgres
+}
+
+impl SearchEngine {
+    /// `SEARCH_BACKEND` is either `meilisearch` (default) or `postgres`.
+    pub fn from_env() -> Self {
+        match dotenv::var("SEARCH_BACKEND").ok().as_deref() {
+            Some("postgres") => SearchEngine::Postgres,
+            Some("meilisearch") | None => SearchEngine::Meilisearch(SearchIndexer::from_env()),
+            Some(other) => {
+                tracing::warn!("unknown SEARCH_BACKEND {}, falling back to meilisearch.", other);
+                SearchEngine::Meilisearch(SearchIndexer::from_env())
+            }
+        }
+    }
+
+    pub async fn configure(&self, pool: &PgPool) -> Result<(), SearchError> {
+        match self {
+            SearchEngine::Meilisearch(indexer) => indexer.configure().await,
+            // Indexes are created by migrations, only readings of livers added since then are missing.
+            SearchEngine::Postgres => {
+                let livers = search_object::fetch_livers_without_reading(pool).await?;
+                write_readings(&livers, pool).await
+            }
+        }
+    }
+
+    pub async fn reindex(&self, pool: &PgPool) -> Result<(), SearchError> {
+        match self {
+            SearchEngine::Meilisearch(indexer) => indexer.reindex(pool).await,
+            // Search vectors are generated columns, only readings are written by us.
+            SearchEngine::Postgres => {
+                let livers = <LiverObject as Fetch>::fetch_all(pool).await?;
+                write_readings(&livers, pool).await?;
+                tracing::info!("{:<10} {}", yansi::Paint::green("reindexed"), "liver_readings");
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn sync(&self, table: Table, upserted: Vec<String>, deleted: Vec<String>, pool: &PgPool) -> Result<(), SearchError> {
+        match self {
+            SearchEngine::Meilisearch(indexer) => indexer.sync(table, upserted, deleted, pool).await,
+            // Search vectors are generated columns, updated with the row itself,
+            // and readings of deleted livers are deleted by the foreign key.
+            SearchEngine::Postgres => match table {
+                Table::Livers => {
+                    let ids = upserted.iter()
+                        .filter_map(|id| id.parse::<i64>().ok())
+                        .collect::<Vec<_>>();
+                    let livers = LiverObject::fetch_from_ids(&ids, pool).await?;
+                    write_readings(&livers, pool).await
+                }
+                _ => Ok(())
+            }
+        }
+    }
+
+    /// Searches livers, channels and videos at once.
+    pub async fn search(&self, query: &str, affiliation_id: Option<i64>, limit: usize, pool: &PgPool) -> Result<SearchResult, SearchError> {
+        match self {
+            SearchEngine::Meilisearch(indexer) => indexer.search(query, affiliation_id, limit, pool).await,
+            SearchEngine::Postgres => pg_search::search(query, affiliation_id, limit, pool).await
+        }
+    }
+}
+
+/// Normalised reading of the name and the localized name of a liver,
+/// so that it is found whether typed in kana, romaji or full-width characters.
+pub(super) fn liver_reading(liver: &LiverObject) -> String {
+    format!("{} {}", normalize(liver.name()), normalize(liver.localized_name()))
+}
+
+async fn write_readings(livers: &[LiverObject], pool: &PgPool) -> Result<(), SearchError> {
+    if livers.is_empty() {
+        return Ok(());
+    }
+    let ids = livers.iter().map(|liver| i64::from(liver.liver_id())).collect::<Vec<_>>();
+    let readings = livers.iter().map(liver_reading).collect::<Vec<_>>();
+    search_object::upsert_liver_readings(&ids, &readings, pool).await?;
+    Ok(())
+}
+
+/// Rows that are reflected into the search indexes.
+pub trait Indexed {
+    /// Primary key of the row, used as the id of its document.
+    fn document_id(&self) -> String;
+}
+
+impl Indexed for AffiliationObject {
+    fn document_id(&self) -> String {
+        i64::from(self.affiliation_id()).to_string()
+    }
+}
+
+impl Indexed for LiverObject {
+    fn document_id(&self) -> String {
+        i64::from(self.liver_id()).to_string()
+    }
+}
+
+impl Indexed for ChannelObject {
+    fn document_id(&self) -> String {
+        self.channel_id().to_owned().into()
+    }
+}
+
+impl Indexed for VideoObject {
+    fn document_id(&self) -> String {
+        self.video_id().to_owned().into()
+    }
+}
+
+/// Splits committed changes into ids of documents to (re)index and ids of documents to remove.
+pub fn document_ids<T: Indexed>(changes: &[Changed<T>]) -> (Vec<String>, Vec<String>) {
+    let mut upserted = Vec::new();
+    let mut deleted = Vec::new();
+    for change in changes {
+        match change {
+            Changed::Inserted(new) | Changed::Updated(_, new) => upserted.push(new.document_id()),
+            Changed::Deleted(old) => deleted.push(old.document_id())
+        }
+    }
+    (upserted, deleted)
+}
+
+/// Search hits look the same whichever backend found them.
+#[derive(Debug, Serialize)]
+pub(super) struct LiverDocument<'a> {
+    pub(super) liver_id: i64,
+    pub(super) affiliation_id: Option<i64>,
+    pub(super) name: &'a str,
+    pub(super) localized_name: &'a str,
+    pub(super) reading: String
+}
+
+impl<'a> From<&'a LiverObject> for LiverDocument<'a> {
+    fn from(liver: &'a LiverObject) -> Self {
+        Self {
+            liver_id: liver.liver_id().into(),
+            affiliation_id: liver.affiliation_id().map(Into::into),
+            name: liver.name(),
+            localized_name: liver.localized_name(),
+            reading: liver_reading(liver)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct ChannelDocument<'a> {
+    pub(super) channel_id: String,
+    pub(super) liver_id: Option<i64>,
+    pub(super) affiliation_id: Option<i64>,
+    pub(super) liver_name: Option<&'a str>,
+    pub(super) liver_reading: Option<String>,
+    pub(super) description: &'a str,
+    pub(super) logo_url: &'a str
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct VideoDocument<'a> {
+    pub(super) video_id: String,
+    pub(super) channel_id: Option<String>,
+    pub(super) liver_id: Option<i64>,
+    pub(super) affiliation_id: Option<i64>,
+    pub(super) liver_name: Option<&'a str>,
+    pub(super) title: &'a str,
+    pub(super) description: &'a str,
+    pub(super) thumbnail_url: &'a str,
+    pub(super) status: &'static str,
+    /// Seconds from the epoch, so that the attribute is sortable.
+    pub(super) will_start_at: Option<i64>,
+    pub(super) published_at: Option<i64>
+}
+
+impl<'a> From<&'a ScheduledVideoObject> for VideoDocument<'a> {
+    fn from(scheduled: &'a ScheduledVideoObject) -> Self {
+        let video = scheduled.video();
+        let status = match (video.will_start_at(), video.started_at()) {
+            (_, Some(_)) if video.ended_at().is_some() => "uploaded",
+            (_, Some(_)) => "live",
+            (Some(_), None) => "upcoming",
+            (None, None) => "uploaded"
+        };
+        Self {
+            video_id: video.video_id().to_owned().into(),
+            channel_id: video.channel_id().map(|id| id.to_owned().into()),
+            liver_id: scheduled.liver_id().map(Into::into),
+            affiliation_id: scheduled.affiliation_id().map(Into::into),
+            liver_name: scheduled.liver_name(),
+            title: video.title(),
+            description: video.description(),
+            thumbnail_url: video.thumbnail_url(),
+            status,
+            will_start_at: video.will_start_at().map(|at| at.timestamp()),
+            published_at: video.published_at().map(|at| at.timestamp())
+        }
+    }
+}
+
+/// Resolves names of the affiliations, most matched first.
+pub(super) async fn affiliation_facets(counts: HashMap<i64, usize>, pool: &PgPool) -> Result<Vec<AffiliationFacet>, sqlx::Error> {
+    let ids = counts.keys().copied().collect::<Vec<_>>();
+    let names = AffiliationObject::fetch_from_ids(&ids, pool).await?
+        .into_iter()
+        .map(|affiliation| (i64::from(affiliation.affiliation_id()), affiliation.name().to_owned()))
+        .collect::<HashMap<_, _>>();
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(a_id, a_count), (b_id, b_count)| b_count.cmp(a_count).then(a_id.cmp(b_id)));
+    Ok(counts.into_iter()
+        .map(|(id, count)| AffiliationFacet { affiliation_id: id.into(), name: names.get(&id).cloned(), count })
+        .collect())
+}
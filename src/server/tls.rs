@@ -0,0 +1,105 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use axum::Router;
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_server::tls_rustls::RustlsConfig;
+
+/// Files are checked this often, and reloaded when either of them was modified.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Certificate chain and private key of the REST listener, from `WEBAPI_TLS_CERT` and `WEBAPI_TLS_KEY` (PEM files).
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    cert: PathBuf,
+    key: PathBuf
+}
+
+impl TlsFiles {
+    /// `None` when neither is set, so that plain HTTP is served.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match (dotenv::var("WEBAPI_TLS_CERT"), dotenv::var("WEBAPI_TLS_KEY")) {
+            (Ok(cert), Ok(key)) => Ok(Some(Self { cert: cert.into(), key: key.into() })),
+            (Err(_), Err(_)) => Ok(None),
+            _ => Err("WEBAPI_TLS_CERT and WEBAPI_TLS_KEY must be set together.".to_owned())
+        }
+    }
+
+    pub async fn load(&self) -> std::io::Result<RustlsConfig> {
+        RustlsConfig::from_pem_file(&self.cert, &self.key).await
+    }
+
+    /// Reload `config` when the files change on disk, such as when a certificate is renewed.
+    ///
+    /// A pair that fails to load is logged and the previous one is kept, so that a half-written renewal does not stop the server.
+    pub fn watch(self, config: RustlsConfig) {
+        tokio::spawn(async move {
+            let mut loaded = self.modified();
+            let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+            loop {
+                interval.tick().await;
+                let modified = self.modified();
+                if modified == loaded {
+                    continue;
+                }
+                match config.reload_from_pem_file(&self.cert, &self.key).await {
+                    Ok(()) => {
+                        tracing::info!("reloaded tls certificate from {}", self.cert.display());
+                        loaded = modified;
+                    }
+                    Err(e) => tracing::warn!("failed to reload tls certificate, keeping the previous one: {}", e)
+                }
+            }
+        });
+    }
+
+    fn modified(&self) -> (Option<SystemTime>, Option<SystemTime>) {
+        let modified = |path: &PathBuf| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        (modified(&self.cert), modified(&self.key))
+    }
+}
+
+/// Address of the listener redirecting HTTP to HTTPS, from `WEBAPI_HTTP_REDIRECT_BIND`,
+/// or `WEBAPI_HTTP_REDIRECT_PORT` on the ip the HTTPS listener is bound to.
+pub fn redirect_address(https_address: SocketAddr) -> Result<Option<SocketAddr>, String> {
+    if let Ok(address) = dotenv::var("WEBAPI_HTTP_REDIRECT_BIND") {
+        return address.parse()
+            .map(Some)
+            .map_err(|_| format!("WEBAPI_HTTP_REDIRECT_BIND {} is not an address like 0.0.0.0:80.", address));
+    }
+    match dotenv::var("WEBAPI_HTTP_REDIRECT_PORT") {
+        Ok(port) => port.parse()
+            .map(|port| Some(SocketAddr::new(https_address.ip(), port)))
+            .map_err(|_| format!("WEBAPI_HTTP_REDIRECT_PORT {} is not a port.", port)),
+        Err(_) => Ok(None)
+    }
+}
+
+/// Answer every request on `bind_address` with a permanent redirect to the same path on `https_port`.
+pub async fn redirect_to_https(bind_address: SocketAddr, https_port: u16) {
+    let app = Router::new()
+        .fallback(move |headers: HeaderMap, uri: Uri| async move { redirect(&headers, &uri, https_port) });
+    tracing::debug!("redirecting http on {} to https", bind_address);
+    axum::Server::bind(&bind_address)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(super::axum::exit())
+        .await
+        .unwrap_or_else(|_| panic!("Cannot startup http redirect server!"))
+}
+
+fn redirect(headers: &HeaderMap, uri: &Uri, https_port: u16) -> Response {
+    let host = headers.get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<axum::http::uri::Authority>().ok());
+    let host = match host {
+        Some(host) => host,
+        None => return (StatusCode::BAD_REQUEST, "Host header is required.").into_response()
+    };
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let location = match https_port {
+        443 => format!("https://{}{}", host.host(), path),
+        port => format!("https://{}:{}{}", host.host(), port, path)
+    };
+    Redirect::permanent(&location).into_response()
+}